@@ -0,0 +1,159 @@
+//! compiles HLSL with FXC through `D3DCompile2`. only available with the `fxc` feature, FXC only
+//! exists on windows.
+use std::cell::RefCell;
+use std::ffi::{c_void, CStr};
+use std::fs::{canonicalize, read};
+use std::path::{Path, PathBuf};
+use std::ptr::null;
+use std::slice::from_raw_parts;
+
+use windows::core::{Error, HRESULT, PCSTR};
+use windows::Win32::Graphics::Direct3D::{D3D_INCLUDE_LOCAL, D3D_INCLUDE_TYPE, D3D_SHADER_MACRO, ID3DBlob, ID3DInclude, ID3DInclude_Impl};
+use windows::Win32::Graphics::Direct3D::Fxc::D3DCompile2;
+
+const E_FAIL: HRESULT = HRESULT(0x80004005_u32 as i32);
+
+/// resolves `#include` for [compile].
+///
+/// `#include "name"` is looked up in the in-memory files, then next to the including file (the
+/// directory of `src_name` for the main source), then in the include directories in order.
+/// `#include <name>` skips the directory of the including file.
+pub struct IncludeHandler {
+    files: Vec<(String, Vec<u8>)>,
+    include_dirs: Vec<PathBuf>,
+    root: PathBuf,
+    /// contents handed to the compiler and the directory nested includes resolve against.
+    opened: RefCell<Vec<(Box<[u8]>, PathBuf)>>,
+    resolved: RefCell<Vec<PathBuf>>,
+}
+
+impl IncludeHandler {
+    /// `files` are in-memory files by include name. `root` is the directory includes of the main
+    /// source are resolved against, usually the directory of `src_name`.
+    pub fn new(files: Vec<(String, Vec<u8>)>, include_dirs: Vec<PathBuf>, root: PathBuf) -> Self {
+        Self { files, include_dirs, root, opened: RefCell::new(Vec::new()), resolved: RefCell::new(Vec::new()) }
+    }
+
+    /// files read from disk so far, canonicalized where possible.
+    pub fn resolved(&self) -> Vec<PathBuf> {
+        self.resolved.borrow().clone()
+    }
+
+    fn resolve(&self, kind: D3D_INCLUDE_TYPE, name: &str, parent: *const c_void) -> Option<(Vec<u8>, PathBuf)> {
+        let normalized = name.replace('\\', "/");
+        if let Some((_, data)) = self.files.iter().find(|(file, _)| file.replace('\\', "/") == normalized) {
+            return Some((data.clone(), self.root.clone()));
+        }
+
+        let parent_dir = self.opened.borrow().iter()
+            .find(|(data, _)| data.as_ptr() as *const c_void == parent)
+            .map(|(_, dir)| dir.clone())
+            .unwrap_or_else(|| self.root.clone());
+        let local = if kind == D3D_INCLUDE_LOCAL { Some(&parent_dir) } else { None };
+        for dir in local.into_iter().chain(&self.include_dirs) {
+            let path = dir.join(name);
+            if let Ok(data) = read(&path) {
+                let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                self.resolved.borrow_mut().push(canonicalize(&path).unwrap_or(path));
+                return Some((data, dir));
+            }
+        }
+        None
+    }
+}
+
+impl ID3DInclude_Impl for IncludeHandler {
+    // the signature is given by `ID3DInclude_Impl`, the compiler passes valid out pointers
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn Open(&self, includetype: D3D_INCLUDE_TYPE, pfilename: &PCSTR, pparentdata: *const c_void, ppdata: *mut *mut c_void, pbytes: *mut u32) -> windows::core::Result<()> {
+        let name = unsafe { pfilename.to_string() }.map_err(|_| Error::from(E_FAIL))?;
+        let (data, dir) = self.resolve(includetype, &name, pparentdata).ok_or_else(|| Error::from(E_FAIL))?;
+        let data = data.into_boxed_slice();
+        unsafe {
+            *ppdata = data.as_ptr() as *mut c_void;
+            *pbytes = data.len() as u32;
+        }
+        // kept alive until the handler is dropped, the compiler may still refer to parent data
+        self.opened.borrow_mut().push((data, dir));
+        Ok(())
+    }
+
+    fn Close(&self, _pdata: *const c_void) -> windows::core::Result<()> {
+        Ok(())
+    }
+}
+
+/// parameters of `D3DCompile2`.
+pub struct FxcInput<'a> {
+    pub src: &'a [u8],
+    /// used in messages and as the directory `#include` is resolved against.
+    pub src_name: Option<&'a CStr>,
+    pub defines: &'a [(&'a CStr, &'a CStr)],
+    pub entry_point: &'a CStr,
+    pub target: &'a CStr,
+    pub flags1: u32,
+    pub flags2: u32,
+    pub secondary_data_flags: u32,
+    pub secondary_data: &'a [u8],
+    pub include: &'a IncludeHandler,
+}
+
+fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    unsafe { from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()) }
+}
+
+/// compile `input` to bytecode. on failure returns the compiler log, or a description of the
+/// failed call when the compiler produced no log.
+pub fn compile(input: &FxcInput) -> Result<Vec<u8>, String> {
+    let input_name = PCSTR({
+        if let Some(name) = input.src_name {
+            name.as_ptr()
+        } else {
+            null()
+        }
+    } as _);
+    let secondary_data: *const u8 = {
+        if !input.secondary_data.is_empty() {
+            input.secondary_data.as_ptr()
+        } else {
+            null()
+        }
+    };
+
+    let mut defines: Vec<D3D_SHADER_MACRO> = input.defines.iter().map(|(name, def)| {
+        D3D_SHADER_MACRO {
+            Name: PCSTR(name.as_ptr() as _),
+            Definition: PCSTR(def.as_ptr() as _),
+        }
+    }).collect();
+    defines.push(D3D_SHADER_MACRO {
+        Name: PCSTR(null()),
+        Definition: PCSTR(null()),
+    });
+
+    let mut error_msgs: Option<ID3DBlob> = None;
+    let mut shader_bytes: Option<ID3DBlob> = None;
+
+
+    let include = ID3DInclude::new(input.include);
+    let result = unsafe {
+        D3DCompile2(
+            input.src.as_ptr() as _,
+            input.src.len(), input_name, Some(defines.as_ptr())
+            , &*include, PCSTR(input.entry_point.as_ptr() as _),
+            PCSTR(input.target.as_ptr() as _), input.flags1, input.flags2, input.secondary_data_flags,
+            Some(secondary_data as _),
+            input.secondary_data.len(), &mut shader_bytes, Some(&mut error_msgs))
+    };
+    if let Err(e) = result {
+        return Err(match error_msgs {
+            Some(error_msgs) => String::from_utf8_lossy(blob_bytes(&error_msgs)).into_owned(),
+            None => format!("failed to compile shader. {:?}", e),
+        });
+    }
+
+    match shader_bytes {
+        Some(shader_bytes) => Ok(blob_bytes(&shader_bytes).to_vec()),
+        None => Err("compilation succeeded but no bytes were returned!".to_owned()),
+    }
+}
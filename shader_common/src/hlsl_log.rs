@@ -0,0 +1,107 @@
+//! parses the log of the HLSL compiler.
+//!
+//! FXC reports errors as `file(line,column): error X0000: message`, with `column` sometimes given as
+//! a range like `5-9`. DXC uses the clang format `file:line:column: error: message`.
+
+#[cfg(test)]
+mod test {
+    use super::{HlslMessage, parse_log};
+
+    const LOG: &str = "C:\\shaders\\blur_ps.hlsl(3,5-7): error X3004: undeclared identifier 'foo'
+C:\\shaders\\blur_ps.hlsl(4,1): warning X3206: implicit truncation of vector type
+C:\\Program Files (x86)\\common.hlsli(12,9): error X3000: syntax error: unexpected token '}'
+";
+
+    #[test]
+    fn test_parse_log() {
+        let messages = parse_log(LOG);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], HlslMessage {
+            file: "C:\\shaders\\blur_ps.hlsl".to_owned(),
+            line: 3,
+            column: 5,
+            message: "error X3004: undeclared identifier 'foo'".to_owned(),
+        });
+        // parentheses inside the path
+        assert_eq!(messages[1].file, "C:\\Program Files (x86)\\common.hlsli");
+        assert_eq!((messages[1].line, messages[1].column), (12, 9));
+
+        assert!(parse_log("error X3501: 'main': entrypoint not found").is_empty());
+    }
+
+    const DXC_LOG: &str = "C:\\shaders\\reduce_cs.hlsl:3:12: error: use of undeclared identifier 'foo'
+    return foo;
+           ^
+C:\\shaders\\reduce_cs.hlsl:5:1: warning: implicit truncation of vector type [-Wconversion]
+./common.hlsli:12:9: error: expected ';' after expression
+hlsl.hlsl:1:1: note: previous definition is here
+";
+
+    #[test]
+    fn test_parse_dxc_log() {
+        let messages = parse_log(DXC_LOG);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], HlslMessage {
+            file: "C:\\shaders\\reduce_cs.hlsl".to_owned(),
+            line: 3,
+            column: 12,
+            message: "error: use of undeclared identifier 'foo'".to_owned(),
+        });
+        assert_eq!(messages[1].file, "./common.hlsli");
+        assert_eq!((messages[1].line, messages[1].column), (12, 9));
+
+        assert!(parse_log("error: validation errors").is_empty());
+    }
+}
+
+/// an error entry of the compiler log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HlslMessage {
+    /// file as reported by the compiler. empty for sources compiled without a name.
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// e.g. `error X3004: undeclared identifier 'foo'`
+    pub message: String,
+}
+
+/// parse the error entries of an FXC or DXC log. warnings, notes and lines without a location are
+/// skipped.
+pub fn parse_log(log: &str) -> Vec<HlslMessage> {
+    log.lines().filter_map(parse_line).filter(|m| m.message.starts_with("error")).collect()
+}
+
+fn parse_line(line: &str) -> Option<HlslMessage> {
+    parse_fxc_line(line).or_else(|| parse_dxc_line(line))
+}
+
+/// `file(line,column): message`
+fn parse_fxc_line(line: &str) -> Option<HlslMessage> {
+    let end = line.find("): ")?;
+    let start = line[..end].rfind('(')?;
+    let mut location = line[start + 1..end].split(',');
+    let line_no = location.next()?.trim().parse().ok()?;
+    let column = location.next()?.split('-').next()?.trim().parse().ok()?;
+    Some(HlslMessage {
+        file: line[..start].trim().to_owned(),
+        line: line_no,
+        column,
+        message: line[end + 3..].trim().to_owned(),
+    })
+}
+
+/// `file:line:column: message`. the file may contain `:` itself, e.g. a drive letter.
+fn parse_dxc_line(line: &str) -> Option<HlslMessage> {
+    line.match_indices(':').find_map(|(start, _)| {
+        let mut parts = line[start + 1..].splitn(3, ':');
+        let line_no = parts.next()?.parse().ok()?;
+        let column = parts.next()?.parse().ok()?;
+        let message = parts.next()?.strip_prefix(' ')?;
+        Some(HlslMessage {
+            file: line[..start].trim().to_owned(),
+            line: line_no,
+            column,
+            message: message.trim().to_owned(),
+        })
+    })
+}
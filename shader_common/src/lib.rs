@@ -0,0 +1,7 @@
+//! HLSL compiling, compiler log parsing and shader reflection shared by `shader_macro` and
+//! `dxfilter`, so shaders compiled at build time and at runtime behave the same.
+//!
+#[cfg(feature = "fxc")]
+pub mod fxc;
+pub mod hlsl_log;
+pub mod reflection;
@@ -0,0 +1,382 @@
+//! reads resource bindings, constant buffer layouts and signatures out of compiled shader bytecode.
+//!
+//! bytecode is a DXBC container: a header followed by chunks. `RDEF` describes bound resources and
+//! constant buffers, `ISGN`/`OSGN` (or their SM 5 variants) the input and output signatures. this
+//! only reads the container, so it works for blobs from FXC as well as precompiled ones on hosts
+//! without the compiler.
+
+#[cfg(test)]
+mod test {
+    use super::{BoundResource, ConstantBufferLayout, ConstantVariable, parse, ResourceKind, SignatureElement};
+
+    /// writes DXBC containers with the layout FXC produces.
+    struct Chunk {
+        fourcc: &'static [u8; 4],
+        data: Vec<u8>,
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// appends a nul terminated string, returns its offset.
+    fn push_str(data: &mut Vec<u8>, s: &str) -> u32 {
+        let offset = data.len() as u32;
+        data.extend_from_slice(s.as_bytes());
+        data.push(0);
+        offset
+    }
+
+    fn container(chunks: &[Chunk]) -> Vec<u8> {
+        let header_size = 32 + 4 * chunks.len();
+        let mut offsets = Vec::new();
+        let mut body = Vec::new();
+        for chunk in chunks {
+            offsets.push((header_size + body.len()) as u32);
+            body.extend_from_slice(chunk.fourcc);
+            body.extend(u32s(&[chunk.data.len() as u32]));
+            body.extend_from_slice(&chunk.data);
+        }
+        let mut out = b"DXBC".to_vec();
+        out.extend([0u8; 16]);
+        out.extend(u32s(&[1, (header_size + body.len()) as u32, chunks.len() as u32]));
+        out.extend(u32s(&offsets));
+        out.extend(body);
+        out
+    }
+
+    /// `RDEF` of a ps_5_0 shader with `Texture2D tx : register(t0)`, `SamplerState s : register(s1)`
+    /// and `cbuffer Params : register(b2) { float2 size; float strength; float pad; }`.
+    fn rdef() -> Vec<u8> {
+        // header, RD11 header, 3 bindings of 32 bytes, 1 cbuffer of 24 bytes, 3 variables of 40 bytes
+        let bindings_offset = 60u32;
+        let cbuffers_offset = bindings_offset + 3 * 32;
+        let variables_offset = cbuffers_offset + 24;
+        let strings_offset = variables_offset + 3 * 40;
+
+        let mut strings = Vec::new();
+        let mut name = |s: &str| strings_offset + push_str(&mut strings, s);
+        let (tx, sampler, params) = (name("tx"), name("s"), name("Params"));
+        let (size, strength, pad) = (name("size"), name("strength"), name("pad"));
+        let creator = name("Microsoft (R) HLSL Shader Compiler 10.1");
+
+        let mut data = u32s(&[1, cbuffers_offset, 3, bindings_offset, 0xffff_0500, 0, creator]);
+        data.extend(u32s(&[u32::from_le_bytes(*b"RD11"), 60, 24, 32, 40, 36, 12, 0]));
+        data.extend(u32s(&[sampler, 3, 0, 0, 0, 1, 1, 0]));
+        data.extend(u32s(&[tx, 2, 5, 4, 0xffff_ffff, 0, 1, 12]));
+        data.extend(u32s(&[params, 0, 0, 0, 0, 2, 1, 0]));
+        data.extend(u32s(&[params, 3, variables_offset, 16, 0, 0]));
+        for (name, offset, size) in [(size, 0, 8), (strength, 8, 4), (pad, 12, 4)] {
+            data.extend(u32s(&[name, offset, size, 2, 0, 0, 0xffff_ffff, 0, 0xffff_ffff, 0]));
+        }
+        data.extend(strings);
+        data
+    }
+
+    /// signature chunk with `(semantic, index, register, mask)` elements of `stride` bytes.
+    fn signature(elements: &[(&str, u32, u32, u8)], stride: u32) -> Vec<u8> {
+        let strings_offset = 8 + stride * elements.len() as u32;
+        let mut strings = Vec::new();
+        let mut data = u32s(&[elements.len() as u32, 8]);
+        for &(semantic, index, register, mask) in elements {
+            let name = strings_offset + push_str(&mut strings, semantic);
+            if stride >= 28 {
+                data.extend(u32s(&[0]));
+            }
+            data.extend(u32s(&[name, index, 0, 3, register]));
+            data.extend([mask, mask, 0, 0]);
+            if stride == 32 {
+                data.extend(u32s(&[0]));
+            }
+        }
+        data.extend(strings);
+        data
+    }
+
+    #[test]
+    fn test_parse() {
+        let blob = container(&[
+            Chunk { fourcc: b"RDEF", data: rdef() },
+            Chunk { fourcc: b"ISGN", data: signature(&[("SV_POSITION", 0, 0, 0xf), ("TEXCOORD", 0, 1, 0x3)], 24) },
+            Chunk { fourcc: b"OSGN", data: signature(&[("SV_Target", 0, 0, 0xf)], 24) },
+            Chunk { fourcc: b"SHEX", data: vec![0; 8] },
+        ]);
+        let reflection = parse(&blob).unwrap();
+        assert_eq!(reflection.resources, vec![
+            BoundResource { name: "s".to_owned(), kind: ResourceKind::Sampler, slot: 1, count: 1, space: 0 },
+            BoundResource { name: "tx".to_owned(), kind: ResourceKind::Texture, slot: 0, count: 1, space: 0 },
+            BoundResource { name: "Params".to_owned(), kind: ResourceKind::ConstantBuffer, slot: 2, count: 1, space: 0 },
+        ]);
+        assert_eq!(reflection.constant_buffers, vec![ConstantBufferLayout {
+            name: "Params".to_owned(),
+            size: 16,
+            variables: vec![
+                ConstantVariable { name: "size".to_owned(), offset: 0, size: 8 },
+                ConstantVariable { name: "strength".to_owned(), offset: 8, size: 4 },
+                ConstantVariable { name: "pad".to_owned(), offset: 12, size: 4 },
+            ],
+        }]);
+        assert_eq!(reflection.inputs, vec![
+            SignatureElement { semantic: "SV_POSITION".to_owned(), semantic_index: 0, register: 0, mask: 0xf },
+            SignatureElement { semantic: "TEXCOORD".to_owned(), semantic_index: 0, register: 1, mask: 0x3 },
+        ]);
+        assert_eq!(reflection.outputs, vec![SignatureElement { semantic: "SV_Target".to_owned(), semantic_index: 0, register: 0, mask: 0xf }]);
+    }
+
+    #[test]
+    fn test_parse_signature_variants() {
+        // geometry shader outputs carry a stream, SM 5.1 signatures also a min precision
+        for (fourcc, stride) in [(b"OSG5", 28), (b"OSG1", 32)] {
+            let blob = container(&[
+                Chunk { fourcc: b"RDEF", data: rdef() },
+                Chunk { fourcc, data: signature(&[("COLOR", 2, 3, 0x7)], stride) },
+            ]);
+            let outputs = parse(&blob).unwrap().outputs;
+            assert_eq!(outputs, vec![SignatureElement { semantic: "COLOR".to_owned(), semantic_index: 2, register: 3, mask: 0x7 }], "{:?}", fourcc);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(b"").unwrap_err().contains("not DXBC"));
+        assert!(parse(&[0; 64]).unwrap_err().contains("not DXBC"));
+
+        // stripped of reflection data
+        let blob = container(&[Chunk { fourcc: b"SHEX", data: vec![0; 8] }]);
+        assert!(parse(&blob).unwrap_err().contains("no RDEF"));
+
+        // chunk offset and resource table past the end
+        let mut blob = container(&[Chunk { fourcc: b"RDEF", data: rdef() }]);
+        blob[35] = 0xff;
+        assert!(parse(&blob).is_err());
+        let mut data = rdef();
+        data[15] = 0xff;
+        assert!(parse(&container(&[Chunk { fourcc: b"RDEF", data }])).is_err());
+    }
+}
+
+/// register class a resource is bound to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResourceKind {
+    /// `cbuffer`, register `b`.
+    ConstantBuffer,
+    /// textures, typed, structured and byte address buffers, register `t`.
+    Texture,
+    /// `SamplerState`, register `s`.
+    Sampler,
+    /// `RW` resources and append / consume buffers, register `u`.
+    UnorderedAccess,
+}
+
+impl ResourceKind {
+    /// register prefix in HLSL, e.g. `t` for `register(t0)`.
+    pub fn register(&self) -> char {
+        match self {
+            ResourceKind::ConstantBuffer => 'b',
+            ResourceKind::Texture => 't',
+            ResourceKind::Sampler => 's',
+            ResourceKind::UnorderedAccess => 'u',
+        }
+    }
+}
+
+/// a resource the shader reads or writes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundResource {
+    pub name: String,
+    pub kind: ResourceKind,
+    /// first register, e.g. `0` for `t0`.
+    pub slot: u32,
+    /// number of registers, more than one for arrays.
+    pub count: u32,
+    /// register space, always `0` below SM 5.1.
+    pub space: u32,
+}
+
+/// a member of a constant buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstantVariable {
+    pub name: String,
+    /// byte offset from the start of the buffer.
+    pub offset: u32,
+    /// size in bytes.
+    pub size: u32,
+}
+
+/// layout of a `cbuffer`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstantBufferLayout {
+    pub name: String,
+    /// size in bytes, a multiple of 16.
+    pub size: u32,
+    pub variables: Vec<ConstantVariable>,
+}
+
+/// an element of the input or output signature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignatureElement {
+    /// semantic name as written in the shader, e.g. `TEXCOORD` or `SV_Target`.
+    pub semantic: String,
+    pub semantic_index: u32,
+    pub register: u32,
+    /// components used, bit 0 for `x` to bit 3 for `w`.
+    pub mask: u8,
+}
+
+/// resources, constant buffers and signatures of a compiled shader.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShaderReflection {
+    pub resources: Vec<BoundResource>,
+    pub constant_buffers: Vec<ConstantBufferLayout>,
+    pub inputs: Vec<SignatureElement>,
+    pub outputs: Vec<SignatureElement>,
+}
+
+impl ShaderReflection {
+    /// resource declared as `name` in the shader.
+    pub fn resource(&self, name: &str) -> Option<&BoundResource> {
+        self.resources.iter().find(|r| r.name == name)
+    }
+
+    /// layout of the `cbuffer` declared as `name`.
+    pub fn constant_buffer(&self, name: &str) -> Option<&ConstantBufferLayout> {
+        self.constant_buffers.iter().find(|cb| cb.name == name)
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| format!("offset {} is out of bounds", offset))
+}
+
+fn read_str(data: &[u8], offset: usize) -> Result<String, String> {
+    let tail = data.get(offset..).ok_or_else(|| format!("string offset {} is out of bounds", offset))?;
+    let len = tail.iter().position(|b| *b == 0).ok_or_else(|| format!("string at {} is not terminated", offset))?;
+    Ok(String::from_utf8_lossy(&tail[..len]).into_owned())
+}
+
+/// `(fourcc, data)` of a chunk.
+type Chunk<'a> = (&'a [u8], &'a [u8]);
+
+/// every chunk of the container.
+fn chunks(bytecode: &[u8]) -> Result<Vec<Chunk<'_>>, String> {
+    if bytecode.get(0..4) != Some(b"DXBC") {
+        return Err("not DXBC bytecode".to_owned());
+    }
+    let count = read_u32(bytecode, 28)? as usize;
+    let mut chunks = Vec::new();
+    for i in 0..count {
+        let offset = read_u32(bytecode, 32 + 4 * i)? as usize;
+        let size = read_u32(bytecode, offset + 4)? as usize;
+        let data = bytecode.get(offset + 8..offset + 8 + size).ok_or_else(|| format!("chunk {} is out of bounds", i))?;
+        chunks.push((&bytecode[offset..offset + 4], data));
+    }
+    Ok(chunks)
+}
+
+fn resource_kind(input_type: u32) -> Option<ResourceKind> {
+    // D3D_SHADER_INPUT_TYPE
+    match input_type {
+        0 => Some(ResourceKind::ConstantBuffer),
+        1 | 2 | 5 | 7 | 12 => Some(ResourceKind::Texture),
+        3 => Some(ResourceKind::Sampler),
+        4 | 6 | 8..=11 | 13 => Some(ResourceKind::UnorderedAccess),
+        _ => None,
+    }
+}
+
+fn parse_rdef(data: &[u8], reflection: &mut ShaderReflection) -> Result<(), String> {
+    let cbuffer_count = read_u32(data, 0)? as usize;
+    let cbuffer_offset = read_u32(data, 4)? as usize;
+    let binding_count = read_u32(data, 8)? as usize;
+    let binding_offset = read_u32(data, 12)? as usize;
+    let version = read_u32(data, 16)? & 0xffff;
+    // SM 5.1 added the register space and id to bindings, SM 5 texture and sampler ranges to variables
+    let binding_size = if version >= 0x501 { 40 } else { 32 };
+    let variable_size = if version >= 0x500 { 40 } else { 24 };
+
+    for i in 0..binding_count {
+        let at = binding_offset + i * binding_size;
+        let input_type = read_u32(data, at + 4)?;
+        let Some(kind) = resource_kind(input_type) else {
+            continue;
+        };
+        reflection.resources.push(BoundResource {
+            name: read_str(data, read_u32(data, at)? as usize)?,
+            kind,
+            slot: read_u32(data, at + 20)?,
+            count: read_u32(data, at + 24)?,
+            space: if binding_size == 40 { read_u32(data, at + 32)? } else { 0 },
+        });
+    }
+
+    for i in 0..cbuffer_count {
+        let at = cbuffer_offset + i * 24;
+        // D3D_CBUFFER_TYPE, the others describe tbuffers, interfaces and structured buffers
+        if read_u32(data, at + 20)? != 0 {
+            continue;
+        }
+        let variable_count = read_u32(data, at + 4)? as usize;
+        let variable_offset = read_u32(data, at + 8)? as usize;
+        let mut variables = Vec::with_capacity(variable_count);
+        for j in 0..variable_count {
+            let var = variable_offset + j * variable_size;
+            variables.push(ConstantVariable {
+                name: read_str(data, read_u32(data, var)? as usize)?,
+                offset: read_u32(data, var + 4)?,
+                size: read_u32(data, var + 8)?,
+            });
+        }
+        reflection.constant_buffers.push(ConstantBufferLayout {
+            name: read_str(data, read_u32(data, at)? as usize)?,
+            size: read_u32(data, at + 12)?,
+            variables,
+        });
+    }
+    Ok(())
+}
+
+/// elements of a signature chunk. `stream` and `precision` tell whether elements start with a
+/// stream index and end with a min precision.
+fn parse_signature(data: &[u8], stream: bool, precision: bool) -> Result<Vec<SignatureElement>, String> {
+    let count = read_u32(data, 0)? as usize;
+    let offset = read_u32(data, 4)? as usize;
+    let size = 24 + if stream { 4 } else { 0 } + if precision { 4 } else { 0 };
+    let mut elements = Vec::with_capacity(count);
+    for i in 0..count {
+        let at = offset + i * size + if stream { 4 } else { 0 };
+        elements.push(SignatureElement {
+            semantic: read_str(data, read_u32(data, at)? as usize)?,
+            semantic_index: read_u32(data, at + 4)?,
+            register: read_u32(data, at + 16)?,
+            mask: *data.get(at + 20).ok_or_else(|| format!("signature element {} is out of bounds", i))?,
+        });
+    }
+    Ok(elements)
+}
+
+/// reflection data of DXBC `bytecode`. fails when the container is malformed or was stripped of
+/// its `RDEF` chunk.
+pub fn parse(bytecode: &[u8]) -> Result<ShaderReflection, String> {
+    let mut reflection = ShaderReflection::default();
+    let mut has_rdef = false;
+    for (fourcc, data) in chunks(bytecode)? {
+        match fourcc {
+            b"RDEF" => {
+                parse_rdef(data, &mut reflection).map_err(|e| format!("invalid RDEF chunk: {}", e))?;
+                has_rdef = true;
+            }
+            b"ISGN" => reflection.inputs = parse_signature(data, false, false)?,
+            b"ISG1" => reflection.inputs = parse_signature(data, true, true)?,
+            b"OSGN" => reflection.outputs = parse_signature(data, false, false)?,
+            b"OSG5" => reflection.outputs = parse_signature(data, true, false)?,
+            b"OSG1" => reflection.outputs = parse_signature(data, true, true)?,
+            _ => {}
+        }
+    }
+    if !has_rdef {
+        return Err("bytecode has no RDEF chunk, it was compiled or stripped without reflection data".to_owned());
+    }
+    Ok(reflection)
+}
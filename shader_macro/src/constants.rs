@@ -0,0 +1,255 @@
+//! implementation of `#[derive(ShaderConstants)]`. checks that a `#[repr(C)]` struct follows the
+//! HLSL constant buffer packing rules and generates the matching `cbuffer` fields.
+//!
+//! HLSL packs constant buffers into 16 byte registers:
+//! * a scalar or vector never straddles a register boundary.
+//! * every element of an array starts a new register.
+//! * the buffer size is a multiple of 16 bytes.
+//!
+//! `#[repr(C)]` lays fields out back to back with 4 byte alignment, so the rust layout matches
+//! HLSL exactly when no field needs implicit HLSL padding. Instead of inserting that padding
+//! silently, the derive rejects the struct and says where explicit padding is missing.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Expr, Fields, Lit, LitStr, Type};
+
+#[cfg(test)]
+mod test {
+    use syn::{DeriveInput, parse_quote};
+
+    use crate::constants::{derive, layout, HlslType};
+
+    fn vector(name: &str, size: u32) -> HlslType {
+        HlslType { name: name.to_owned(), size, array_len: None }
+    }
+
+    #[test]
+    fn test_layout() {
+        let fields = vec![
+            ("a".to_owned(), vector("float3", 12)),
+            ("b".to_owned(), vector("float", 4)),
+            ("c".to_owned(), vector("float2", 8)),
+            ("d".to_owned(), vector("uint2", 8)),
+        ];
+        assert_eq!(layout(&fields), Ok(32));
+
+        // float3 at offset 8 would cross into the next register
+        let fields = vec![("a".to_owned(), vector("float2", 8)), ("b".to_owned(), vector("float3", 12))];
+        let (idx, msg) = layout(&fields).unwrap_err();
+        assert_eq!(idx, Some(1));
+        assert!(msg.contains("offset 8") && msg.contains("8 bytes of padding"), "{}", msg);
+
+        // arrays start on a register boundary
+        let fields = vec![
+            ("a".to_owned(), vector("float", 4)),
+            ("b".to_owned(), HlslType { name: "float4".to_owned(), size: 16, array_len: Some(2) }),
+        ];
+        assert_eq!(layout(&fields).unwrap_err().0, Some(1));
+
+        // size must be a multiple of 16
+        let fields = vec![("a".to_owned(), vector("float3", 12))];
+        let (idx, msg) = layout(&fields).unwrap_err();
+        assert_eq!(idx, None);
+        assert!(msg.contains("4 bytes of padding"), "{}", msg);
+    }
+
+    #[test]
+    fn test_derive() {
+        let input: DeriveInput = parse_quote! {
+            #[repr(C)]
+            struct Params {
+                color: [f32; 3],
+                strength: f32,
+                matrix: [[f32; 4]; 2],
+                size: [u32; 2],
+                mode: i32,
+                _padding: u32,
+            }
+        };
+        let out = derive(input).unwrap().to_string();
+        let expected = "    float3 color;\n    float strength;\n    float4 matrix[2];\n    uint2 size;\n    int mode;\n    uint _padding;\n";
+        assert!(out.contains(&format!("{:?}", expected)), "{}", out);
+        assert!(out.contains("64usize"), "{}", out);
+    }
+
+    #[test]
+    fn test_derive_errors() {
+        let errors = [
+            // missing repr(C)
+            parse_quote! { struct A { a: [f32; 4] } },
+            // bool has no fixed HLSL layout in rust
+            parse_quote! { #[repr(C)] struct A { a: bool, b: [f32; 3] } },
+            // scalar arrays pad every element in HLSL
+            parse_quote! { #[repr(C)] struct A { a: [f32; 8] } },
+            // tuple struct
+            parse_quote! { #[repr(C)] struct A([f32; 4]); },
+            // generics
+            parse_quote! { #[repr(C)] struct A<T> { a: [f32; 4], b: T } },
+        ];
+        for input in errors {
+            let input: DeriveInput = input;
+            assert!(derive(input).is_err());
+        }
+
+        let input: DeriveInput = parse_quote! { #[repr(C, align(16))] struct A { a: [f32; 2], b: [f32; 3], c: f32 } };
+        let err = derive(input).unwrap_err().to_string();
+        assert!(err.contains("`b`"), "{}", err);
+    }
+}
+
+/// size of an HLSL constant buffer register.
+const REGISTER_SIZE: u32 = 16;
+
+/// HLSL type of a struct field.
+#[derive(Debug, PartialEq)]
+struct HlslType {
+    /// e.g. `float3`
+    name: String,
+    /// size of a single element in bytes.
+    size: u32,
+    /// element count when the field is an array. each element takes a full register.
+    array_len: Option<u32>,
+}
+
+/// HLSL name of `f32`, `u32` and `i32`.
+fn scalar(ty: &Type) -> Option<&'static str> {
+    let path = match ty {
+        Type::Path(p) if p.qself.is_none() => &p.path,
+        _ => return None,
+    };
+    match path.get_ident()?.to_string().as_str() {
+        "f32" => Some("float"),
+        "u32" => Some("uint"),
+        "i32" => Some("int"),
+        _ => None,
+    }
+}
+
+fn literal_len(len: &Expr) -> Option<u32> {
+    match len {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn hlsl_type(ty: &Type) -> Result<HlslType, Error> {
+    if let Some(name) = scalar(ty) {
+        return Ok(HlslType { name: name.to_owned(), size: 4, array_len: None });
+    }
+    if let Type::Array(array) = ty {
+        let len = literal_len(&array.len)
+            .ok_or_else(|| Error::new_spanned(&array.len, "array length must be an integer literal"))?;
+        if let Some(name) = scalar(&array.elem) {
+            if (2..=4).contains(&len) {
+                return Ok(HlslType { name: format!("{}{}", name, len), size: 4 * len, array_len: None });
+            }
+            return Err(Error::new_spanned(ty, format!(
+                "`[{}; {}]` has no HLSL equivalent. use 2 to 4 elements for a `{}N` vector; HLSL arrays \
+                 place every element in its own 16 byte register, so declare them as `[[{}; 4]; N]`",
+                name_of(&array.elem), len, name, name_of(&array.elem))));
+        }
+        if let Type::Array(inner) = &*array.elem {
+            if let (Some(name), Some(4)) = (scalar(&inner.elem), literal_len(&inner.len)) {
+                if len == 0 {
+                    return Err(Error::new_spanned(ty, "HLSL arrays can't be empty"));
+                }
+                return Ok(HlslType { name: format!("{}4", name), size: REGISTER_SIZE, array_len: Some(len) });
+            }
+        }
+    }
+    Err(Error::new_spanned(ty, "unsupported constant buffer field. use `f32`, `u32` or `i32` (also for HLSL `bool`), \
+                                `[T; 2..=4]` for vectors or `[[T; 4]; N]` for arrays"))
+}
+
+fn name_of(ty: &Type) -> String {
+    quote!(#ty).to_string()
+}
+
+/// size of the struct following HLSL packing. on error returns the index of the offending field,
+/// `None` for the struct itself, and a message.
+fn layout(fields: &[(String, HlslType)]) -> Result<u32, (Option<usize>, String)> {
+    let mut offset = 0;
+    for (i, (name, ty)) in fields.iter().enumerate() {
+        let in_register = offset % REGISTER_SIZE;
+        match ty.array_len {
+            Some(len) => {
+                if in_register != 0 {
+                    return Err((Some(i), format!(
+                        "array `{}` starts at offset {} but HLSL arrays start on a 16 byte boundary. add {} bytes of padding before it",
+                        name, offset, REGISTER_SIZE - in_register)));
+                }
+                offset += ty.size * len;
+            }
+            None => {
+                if in_register + ty.size > REGISTER_SIZE {
+                    return Err((Some(i), format!(
+                        "`{}` ({}) at offset {} straddles a 16 byte boundary. add {} bytes of padding before it",
+                        name, ty.name, offset, REGISTER_SIZE - in_register)));
+                }
+                offset += ty.size;
+            }
+        }
+    }
+    if offset % REGISTER_SIZE != 0 {
+        return Err((None, format!("size is {} bytes but constant buffers are a multiple of 16 bytes. add {} bytes of padding at the end",
+                                  offset, REGISTER_SIZE - offset % REGISTER_SIZE)));
+    }
+    Ok(offset)
+}
+
+fn is_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+        .filter_map(|attr| attr.meta.require_list().ok())
+        .any(|list| list.tokens.to_string().split(',').any(|item| item.trim() == "C"))
+}
+
+/// expand `#[derive(ShaderConstants)]` for `input`.
+pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream, Error> {
+    let ident = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "ShaderConstants can't be derived for generic structs"));
+    }
+    if !is_repr_c(&input) {
+        return Err(Error::new_spanned(ident, "ShaderConstants needs `#[repr(C)]` so the field layout is fixed"));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(ident, "ShaderConstants needs named fields")),
+        },
+        _ => return Err(Error::new_spanned(ident, "ShaderConstants can only be derived for structs")),
+    };
+
+    let mut typed = Vec::with_capacity(fields.len());
+    for field in fields {
+        typed.push((field.ident.as_ref().unwrap().to_string(), hlsl_type(&field.ty)?));
+    }
+    let size = layout(&typed).map_err(|(idx, msg)| match idx {
+        Some(idx) => Error::new_spanned(&fields[idx], msg),
+        None => Error::new_spanned(ident, msg),
+    })?;
+
+    let mut hlsl = String::new();
+    for (name, ty) in &typed {
+        match ty.array_len {
+            Some(len) => hlsl.push_str(&format!("    {} {}[{}];\n", ty.name, name, len)),
+            None => hlsl.push_str(&format!("    {} {};\n", ty.name, name)),
+        }
+    }
+    let name = LitStr::new(&ident.to_string(), ident.span());
+    let hlsl = LitStr::new(&hlsl, ident.span());
+    let size = size as usize;
+    let size_msg = LitStr::new(&format!("size of `{}` doesn't match its HLSL layout", ident), ident.span());
+
+    Ok(quote! {
+        impl ::dxfilter::constant_buffer::ShaderConstants for #ident {
+            const NAME: &'static str = #name;
+            const HLSL_FIELDS: &'static str = #hlsl;
+        }
+        const _: () = assert!(::core::mem::size_of::<#ident>() == #size, #size_msg);
+    })
+}
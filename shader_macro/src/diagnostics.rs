@@ -0,0 +1,106 @@
+//! turns the error log of the HLSL compiler into `syn::Error`s pointing at the `compile_shader!`
+//! input, quoting the offending HLSL line.
+//!
+//! Proc macros can't point into the middle of a string literal on stable, so the error is spanned
+//! at the `src` literal or the `src_file` path and the message carries the line.
+use std::fs::read_to_string;
+
+use proc_macro2::Span;
+use syn::Error;
+
+use shader_common::hlsl_log::{HlslMessage, parse_log};
+
+/// the shader source handed to the compiler.
+pub(crate) struct Source<'a> {
+    /// name passed as `pSourceName`, i.e. the `src_name` or `src_file` of the macro.
+    pub name: Option<&'a str>,
+    pub text: &'a str,
+    /// true when `text` is the `src` literal rather than the contents of `src_file`.
+    pub inline: bool,
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    let normalize = |p: &str| p.replace('\\', "/").to_lowercase();
+    normalize(a) == normalize(b)
+}
+
+/// describe one compiler message with its location and the quoted source line.
+fn describe(msg: &HlslMessage, source: &Source) -> String {
+    let in_main = msg.file.is_empty() || source.name.is_none_or(|name| same_file(name, &msg.file));
+    let (location, text) = if in_main {
+        let location = match (source.inline, source.name) {
+            (true, _) | (false, None) => format!("line {}, column {} of `src`", msg.line, msg.column),
+            (false, Some(name)) => format!("{}:{}:{}", name, msg.line, msg.column),
+        };
+        (location, Some(source.text.to_owned()))
+    } else {
+        (format!("{}:{}:{}", msg.file, msg.line, msg.column), read_to_string(&msg.file).ok())
+    };
+
+    let mut out = format!("{}\n  --> {}", msg.message, location);
+    let quoted = text.as_deref().and_then(|t| t.lines().nth(msg.line.saturating_sub(1)));
+    if let Some(quoted) = quoted {
+        let gutter = msg.line.to_string().len().max(3);
+        out.push_str(&format!("\n{:>w$} |\n{:>w$} | {}\n{:>w$} | {}^",
+                              "", msg.line, quoted.trim_end(), "", " ".repeat(msg.column.saturating_sub(1)), w = gutter));
+    }
+    out
+}
+
+/// build the error reported for a failed compilation. `span` is the `src` literal or the
+/// `src_file` path of the macro input.
+pub(crate) fn compile_error(log: &str, span: Span, source: &Source) -> Error {
+    let messages = parse_log(log);
+    let mut errors = messages.iter().map(|msg| Error::new(span, describe(msg, source)));
+    match errors.next() {
+        Some(mut first) => {
+            for e in errors {
+                first.combine(e);
+            }
+            first
+        }
+        None => Error::new(span, format!("failed to compile shader. {}", log.trim())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proc_macro2::Span;
+
+    use crate::diagnostics::{compile_error, Source};
+
+    #[test]
+    fn test_inline_error() {
+        let src = "float4 main() : SV_Target\n{\n    return foo;\n}\n";
+        let log = "(3,12-14): error X3004: undeclared identifier 'foo'\n";
+        let source = Source { name: None, text: src, inline: true };
+        let err = compile_error(log, Span::call_site(), &source).to_string();
+        assert!(err.contains("error X3004: undeclared identifier 'foo'"), "{}", err);
+        assert!(err.contains("line 3, column 12 of `src`"), "{}", err);
+        assert!(err.contains("  3 |     return foo;\n    |            ^"), "{}", err);
+    }
+
+    #[test]
+    fn test_file_error() {
+        let src = "float4 main() : SV_Target\n{\n    return foo;\n}\n";
+        let source = Source { name: Some("src/shaders/blur_ps.hlsl"), text: src, inline: false };
+        let log = "src\\shaders\\blur_ps.hlsl(3,12): error X3004: undeclared identifier 'foo'\n";
+        let err = compile_error(log, Span::call_site(), &source).to_string();
+        assert!(err.contains("--> src/shaders/blur_ps.hlsl:3:12"), "{}", err);
+        assert!(err.contains("return foo;"), "{}", err);
+
+        // errors in includes that can't be read still show the location
+        let log = "missing.hlsli(1,1): error X3000: syntax error\n";
+        let err = compile_error(log, Span::call_site(), &source).to_string();
+        assert!(err.contains("--> missing.hlsli:1:1"), "{}", err);
+
+        // DXC reports the same error in the clang format
+        let log = "src/shaders/blur_ps.hlsl:3:12: error: use of undeclared identifier 'foo'\n    return foo;\n           ^\n";
+        let err = compile_error(log, Span::call_site(), &source).to_string();
+        assert!(err.starts_with("error: use of undeclared identifier 'foo'\n  --> src/shaders/blur_ps.hlsl:3:12"), "{}", err);
+
+        // unparsable logs are passed through
+        let err = compile_error("error X3501: 'main': entrypoint not found", Span::call_site(), &source).to_string();
+        assert!(err.contains("entrypoint not found"), "{}", err);
+    }
+}
@@ -0,0 +1,172 @@
+//! compiles HLSL to DXIL with DXC for `compiler: "dxc"`. only available on windows, other hosts
+//! use precompiled blobs.
+//!
+//! dxcompiler.dll is loaded when a shader asks for DXC, so crates that only use FXC don't need it.
+//! it is looked up at `SHADER_MACRO_DXC` when set, otherwise on the DLL search path. DXC signs the
+//! output with dxil.dll when it can load it, D3D12 rejects unsigned DXIL.
+use std::cell::RefCell;
+use std::env::var;
+use std::ffi::c_void;
+use std::fs::{canonicalize, read};
+use std::mem::transmute;
+use std::path::{Path, PathBuf};
+use std::ptr::null_mut;
+use std::rc::Rc;
+use std::slice::from_raw_parts;
+
+use windows::core::{implement, w, Error, GUID, HRESULT, HSTRING, Interface, PCWSTR, s};
+use windows::Win32::Graphics::Direct3D::Dxc::{CLSID_DxcCompiler, CLSID_DxcUtils, DXC_CP_UTF8, DXC_OUT_OBJECT, DxcBuffer,
+                                             IDxcBlob, IDxcCompiler3, IDxcIncludeHandler, IDxcIncludeHandler_Impl,
+                                             IDxcResult, IDxcUtils};
+use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+
+const E_FAIL: HRESULT = HRESULT(0x80004005_u32 as i32);
+
+/// environment variable with the path of dxcompiler.dll.
+pub(crate) const DXC_VAR: &str = "SHADER_MACRO_DXC";
+
+/// signature of `DxcCreateInstance`.
+type CreateInstance = unsafe extern "system" fn(*const GUID, *const GUID, *mut *mut c_void) -> HRESULT;
+
+/// resolves `#include` for [compile].
+///
+/// DXC hands over the candidate paths itself: next to the including file, then in every `-I`
+/// directory. in-memory files match by their include name at the end of the candidate path.
+#[implement(IDxcIncludeHandler)]
+struct IncludeHandler {
+    utils: IDxcUtils,
+    files: Vec<(String, Vec<u8>)>,
+    root: PathBuf,
+    resolved: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+impl IncludeHandler {
+    fn resolve(&self, name: &str) -> Option<Vec<u8>> {
+        let normalized = name.replace('\\', "/");
+        let in_memory = self.files.iter().find(|(file, _)| {
+            let file = file.replace('\\', "/");
+            normalized == file || normalized.ends_with(&format!("/{}", file))
+        });
+        if let Some((_, data)) = in_memory {
+            return Some(data.clone());
+        }
+
+        let path = self.root.join(name);
+        let data = read(&path).ok()?;
+        self.resolved.borrow_mut().push(canonicalize(&path).unwrap_or(path));
+        Some(data)
+    }
+}
+
+impl IDxcIncludeHandler_Impl for IncludeHandler {
+    fn LoadSource(&self, pfilename: &PCWSTR) -> windows::core::Result<IDxcBlob> {
+        let name = unsafe { pfilename.to_string() }.map_err(|_| Error::from(E_FAIL))?;
+        let data = self.resolve(&name).ok_or_else(|| Error::from(E_FAIL))?;
+        // CreateBlob copies the data
+        let blob = unsafe { self.utils.CreateBlob(data.as_ptr() as _, data.len() as u32, DXC_CP_UTF8) }?;
+        blob.cast()
+    }
+}
+
+/// parameters of `IDxcCompiler3::Compile`.
+pub(crate) struct DxcInput<'a> {
+    pub src: &'a [u8],
+    /// used in messages and as the directory `#include` is resolved against.
+    pub src_name: Option<&'a str>,
+    pub defines: &'a [(&'a str, &'a str)],
+    pub entry_point: &'a str,
+    pub target: &'a str,
+    pub include_dirs: &'a [PathBuf],
+    /// in-memory files by include name.
+    pub includes: &'a [(String, Vec<u8>)],
+    /// directory relative includes are resolved against when `src_name` is not given.
+    pub root: &'a Path,
+    /// passed to DXC after the options above, e.g. `-enable-16bit-types`.
+    pub args: &'a [String],
+}
+
+fn blob_bytes(blob: &IDxcBlob) -> &[u8] {
+    unsafe { from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()) }
+}
+
+/// `DxcCreateInstance` of dxcompiler.dll.
+fn load_dxc() -> Result<CreateInstance, String> {
+    let path = var(DXC_VAR).ok().map(HSTRING::from);
+    let name = path.as_ref().map_or(w!("dxcompiler.dll"), |path| PCWSTR(path.as_ptr()));
+    // the library stays loaded for the lifetime of the compiler process
+    let module = unsafe { LoadLibraryW(name) }.map_err(|e| format!(
+        "DXC can't be loaded ({}). put dxcompiler.dll and dxil.dll from the Windows SDK or the DirectXShaderCompiler \
+         release on PATH, or set {} to the path of dxcompiler.dll", e.message(), DXC_VAR))?;
+    let create = unsafe { GetProcAddress(module, s!("DxcCreateInstance")) }
+        .ok_or_else(|| "dxcompiler.dll doesn't export DxcCreateInstance".to_owned())?;
+    Ok(unsafe { transmute::<unsafe extern "system" fn() -> isize, CreateInstance>(create) })
+}
+
+fn create_instance<T: Interface>(create: CreateInstance, clsid: &GUID) -> Result<T, String> {
+    let mut instance = null_mut();
+    unsafe { create(clsid, &T::IID, &mut instance) }.ok()
+        .map_err(|e| format!("failed to create a DXC instance. {}", e.message()))?;
+    Ok(unsafe { T::from_raw(instance) })
+}
+
+/// compile `input` to DXIL. returns the bytecode and the files included from disk. on failure
+/// returns the compiler log, or a description of the failed call when there is no log.
+pub(crate) fn compile(input: &DxcInput) -> Result<(Vec<u8>, Vec<PathBuf>), String> {
+    let create = load_dxc()?;
+    let compiler: IDxcCompiler3 = create_instance(create, &CLSID_DxcCompiler)?;
+    let utils: IDxcUtils = create_instance(create, &CLSID_DxcUtils)?;
+
+    let root = input.src_name.and_then(|name| Path::new(name).parent()).unwrap_or(input.root);
+    let resolved = Rc::new(RefCell::new(Vec::new()));
+    let include: IDxcIncludeHandler = IncludeHandler {
+        utils,
+        files: input.includes.to_vec(),
+        root: root.to_path_buf(),
+        resolved: resolved.clone(),
+    }.into();
+
+    let mut args: Vec<String> = Vec::new();
+    if let Some(name) = input.src_name {
+        args.push(name.to_owned());
+    }
+    args.extend(["-E".to_owned(), input.entry_point.to_owned(), "-T".to_owned(), input.target.to_owned()]);
+    for (name, def) in input.defines {
+        args.push(format!("-D{}={}", name, def));
+    }
+    for dir in input.include_dirs {
+        args.push("-I".to_owned());
+        args.push(dir.to_string_lossy().into_owned());
+    }
+    args.extend(input.args.iter().cloned());
+    let args: Vec<HSTRING> = args.iter().map(HSTRING::from).collect();
+    let arg_ptrs: Vec<PCWSTR> = args.iter().map(|arg| PCWSTR(arg.as_ptr())).collect();
+
+    let source = DxcBuffer {
+        Ptr: input.src.as_ptr() as _,
+        Size: input.src.len(),
+        Encoding: DXC_CP_UTF8.0,
+    };
+    let result: IDxcResult = unsafe { compiler.Compile(&source, Some(&arg_ptrs), &include) }
+        .map_err(|e| format!("failed to compile shader. {}", e.message()))?;
+
+    let status = unsafe { result.GetStatus() }.map_err(|e| format!("failed to compile shader. {}", e.message()))?;
+    if status.is_err() {
+        let log = unsafe { result.GetErrorBuffer() }.ok()
+            .map(|log| {
+                let bytes = unsafe { from_raw_parts(log.GetBufferPointer() as *const u8, log.GetBufferSize()) };
+                String::from_utf8_lossy(bytes).trim_end_matches('\0').to_owned()
+            })
+            .filter(|log| !log.trim().is_empty());
+        return Err(log.unwrap_or_else(|| format!("failed to compile shader. {}", Error::from(status).message())));
+    }
+
+    let mut object: Option<IDxcBlob> = None;
+    unsafe { result.GetOutput(DXC_OUT_OBJECT, null_mut(), &mut object) }
+        .map_err(|e| format!("failed to read the compiled shader. {}", e.message()))?;
+    let bytes = match object {
+        Some(object) => blob_bytes(&object).to_vec(),
+        None => return Err("compilation succeeded but no bytes were returned!".to_owned()),
+    };
+    let resolved = resolved.borrow().clone();
+    Ok((bytes, resolved))
+}
@@ -4,31 +4,247 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use std::ffi::CString;
 use std::fs::read_to_string;
-use std::path::PathBuf;
-use std::ptr::null;
-use std::slice::from_raw_parts;
+use std::env::var_os;
+use std::path::{Path, PathBuf};
 
+use proc_macro2::Span;
 use quote::quote;
-use syn::{braced, bracketed, Ident, LitByteStr, LitInt, LitStr, parse_macro_input, Token};
+use syn::{braced, bracketed, DeriveInput, Error, Ident, LitBool, LitByteStr, LitInt, LitStr, parenthesized, parse_macro_input, Token};
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use syn::token::Comma;
-use windows::core::PCSTR;
-use windows::Win32::Graphics::Direct3D::{D3D_SHADER_MACRO, ID3DBlob, ID3DInclude};
-use windows::Win32::Graphics::Direct3D::Fxc::D3DCompile2;
-use windows::Win32::Graphics::Hlsl::D3D_COMPILE_STANDARD_FILE_INCLUDE;
 
+#[cfg(windows)]
+use shader_common::fxc;
+use shader_common::reflection;
+
+mod constants;
+// only the compiler reports HLSL errors
+#[cfg_attr(not(windows), allow(dead_code))]
+mod diagnostics;
+#[cfg(windows)]
+mod dxc;
+mod precompiled;
+mod variants;
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::{Compiler, ShaderMacroInput};
+
+    fn parse(src: &str) -> ShaderMacroInput {
+        match syn::parse_str(src) {
+            Ok(input) => input,
+            Err(e) => panic!("failed to parse {}: {}", src, e),
+        }
+    }
+
+    fn parse_err(src: &str) -> String {
+        match syn::parse_str::<ShaderMacroInput>(src) {
+            Ok(_) => panic!("expected {} to fail", src),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn root() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn test_parse_required() {
+        let input = parse(r#"src: "float4 main() : SV_Target { return 0; }", entry_point: "main", target: "ps_5_0""#);
+        assert_eq!(input.src_data, "float4 main() : SV_Target { return 0; }");
+        assert!(input.src_inline);
+        assert_eq!(input.src_file, None);
+        assert_eq!(input.src_name, None);
+        assert_eq!(input.entry_point.to_str().unwrap(), "main");
+        assert_eq!(input.target.to_str().unwrap(), "ps_5_0");
+
+        // defaults
+        assert!(input.macros.is_empty() && input.include_dirs.is_empty() && input.includes.is_empty());
+        assert_eq!((input.flags1, input.flags2, input.secondary_data_flags), (0, 0, 0));
+        assert!(input.secondary_data.is_empty());
+        assert_eq!(input.precompiled, None);
+        assert!(!input.reflect);
+        assert_eq!(input.compiler, Compiler::Fxc);
+        assert!(input.args.is_empty());
+
+        // order doesn't matter and a trailing comma is fine
+        let input = parse(r#"target: "cs_5_0", entry_point: "run", src: "x","#);
+        assert_eq!(input.entry_point.to_str().unwrap(), "run");
+    }
+
+    #[test]
+    fn test_parse_optional() {
+        let input = parse(r##"
+            src: "#include \"colors.hlsli\"",
+            src_name: "shaders/main.hlsl",
+            entry_point: "main",
+            target: "ps_5_0",
+            defines: { ["A", "1"], ("B", "two"), },
+            include_dirs: ["shared/hlsl", "more"],
+            includes: { "colors.hlsli": "float3 f() { return 0; }", "b.hlsli": "" },
+            flags1: 2048,
+            flags2: 1,
+            secondary_data_flags: 3,
+            secondary_data: b"data",
+            precompiled: "blobs/main.cso",
+            reflect: true,
+        "##);
+        assert_eq!(input.src_name.unwrap().to_str().unwrap(), root().join("shaders/main.hlsl").to_str().unwrap());
+        let defines: Vec<_> = input.macros.iter().map(|m| (m.name.to_str().unwrap(), m.def.to_str().unwrap())).collect();
+        assert_eq!(defines, vec![("A", "1"), ("B", "two")]);
+        assert_eq!(input.include_dirs, vec![root().join("shared/hlsl"), root().join("more")]);
+        assert_eq!(input.includes, vec![
+            ("colors.hlsli".to_owned(), "float3 f() { return 0; }".to_owned()),
+            ("b.hlsli".to_owned(), "".to_owned()),
+        ]);
+        assert_eq!((input.flags1, input.flags2, input.secondary_data_flags), (2048, 1, 3));
+        assert_eq!(input.secondary_data, b"data");
+        assert_eq!(input.precompiled, Some(root().join("blobs/main.cso")));
+        assert!(input.reflect);
+    }
+
+    #[test]
+    fn test_parse_compiler() {
+        let input = parse(r#"src: "", entry_point: "main", target: "ps_5_0", compiler: "fxc""#);
+        assert_eq!(input.compiler, Compiler::Fxc);
+
+        let input = parse(r#"
+            src: "", entry_point: "main", target: "cs_6_2",
+            compiler: "dxc",
+            args: ["-enable-16bit-types", "-HV", "2021",],
+            include_dirs: ["shared"],
+        "#);
+        assert_eq!(input.compiler, Compiler::Dxc);
+        assert_eq!(input.args, vec!["-enable-16bit-types", "-HV", "2021"]);
+
+        let with = |extra: &str| format!(r#"src: "", entry_point: "main", {}"#, extra);
+        assert_eq!(parse_err(&with(r#"target: "cs_6_0", compiler: "clang""#)), "unknown compiler `clang`. expected `fxc` or `dxc`");
+        assert!(parse_err(&with(r#"target: "cs_6_0""#)).contains("shader model 6 targets need `compiler: \"dxc\"`"));
+        assert!(parse_err(&with(r#"target: "ps_5_0", args: ["-O3"]"#)).starts_with("`args` is only supported with"));
+        for fxc_only in ["flags1: 1", "flags2: 0", "secondary_data_flags: 0", r#"secondary_data: b"x""#] {
+            let err = parse_err(&with(&format!(r#"target: "cs_6_0", compiler: "dxc", {}"#, fxc_only)));
+            assert!(err.ends_with("is an FXC option, pass DXC options with `args`"), "{}", err);
+        }
+        assert!(parse_err(&with(r#"target: "cs_6_0", compiler: "dxc", reflect: true"#)).starts_with("`reflect` is only supported with FXC"));
+        // no reflection is fine
+        parse(&with(r#"target: "cs_6_0", compiler: "dxc", reflect: false"#));
+        parse_err(&with(r#"target: "cs_6_0", compiler: dxc"#));
+        parse_err(&with(r#"target: "cs_6_0", compiler: "dxc", args: "-O3""#));
+    }
+
+    #[test]
+    fn test_parse_src_file() {
+        let input = parse(r#"src_file: "Cargo.toml", entry_point: "main", target: "ps_5_0""#);
+        let path = root().join("Cargo.toml");
+        assert!(!input.src_inline);
+        assert_eq!(input.src_file.as_ref(), Some(&path));
+        // the file name doubles as the name for messages and includes
+        assert_eq!(input.src_name.unwrap().to_str().unwrap(), path.to_str().unwrap());
+        assert!(input.src_data.contains("shader_macro"));
+
+        // forward slashes work on every host, backslashes are converted off windows
+        let input = parse(r#"src_file: "src/lib.rs", entry_point: "main", target: "ps_5_0""#);
+        assert!(input.src_data.contains("compile_shader"));
+        let input = parse(r#"src_file: "src\\lib.rs", entry_point: "main", target: "ps_5_0""#);
+        assert!(input.src_data.contains("compile_shader"));
+
+        // absolute paths are kept
+        let src = format!(r#"src_file: {:?}, entry_point: "main", target: "ps_5_0""#, path.to_str().unwrap());
+        assert_eq!(parse(&src).src_file, Some(path));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let required = r#"entry_point: "main", target: "ps_5_0""#;
+        let with = |extra: &str| format!(r#"src: "", {}, {}"#, required, extra);
+
+        assert!(parse_err(r#"src: "", entry: "main", target: "ps_5_0""#).starts_with("unknown key `entry`. expected one of src, src_file"));
+        assert_eq!(parse_err(r#"src: "", target: "ps_5_0""#), "missing `entry_point`");
+        assert_eq!(parse_err(r#"src: "", entry_point: "main""#), "missing `target`");
+        assert_eq!(parse_err(required), "missing `src` or `src_file`");
+        assert_eq!(parse_err(""), "missing `src` or `src_file`");
+        assert_eq!(parse_err(&with(r#"target: "ps_5_1""#)), "duplicate key `target`");
+        assert_eq!(parse_err(&format!(r#"src: "", src_file: "Cargo.toml", {}"#, required)), "only one of `src` and `src_file` can be given");
+        assert!(parse_err(&format!(r#"src_file: "does/not/exist.hlsl", {}"#, required)).starts_with("failed to read shader source `"));
+
+        // nul bytes can't be passed to the compiler
+        for extra in [r#"src_name: "a\0b""#, r#"defines: {["A\0", "1"]}"#, r#"defines: {["A", "\0"]}"#] {
+            assert!(parse_err(&with(extra)).contains("nul bytes"), "{}", extra);
+        }
+        assert_eq!(parse_err(r#"src: "", entry_point: "ma\0in", target: "ps_5_0""#), "string can't contain nul bytes");
+
+        // syntax
+        for bad in [
+            r#"src "", entry_point: "main", target: "ps_5_0""#,
+            r#"src: "" entry_point: "main", target: "ps_5_0""#,
+            r#""src": "", entry_point: "main", target: "ps_5_0""#,
+        ] {
+            parse_err(bad);
+        }
+
+        // value types
+        for extra in [
+            r#"flags1: "1""#,
+            "flags1: -1",
+            "flags2: 4294967296",
+            "secondary_data_flags: 1.5",
+            r#"secondary_data: "data""#,
+            r#"defines: { ["A"] }"#,
+            r#"include_dirs: "shared""#,
+            r#"includes: { "a.hlsli" }"#,
+            r#"includes: { "a.hlsli": 1 }"#,
+            "precompiled: blob",
+            "reflect: 1",
+            r#"reflect: "true""#,
+        ] {
+            parse_err(&with(extra));
+        }
+    }
+}
+
+/// keys accepted by `compile_shader!`.
+const KEYS: &[&str] = &["src", "src_file", "src_name", "defines", "entry_point", "target", "flags1", "flags2",
+    "secondary_data_flags", "secondary_data", "precompiled", "include_dirs", "includes", "variants", "reflect", "compiler", "args"];
+
+/// HLSL compiler selected with `compiler`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Compiler {
+    /// `D3DCompile2`, shader model 5.1 and below as DXBC.
+    #[default]
+    Fxc,
+    /// dxcompiler.dll, shader model 6.x as DXIL.
+    Dxc,
+}
+
+#[derive(Clone)]
 struct ShaderMacroInput {
     src_data: String,
 
+    // span of the `src` literal or `src_file` path, compile errors point here
+    src_span: Span,
+
+    // true when the source is the `src` literal
+    #[cfg_attr(not(windows), allow(dead_code))]
+    src_inline: bool,
+
+    // path of `src_file`
+    src_file: Option<PathBuf>,
+
     // this value is null terminated
     src_name: Option<CString>,
 
     // last entry should contain name and description as null pointers
     macros: Vec<ShaderMacro>,
 
-    // marking it empty will put D3D_COMPILE_STANDARD_FILE_INCLUDE
-    // include: &'static [ID3DInclude],
+    // searched for #include after the directory of the including file
+    #[cfg_attr(not(windows), allow(dead_code))]
+    include_dirs: Vec<PathBuf>,
+
+    // in-memory files by include name, searched before the file system
+    includes: Vec<(String, String)>,
 
     // null_terminated string
     entry_point: CString,
@@ -43,38 +259,81 @@ struct ShaderMacroInput {
     secondary_data_flags: u32,
 
     secondary_data: Vec<u8>,
+
+    // blob embedded when the compiler is unavailable, instead of the one looked up by hash
+    precompiled: Option<PathBuf>,
+
+    // define axes of `generate_shader_variants!`, empty for `compile_shader!`
+    variants: Vec<variants::VariantAxis>,
+
+    // also emit the reflection data of the bytecode
+    reflect: bool,
+
+    compiler: Compiler,
+
+    // extra DXC command line arguments
+    #[cfg_attr(not(windows), allow(dead_code))]
+    args: Vec<String>,
 }
 
 impl Parse for ShaderMacroInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let mut src_data: Option<String> = None;
+        let mut src_span = Span::call_site();
+        let mut src_inline = true;
+        let mut src_file: Option<PathBuf> = None;
         let mut src_name: Option<CString> = None;
         let mut macros: Vec<ShaderMacro> = Vec::new();
+        let mut include_dirs: Vec<PathBuf> = Vec::new();
+        let mut includes: Vec<(String, String)> = Vec::new();
         let mut entry_point: Option<CString> = None;
         let mut target: Option<CString> = None;
         let mut flags1: u32 = 0;
         let mut flags2: u32 = 0;
         let mut secondary_data_flags: u32 = 0;
         let mut secondary_data: Vec<u8> = vec![];
-
+        let mut seen: Vec<Ident> = Vec::new();
+        let mut precompiled: Option<PathBuf> = None;
+        let mut variant_axes: Vec<variants::VariantAxis> = Vec::new();
+        let mut reflect = false;
+        let mut compiler = Compiler::Fxc;
+        let mut args: Vec<String> = Vec::new();
+        let mut target_lit: Option<LitStr> = None;
 
         while !input.is_empty() {
-            let name: Ident = input.parse()?;
+            let ident: Ident = input.parse()?;
             input.parse::<Token![:]>()?;
-            let name = name.to_string();
+            let name = ident.to_string();
+            if seen.iter().any(|key| *key == name) {
+                return Err(Error::new(ident.span(), format!("duplicate key `{}`", name)));
+            }
+            if (name == "src" || name == "src_file") && src_data.is_some() {
+                return Err(Error::new(ident.span(), "only one of `src` and `src_file` can be given"));
+            }
             match name.as_str() {
                 "src" => {
-                    src_data = Some(input.parse::<LitStr>()?.value());
+                    let lit = input.parse::<LitStr>()?;
+                    src_data = Some(lit.value());
+                    src_span = lit.span();
+                    src_inline = true;
                 }
                 "src_file" => {
-                    let file_name = input.parse::<LitStr>()?.value();
-                    let abs_path = PathBuf::from(file_name);
+                    let lit = input.parse::<LitStr>()?;
+                    let abs_path = manifest_path(&lit.value());
 
-                    src_data = Some(read_to_string(&abs_path).unwrap());
-                    src_name = Some(CString::new(abs_path.parent().unwrap().to_str().unwrap()).unwrap());
+                    let data = read_to_string(&abs_path).map_err(|e| {
+                        Error::new_spanned(&lit, format!("failed to read shader source `{}`. {}", abs_path.display(), e))
+                    })?;
+                    src_data = Some(data);
+                    src_span = lit.span();
+                    src_inline = false;
+                    // #include is resolved relative to the directory of src_name
+                    src_name = Some(path_c_string(&abs_path, &lit)?);
+                    src_file = Some(abs_path);
                 }
                 "src_name" => {
-                    src_name = Some(CString::new(input.parse::<LitStr>()?.value()).unwrap());
+                    let lit = input.parse::<LitStr>()?;
+                    src_name = Some(path_c_string(&manifest_path(&lit.value()), &lit)?);
                 }
                 "defines" => {
                     let content;
@@ -84,11 +343,25 @@ impl Parse for ShaderMacroInput {
                         macros.push(m)
                     }
                 }
+                "include_dirs" => {
+                    let content;
+                    let _ = bracketed!(content in input);
+                    let dirs: Punctuated<LitStr, Token![,]> = content.parse_terminated(|c| c.parse::<LitStr>(), Comma)?;
+                    include_dirs.extend(dirs.iter().map(|dir| manifest_path(&dir.value())));
+                }
+                "includes" => {
+                    let content;
+                    let _ = braced!(content in input);
+                    let files: Punctuated<IncludeFile, Token![,]> = content.parse_terminated(IncludeFile::parse, Comma)?;
+                    includes.extend(files.into_iter().map(|file| (file.name, file.content)));
+                }
                 "entry_point" => {
-                    entry_point = Some(CString::new(input.parse::<LitStr>()?.value()).unwrap());
+                    entry_point = Some(c_string(&input.parse::<LitStr>()?)?);
                 }
                 "target" => {
-                    target = Some(CString::new(input.parse::<LitStr>()?.value()).unwrap());
+                    let lit = input.parse::<LitStr>()?;
+                    target = Some(c_string(&lit)?);
+                    target_lit = Some(lit);
                 }
                 "flags1" => {
                     flags1 = input.parse::<LitInt>()?.base10_parse::<u32>()?;
@@ -102,8 +375,38 @@ impl Parse for ShaderMacroInput {
                 "secondary_data" => {
                     secondary_data = input.parse::<LitByteStr>()?.value();
                 }
-                _ => panic!("unexpected input provided to shader")
+                "precompiled" => {
+                    precompiled = Some(manifest_path(&input.parse::<LitStr>()?.value()));
+                }
+                "reflect" => {
+                    reflect = input.parse::<LitBool>()?.value;
+                }
+                "compiler" => {
+                    let lit = input.parse::<LitStr>()?;
+                    compiler = match lit.value().as_str() {
+                        "fxc" => Compiler::Fxc,
+                        "dxc" => Compiler::Dxc,
+                        other => return Err(Error::new_spanned(&lit, format!("unknown compiler `{}`. expected `fxc` or `dxc`", other))),
+                    };
+                }
+                "args" => {
+                    let content;
+                    let _ = bracketed!(content in input);
+                    let values: Punctuated<LitStr, Token![,]> = content.parse_terminated(|c| c.parse::<LitStr>(), Comma)?;
+                    args.extend(values.iter().map(LitStr::value));
+                }
+                "variants" => {
+                    let content;
+                    let _ = braced!(content in input);
+                    let axes: Punctuated<variants::VariantAxis, Token![,]> = content.parse_terminated(variants::VariantAxis::parse, Comma)?;
+                    variant_axes.extend(axes);
+                }
+                _ => {
+                    return Err(Error::new(ident.span(), format!("unknown key `{}`. expected one of {}",
+                                                                name, KEYS.join(", "))));
+                }
             }
+            seen.push(ident);
             if input.peek(Token![,]) {
                 input.parse::<Token![,]>()?;
             } else {
@@ -114,20 +417,64 @@ impl Parse for ShaderMacroInput {
             }
         }
 
+        // a define set by an axis would silently override `defines` or another axis
+        for (i, axis) in variant_axes.iter().enumerate() {
+            if macros.iter().any(|m| m.name == axis.define) || variant_axes[..i].iter().any(|a| a.define == axis.define) {
+                return Err(Error::new(axis.span, format!("`{}` is defined more than once", axis.define.to_string_lossy())));
+            }
+        }
+
+        // options that only one of the compilers understands
+        let key = |name: &str| seen.iter().find(|key| *key == name);
+        match compiler {
+            Compiler::Fxc => {
+                if let Some(key) = key("args") {
+                    return Err(Error::new(key.span(), "`args` is only supported with `compiler: \"dxc\"`"));
+                }
+                if let Some(lit) = target_lit.as_ref().filter(|lit| lit.value().contains("_6_")) {
+                    return Err(Error::new_spanned(lit, "FXC only supports shader model 5.1 and below, \
+                                                        shader model 6 targets need `compiler: \"dxc\"`"));
+                }
+            }
+            Compiler::Dxc => {
+                for name in ["flags1", "flags2", "secondary_data_flags", "secondary_data"] {
+                    if let Some(key) = key(name) {
+                        return Err(Error::new(key.span(), format!("`{}` is an FXC option, pass DXC options with `args`", name)));
+                    }
+                }
+                if let Some(key) = key("reflect").filter(|_| reflect) {
+                    return Err(Error::new(key.span(), "`reflect` is only supported with FXC, DXIL carries no RDEF reflection chunk"));
+                }
+            }
+        }
+
+        let missing = |key: &str| Error::new(Span::call_site(), format!("missing `{}`", key));
         Ok(ShaderMacroInput {
-            src_data: src_data.unwrap(),
+            src_data: src_data.ok_or_else(|| missing("src` or `src_file"))?,
+            src_span,
+            src_inline,
+            src_file,
             src_name,
             macros,
-            entry_point: entry_point.unwrap(),
-            target: target.unwrap(),
+            include_dirs,
+            includes,
+            entry_point: entry_point.ok_or_else(|| missing("entry_point"))?,
+            target: target.ok_or_else(|| missing("target"))?,
             flags1,
             flags2,
             secondary_data_flags,
             secondary_data,
+            precompiled,
+            variants: variant_axes,
+            reflect,
+            compiler,
+            args,
         })
     }
 }
 
+/// `["NAME", "value"]` or `("NAME", "value")` entry of `defines`.
+#[derive(Clone)]
 struct ShaderMacro {
     name: CString,
     def: CString,
@@ -136,24 +483,92 @@ struct ShaderMacro {
 impl Parse for ShaderMacro {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let content;
-        bracketed!(content in input);
+        if input.peek(syn::token::Paren) {
+            parenthesized!(content in input);
+        } else {
+            bracketed!(content in input);
+        }
         let name: LitStr = content.parse()?;
         content.parse::<Token![,]>()?;
         let value: LitStr = content.parse()?;
         Ok(Self {
-            name: CString::new(name.value()).unwrap(),
-            def: CString::new(value.value()).unwrap(),
+            name: c_string(&name)?,
+            def: c_string(&value)?,
         })
     }
 }
 
+/// `"name.hlsli": "content"` entry of `includes`.
+struct IncludeFile {
+    name: String,
+    content: String,
+}
+
+impl Parse for IncludeFile {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: LitStr = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let content: LitStr = input.parse()?;
+        Ok(Self { name: name.value(), content: content.value() })
+    }
+}
+
+/// resolve a path of the macro input. relative paths are relative to the root of the crate
+/// invoking the macro, like `include_str!` paths in `build.rs` or tests would be, regardless of the
+/// directory cargo runs rustc in.
+fn manifest_path(path: &str) -> PathBuf {
+    // paths written for windows hosts keep working elsewhere
+    #[cfg(not(windows))]
+    let path = path.replace('\\', "/");
+    manifest_dir().join(path)
+}
+
+/// root of the crate invoking the macro. falls back to the current directory outside of cargo.
+fn manifest_dir() -> PathBuf {
+    var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_default()
+}
+
+fn path_c_string(path: &Path, lit: &LitStr) -> syn::Result<CString> {
+    path.to_str().and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| Error::new_spanned(lit, "path must be valid unicode without nul bytes"))
+}
+
+fn c_string(lit: &LitStr) -> syn::Result<CString> {
+    CString::new(lit.value()).map_err(|_| Error::new_spanned(lit, "string can't contain nul bytes"))
+}
+
 
 /// Compile directx shader at compile time and returns byte code. it uses [`D3DCompile2`](https://docs.microsoft.com/en-us/windows/win32/api/d3dcompiler/nf-d3dcompiler-d3dcompile2).
-/// any unexplained parameters are analogous to that function.
+/// any unexplained parameters are analogous to that function. Shader model 6 is compiled with DXC, see below.
 ///
-/// ## Syntax
+/// Relative paths (`src_file`, `src_name`, `include_dirs` and `precompiled`) are relative to the root
+/// of the crate invoking the macro. Use `/` as separator so they resolve on every host. `src_file` and every file it includes are tracked by cargo, so
+/// editing them rebuilds the crate.
+///
+/// HLSL errors are reported as rust compile errors on the `src` literal or `src_file` path, quoting
+/// the offending line of the shader.
 ///
+/// ## Hosts without the HLSL compiler
+///
+/// FXC and DXC only run on windows. On other hosts, or on windows with `SHADER_MACRO_PRECOMPILED` set, the
+/// macro embeds a `.cso` blob written by an earlier windows build instead. Blobs are stored next to
+/// `src_file` (or `src_name`) as `{stem}.{entry_point}.{target}.{options}.{hash}.cso`, `options`
+/// covering the defines, flags and compiler and `hash` the source and all options, so editing a shader
+/// without regenerating its blob is an error rather than a stale shader. Files included from disk
+/// are listed with their hash in `{blob}.deps` next to the blob and checked the same way.
+///
+/// To regenerate the blobs, rebuild on windows with `SHADER_MACRO_WRITE_CSO` set and commit them
+/// with their `.deps` files:
+///
+/// ```text
+/// cargo clean -p dxfilter
+/// set SHADER_MACRO_WRITE_CSO=1
+/// cargo build --all-targets
 /// ```
+///
+/// ## Syntax
+///
+/// ```ignore
 /// use shader_macro::compile_shader;
 /// compile_shader!{
 ///     src: "some shader source code",  // [required] either src or src_file is required
@@ -163,19 +578,68 @@ impl Parse for ShaderMacro {
 ///     src_name: "path/to/shader/source/file",  // [optional] required for #include if any.
 ///                                             // This is auto generated when src_file is used.
 ///     defines: {                      // [optional] used to define shader macros before compiling
-///         ("DEFINE_1","32"),
+///         ["DEFINE_1","32"],
+///     },
+///     include_dirs: ["path/to/shared/hlsl"], // [optional] searched for #include after the
+///                                            // directory of the including file
+///     includes: {                     // [optional] in-memory files for #include, searched first
+///         "colors.hlsli": "float3 to_linear(float3 c) { return pow(c, 2.2); }",
 ///     },
 ///     flags1: 0,                      // [optional] flags1
 ///     flags2: 0,                      // [optional] flags2
 ///     secondary_data_flags: 0,         // [optional] secondary_data_flags
-///     secondary_data: ""              // [optional] secondary_data
+///     secondary_data: "",             // [optional] secondary_data
+///     precompiled: "path/to/blob.cso", // [optional] blob to embed on hosts without the compiler.
+///                                      // looked up next to the source by hash when omitted.
+///     reflect: false,                  // [optional] also return the reflection data, see below
+///     compiler: "fxc",                 // [optional] "fxc" or "dxc" for shader model 6, see below
+///     args: ["-enable-16bit-types"],   // [optional] extra DXC arguments, only with "dxc"
 /// }
 ///
 /// ```
 ///
-/// ## Example usage
+/// ## Reflection
+///
+/// With `reflect: true` the macro evaluates to `(bytecode, dxfilter::shader::ShaderReflection)`
+/// instead of the bytecode alone. The reflection lists the textures, samplers, cbuffers (with
+/// member offsets) and UAVs the shader uses and its input and output signatures, read from the
+/// compiled shader, so filters can check their bindings when they are created with
+/// `dxfilter::shader::ShaderReflectionExt`:
+///
+/// ```ignore
+/// let (bytecode, reflection) = compile_shader!{
+///     src_file: "src/shaders/tint_ps.hlsl",
+///     entry_point: "main",
+///     target: "ps_5_0",
+///     reflect: true
+/// };
+/// reflection.check_bindings(&[(ResourceKind::Texture, 0), (ResourceKind::Sampler, 0), (ResourceKind::ConstantBuffer, 0)])?;
+/// reflection.check_constants::<TintConstants>(0)?;
+/// let ps = PixelShader::new(&bytecode, device)?;
+/// ```
 ///
+/// ## Shader model 6
+///
+/// FXC stops at shader model 5.1. With `compiler: "dxc"` the shader is compiled by DXC to DXIL,
+/// which allows `*_6_x` targets with wave intrinsics and, with `args: ["-enable-16bit-types"]`,
+/// 16-bit types. dxcompiler.dll is loaded from `SHADER_MACRO_DXC` if set, otherwise from the DLL
+/// search path, and signs the output when it can load dxil.dll as well. Defines, includes and
+/// precompiled blobs work as with FXC, `flags1`, `flags2`, `secondary_data` and `reflect` are FXC
+/// only. DXIL is for D3D12, the D3D11 shaders of this crate can't be created from it.
+///
+/// ```ignore
+/// let dxil = compile_shader!{
+///     src_file: "src/shaders/reduce_cs.hlsl",
+///     entry_point: "main",
+///     target: "cs_6_2",
+///     compiler: "dxc",
+///     args: ["-enable-16bit-types"]
+/// };
 /// ```
+///
+/// ## Example usage
+///
+/// ```ignore
 /// use shader_macro::compile_shader;
 /// let data = compile_shader!{
 ///    src: "
@@ -190,58 +654,259 @@ impl Parse for ShaderMacro {
 #[proc_macro]
 pub fn compile_shader(_input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(_input as ShaderMacroInput);
-    let input_name = PCSTR({
-        if let Some(name) = &input.src_name {
-            name.as_ptr()
-        } else {
-            null()
-        }
-    } as _);
-    let secondary_data: *const u8 = {
-        if input.secondary_data.len() > 0 {
-            input.secondary_data.as_ptr()
-        } else {
-            null()
+    if let Some(axis) = input.variants.first() {
+        return Error::new(axis.span, "`variants` is only supported by `generate_shader_variants!`").to_compile_error().into();
+    }
+    match compile_or_load(&input) {
+        Ok((bytes, deps)) => {
+            let deps = track_deps(&input, &deps);
+            if !input.reflect {
+                return TokenStream::from(quote!({
+                    #deps
+                    [#(#bytes),*]
+                }));
+            }
+            match reflection::parse(&bytes) {
+                Ok(reflection) => {
+                    let reflection = reflection_tokens(&reflection);
+                    TokenStream::from(quote!({
+                        #deps
+                        ([#(#bytes),*], #reflection)
+                    }))
+                }
+                Err(e) => Error::new(input.src_span, format!("can't read the reflection data of the compiled shader. {}", e))
+                    .to_compile_error().into(),
+            }
         }
-    };
+        Err(e) => e.to_compile_error().into(),
+    }
+}
 
-    let mut defines: Vec<D3D_SHADER_MACRO> = input.macros.iter().map(|v| {
-        D3D_SHADER_MACRO {
-            Name: PCSTR(v.name.as_ptr() as _),
-            Definition: PCSTR(v.def.as_ptr() as _),
-        }
-    }).collect();
-    defines.push(D3D_SHADER_MACRO {
-        Name: PCSTR(null()),
-        Definition: PCSTR(null()),
+/// expression building `dxfilter::shader::ShaderReflection` equal to `reflection`.
+fn reflection_tokens(reflection: &reflection::ShaderReflection) -> proc_macro2::TokenStream {
+    let resources = reflection.resources.iter().map(|r| {
+        let (name, slot, count, space) = (&r.name, r.slot, r.count, r.space);
+        let kind = Ident::new(&format!("{:?}", r.kind), Span::call_site());
+        quote!(::dxfilter::shader::BoundResource {
+            name: ::std::string::String::from(#name),
+            kind: ::dxfilter::shader::ResourceKind::#kind,
+            slot: #slot,
+            count: #count,
+            space: #space,
+        })
+    });
+    let constant_buffers = reflection.constant_buffers.iter().map(|cb| {
+        let (name, size) = (&cb.name, cb.size);
+        let variables = cb.variables.iter().map(|v| {
+            let (name, offset, size) = (&v.name, v.offset, v.size);
+            quote!(::dxfilter::shader::ConstantVariable { name: ::std::string::String::from(#name), offset: #offset, size: #size })
+        });
+        quote!(::dxfilter::shader::ConstantBufferLayout {
+            name: ::std::string::String::from(#name),
+            size: #size,
+            variables: ::std::vec![#(#variables),*],
+        })
     });
+    let signature = |elements: &[reflection::SignatureElement]| {
+        let elements = elements.iter().map(|e| {
+            let (semantic, semantic_index, register, mask) = (&e.semantic, e.semantic_index, e.register, e.mask);
+            quote!(::dxfilter::shader::SignatureElement {
+                semantic: ::std::string::String::from(#semantic),
+                semantic_index: #semantic_index,
+                register: #register,
+                mask: #mask,
+            })
+        });
+        quote!(::std::vec![#(#elements),*])
+    };
+    let (inputs, outputs) = (signature(&reflection.inputs), signature(&reflection.outputs));
+    quote!(::dxfilter::shader::ShaderReflection {
+        resources: ::std::vec![#(#resources),*],
+        constant_buffers: ::std::vec![#(#constant_buffers),*],
+        inputs: #inputs,
+        outputs: #outputs,
+    })
+}
 
-    let mut error_msgs: Option<ID3DBlob> = None;
-    let mut shader_bytes: Option<ID3DBlob> = None;
+/// Compile a shader once for every combination of define values and generate a function that
+/// returns the shader for an enum key. The input is that of `compile_shader!` with an additional
+/// `variants` key. Each entry names a define, the enum whose value selects it and the value of the
+/// define for each enum variant:
+///
+/// ```ignore
+/// generate_shader_variants!(argb_to_y ps {
+///     src_file: "src/common_filters/shaders/argb_to_y_ps.hlsl",
+///     entry_point: "main",
+///     target: "ps_5_0",
+///     variants: {
+///         YUV_MATRIX: YuvMatrix { Bt601 => "601", Bt709 => "709", Bt2020 => "2020" },
+///         YUV_FULL_RANGE: YuvRange { Limited => "0", Full => "1" },
+///     }
+/// });
+///
+/// // fn argb_to_y(device: ID3D11Device4, key: (YuvMatrix, YuvRange)) -> Result<PixelShader>
+/// let ps = argb_to_y(device, (YuvMatrix::Bt709, YuvRange::Full))?;
+/// ```
+///
+/// With a single axis the key is the enum itself. Shader kinds are those of `generate_shader!`.
+/// All combinations are compiled and embedded, so the number of axes should stay small. Every enum
+/// variant must be listed, a missing one fails to compile as a non exhaustive match.
+#[proc_macro]
+pub fn generate_shader_variants(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as variants::VariantsMacroInput);
+    variants::generate(&input).into()
+}
 
+/// bytecode of every combination of `generate_shader_variants!`, as `[&[u8]; N]` expression.
+#[doc(hidden)]
+#[proc_macro]
+pub fn compile_shader_variants(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ShaderMacroInput);
+    let lens: Vec<usize> = input.variants.iter().map(|axis| axis.values.len()).collect();
+    let mut blobs = Vec::new();
+    let mut deps: Vec<PathBuf> = Vec::new();
+    let mut errors: Option<Error> = None;
+    for combination in variants::combinations(&lens) {
+        match compile_or_load(&variants::variant_input(&input, &combination)) {
+            Ok((bytes, variant_deps)) => {
+                blobs.push(bytes);
+                for dep in variant_deps {
+                    if !deps.contains(&dep) {
+                        deps.push(dep);
+                    }
+                }
+            }
+            Err(e) => {
+                // the same source fails differently per combination, name the one that failed
+                let description = variants::describe(&input, &combination);
+                for e in e {
+                    let e = Error::new(e.span(), format!("{} (variant {})", e, description));
+                    match &mut errors {
+                        Some(errors) => errors.combine(e),
+                        None => errors = Some(e),
+                    }
+                }
+            }
+        }
+    }
+    if let Some(errors) = errors {
+        return errors.to_compile_error().into();
+    }
+    let deps = track_deps(&input, &deps);
+    TokenStream::from(quote!({
+        #deps
+        [#(&[#(#blobs),*]),*]
+    }))
+}
 
-    let result = unsafe {
-        D3DCompile2(
-            input.src_data.as_ptr() as _,
-            input.src_data.len(), input_name, Some(defines.as_ptr())
-            , core::mem::transmute::<_, &ID3DInclude>(&(D3D_COMPILE_STANDARD_FILE_INCLUDE as u64)), PCSTR(input.entry_point.as_ptr() as _),
-            PCSTR(input.target.as_ptr() as _), input.flags1, input.flags2, input.secondary_data_flags,
-            Some(secondary_data as _),
-            input.secondary_data.len(), &mut shader_bytes, Some(&mut error_msgs))
-    };
-    if result.is_err() {
-        if let Some(error_msgs) = error_msgs {
-            let bytes = unsafe { from_raw_parts(error_msgs.GetBufferPointer() as *const u8, error_msgs.GetBufferSize() as usize) };
-            panic!("failed to compile shader. {}", std::str::from_utf8(bytes).unwrap());
+/// files read during expansion are invisible to cargo, `include_bytes!` of `src_file` and `deps`
+/// makes it rebuild the invoking crate when one of them changes.
+fn track_deps(input: &ShaderMacroInput, deps: &[PathBuf]) -> proc_macro2::TokenStream {
+    let deps = input.src_file.iter().chain(deps).filter_map(|dep| dep.to_str()).map(|dep| LitStr::new(dep, input.src_span));
+    quote!(#(const _: &[u8] = include_bytes!(#deps);)*)
+}
+
+/// bytecode of the shader and the files it depends on.
+#[cfg(windows)]
+fn compile_or_load(input: &ShaderMacroInput) -> syn::Result<(Vec<u8>, Vec<PathBuf>)> {
+    if !precompiled::compiler_available() {
+        return precompiled::load(input);
+    }
+    let include_files: Vec<(String, Vec<u8>)> = input.includes.iter().map(|(name, content)| (name.clone(), content.clone().into_bytes())).collect();
+    let root = input.src_name.as_ref().and_then(|name| name.to_str().ok())
+        .and_then(|name| Path::new(name).parent())
+        .map_or_else(manifest_dir, Path::to_path_buf);
+    let compiled = match input.compiler {
+        Compiler::Fxc => {
+            let include = fxc::IncludeHandler::new(include_files, input.include_dirs.clone(), root);
+            let defines: Vec<_> = input.macros.iter().map(|m| (m.name.as_c_str(), m.def.as_c_str())).collect();
+            let fxc_input = fxc::FxcInput {
+                src: input.src_data.as_bytes(),
+                src_name: input.src_name.as_deref(),
+                defines: &defines,
+                entry_point: &input.entry_point,
+                target: &input.target,
+                flags1: input.flags1,
+                flags2: input.flags2,
+                secondary_data_flags: input.secondary_data_flags,
+                secondary_data: &input.secondary_data,
+                include: &include,
+            };
+            fxc::compile(&fxc_input).map(|bytes| (bytes, include.resolved()))
+        }
+        Compiler::Dxc => {
+            // built from rust strings, so always UTF-8
+            let utf8 = |s: &CString| s.to_string_lossy().into_owned();
+            let defines: Vec<_> = input.macros.iter().map(|m| (utf8(&m.name), utf8(&m.def))).collect();
+            let defines: Vec<_> = defines.iter().map(|(name, def)| (name.as_str(), def.as_str())).collect();
+            let (entry_point, target) = (utf8(&input.entry_point), utf8(&input.target));
+            let dxc_input = dxc::DxcInput {
+                src: input.src_data.as_bytes(),
+                src_name: input.src_name.as_ref().and_then(|name| name.to_str().ok()),
+                defines: &defines,
+                entry_point: &entry_point,
+                target: &target,
+                include_dirs: &input.include_dirs,
+                includes: &include_files,
+                root: &root,
+                args: &input.args,
+            };
+            dxc::compile(&dxc_input)
         }
-        result.unwrap();
+    };
+    let (bytes, deps) = compiled.map_err(|log| {
+        let source = diagnostics::Source {
+            name: input.src_name.as_ref().and_then(|name| name.to_str().ok()),
+            text: &input.src_data,
+            inline: input.src_inline,
+        };
+        diagnostics::compile_error(&log, input.src_span, &source)
+    })?;
+    if precompiled::write_requested() {
+        precompiled::store(input, &bytes, &deps)?;
     }
+    Ok((bytes, deps))
+}
+
+/// bytecode of the shader and the files it depends on.
+#[cfg(not(windows))]
+fn compile_or_load(input: &ShaderMacroInput) -> syn::Result<(Vec<u8>, Vec<PathBuf>)> {
+    precompiled::load(input)
+}
+
 
-    if let Some(shader_bytes) = shader_bytes {
-        let bytes = unsafe { from_raw_parts(shader_bytes.GetBufferPointer() as *const u8, shader_bytes.GetBufferSize() as usize) };
-        return TokenStream::from(quote!(
-        [#(#bytes),*]
-        ));
+/// Derive `dxfilter::constant_buffer::ShaderConstants` for a `#[repr(C)]` struct so it can be
+/// uploaded with `dxfilter::constant_buffer::ConstantBuffer`. Fails to compile when the struct
+/// doesn't follow HLSL constant buffer packing, i.e. when a field straddles a 16 byte boundary,
+/// an array doesn't start on one or the size isn't a multiple of 16.
+///
+/// Supported field types are `f32`, `u32` and `i32`, `[T; 2..=4]` for `floatN`, `uintN` and `intN`
+/// vectors and `[[T; 4]; N]` for arrays. Use `u32` for HLSL `bool`.
+///
+/// ## Example usage
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Clone, Copy, ShaderConstants)]
+/// struct BlurConstants {
+///     texel_size: [f32; 2],
+///     radius: f32,
+///     _padding: f32,
+/// }
+///
+/// // cbuffer BlurConstants : register(b0)
+/// // {
+/// //     float2 texel_size;
+/// //     float radius;
+/// //     float _padding;
+/// // };
+/// println!("{}", BlurConstants::hlsl_cbuffer(0));
+/// ```
+#[proc_macro_derive(ShaderConstants)]
+pub fn derive_shader_constants(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match constants::derive(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
     }
-    panic!("compilation succeeded but no bytes were returned!");
-}
\ No newline at end of file
+}
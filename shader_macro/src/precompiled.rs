@@ -0,0 +1,328 @@
+//! precompiled shader blobs for hosts without the HLSL compiler.
+//!
+//! FXC and DXC are only driven on windows. Everywhere else `compile_shader!` embeds a `.cso` file written by an
+//! earlier windows build instead of compiling. Unless the input names a blob with `precompiled`,
+//! blobs live next to the shader source as `{stem}.{entry_point}.{target}.{options}.{hash}.cso`
+//! where `hash` covers the source and every compile option, so a blob is never used for a source it
+//! wasn't built from. `options` only covers defines, flags and the compiler with its `args`, it
+//! keeps apart blobs of the same source compiled with different defines, e.g. the variants of
+//! `generate_shader_variants!`.
+//!
+//! `includes` are part of the hash. files included from disk are listed next to the blob in
+//! `{blob}.deps`, one `{hash} {path}` line each with the path relative to the crate root, and are
+//! checked against their hash whenever the blob is loaded. line endings are ignored by all hashes,
+//! so checkouts with CRLF and LF agree.
+//!
+//! Blobs are (re)written by any windows build with `SHADER_MACRO_WRITE_CSO` set.
+#[cfg(windows)]
+use std::env::var_os;
+use std::fs::{canonicalize, read, read_to_string};
+#[cfg(windows)]
+use std::fs::{read_dir, remove_file, write};
+use std::path::{Component, Path, PathBuf};
+
+use syn::Error;
+
+use crate::{manifest_dir, Compiler, ShaderMacroInput};
+
+/// environment variable that makes windows builds write a blob for every compiled shader.
+pub(crate) const WRITE_VAR: &str = "SHADER_MACRO_WRITE_CSO";
+
+/// environment variable that makes windows builds embed blobs instead of compiling, to check that
+/// the checked in blobs are complete.
+#[cfg(windows)]
+pub(crate) const FORCE_VAR: &str = "SHADER_MACRO_PRECOMPILED";
+
+/// true when shaders are compiled with FXC rather than loaded from blobs.
+#[cfg(windows)]
+pub(crate) fn compiler_available() -> bool {
+    var_os(FORCE_VAR).is_none()
+}
+
+/// true when compiled shaders should be written as blobs.
+#[cfg(windows)]
+pub(crate) fn write_requested() -> bool {
+    var_os(WRITE_VAR).is_some()
+}
+
+/// FNV-1a
+struct Hasher(u64);
+
+impl Hasher {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        // length prefix, so moving bytes between fields changes the hash
+        for b in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// text with `\r` removed, the same for CRLF and LF checkouts.
+    fn feed_text(&mut self, text: &[u8]) {
+        let text: Vec<u8> = text.iter().copied().filter(|b| *b != b'\r').collect();
+        self.feed(&text);
+    }
+
+    /// defines, flags and compiler, the options that tell variants of a source apart.
+    fn feed_options(&mut self, input: &ShaderMacroInput) {
+        for m in &input.macros {
+            self.feed(m.name.as_bytes());
+            self.feed(m.def.as_bytes());
+        }
+        self.feed(&input.flags1.to_le_bytes());
+        self.feed(&input.flags2.to_le_bytes());
+        // FXC inputs have no `args`, the marker keeps DXC blobs of the same target apart from them
+        if input.compiler == Compiler::Dxc {
+            self.feed(b"dxc");
+            for arg in &input.args {
+                self.feed(arg.as_bytes());
+            }
+        }
+    }
+}
+
+/// hash of the source and compile options.
+fn input_hash(input: &ShaderMacroInput) -> u64 {
+    let mut hasher = Hasher::new();
+    hasher.feed_text(input.src_data.as_bytes());
+    hasher.feed(input.entry_point.as_bytes());
+    hasher.feed(input.target.as_bytes());
+    hasher.feed_options(input);
+    hasher.feed(&input.secondary_data_flags.to_le_bytes());
+    hasher.feed(&input.secondary_data);
+    for (name, content) in &input.includes {
+        hasher.feed(name.as_bytes());
+        hasher.feed_text(content.as_bytes());
+    }
+    hasher.0
+}
+
+/// prefix of the blob names of `input` in [blob_path], shared by all versions of the source
+/// compiled with the same defines and flags.
+fn blob_prefix(input: &ShaderMacroInput) -> (PathBuf, String) {
+    let name = input.src_name.as_ref().and_then(|name| name.to_str().ok()).map(Path::new);
+    let dir = name.and_then(Path::parent).map(Path::to_path_buf).unwrap_or_else(manifest_dir);
+    let stem = name.and_then(Path::file_stem).and_then(|stem| stem.to_str()).unwrap_or("shader");
+    let mut options = Hasher::new();
+    options.feed_options(input);
+    let prefix = format!("{}.{}.{}.{:08x}.", stem, input.entry_point.to_string_lossy(), input.target.to_string_lossy(),
+                         options.0 as u32);
+    (dir, prefix)
+}
+
+/// where the blob of `input` is read from and written to.
+fn blob_path(input: &ShaderMacroInput) -> PathBuf {
+    if let Some(path) = &input.precompiled {
+        return path.clone();
+    }
+    let (dir, prefix) = blob_prefix(input);
+    dir.join(format!("{}{:016x}.cso", prefix, input_hash(input)))
+}
+
+/// the include list of `blob`.
+fn deps_path(blob: &Path) -> PathBuf {
+    let mut name = blob.as_os_str().to_owned();
+    name.push(".deps");
+    PathBuf::from(name)
+}
+
+fn text_hash(text: &[u8]) -> u64 {
+    let mut hasher = Hasher::new();
+    hasher.feed_text(text);
+    hasher.0
+}
+
+/// `path` relative to the crate root with `/` separators so the include list resolves on every
+/// host. paths outside the crate are kept absolute.
+fn crate_relative(path: &Path) -> String {
+    let root = manifest_dir();
+    let root = canonicalize(&root).unwrap_or(root);
+    let path = canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    match path.strip_prefix(&root) {
+        Ok(relative) => relative.components()
+            .filter_map(|c| match c {
+                Component::Normal(c) => Some(c.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
+
+/// contents of the include list for the files a shader included from disk.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn deps_listing(deps: &[PathBuf]) -> std::io::Result<String> {
+    let mut listing = String::new();
+    let mut seen: Vec<String> = Vec::new();
+    for dep in deps {
+        let name = crate_relative(dep);
+        if seen.contains(&name) {
+            continue;
+        }
+        listing.push_str(&format!("{:016x} {}\n", text_hash(&read(dep)?), name));
+        seen.push(name);
+    }
+    Ok(listing)
+}
+
+fn regenerate_hint() -> String {
+    format!("regenerate the blobs on windows with `cargo clean -p <crate>` followed by `{}=1 cargo build`", WRITE_VAR)
+}
+
+/// load the blob of `input` after checking the files it included are unchanged. returns the blob
+/// and the files to track: the blob, its include list and the included files.
+pub(crate) fn load(input: &ShaderMacroInput) -> syn::Result<(Vec<u8>, Vec<PathBuf>)> {
+    let path = blob_path(input);
+    let bytes = read(&path).map_err(|e| Error::new(input.src_span, format!(
+        "the HLSL compiler is not available on this host and the precompiled shader `{}` can't be read ({}). {}",
+        path.display(), e, regenerate_hint())))?;
+    let listing_path = deps_path(&path);
+    let listing = read_to_string(&listing_path).map_err(|e| Error::new(input.src_span, format!(
+        "the include list `{}` of the precompiled shader can't be read ({}). {}", listing_path.display(), e, regenerate_hint())))?;
+
+    let mut deps = vec![path.clone(), listing_path.clone()];
+    for line in listing.lines().filter(|line| !line.trim().is_empty()) {
+        let (hash, name) = line.split_once(' ').ok_or_else(|| Error::new(input.src_span, format!(
+            "malformed line `{}` in `{}`. {}", line, listing_path.display(), regenerate_hint())))?;
+        let file = manifest_dir().join(name.trim_end());
+        let current = read(&file).map(|text| format!("{:016x}", text_hash(&text)));
+        if current.as_deref().ok() != Some(hash) {
+            return Err(Error::new(input.src_span, format!(
+                "`{}` included by the shader changed since the precompiled shader `{}` was written. {}",
+                file.display(), path.display(), regenerate_hint())));
+        }
+        deps.push(file);
+    }
+    Ok((bytes, deps))
+}
+
+/// write `bytes` as the blob of `input` with the include list of `deps`, removing blobs of older
+/// versions of the same source.
+#[cfg(windows)]
+pub(crate) fn store(input: &ShaderMacroInput, bytes: &[u8], deps: &[PathBuf]) -> syn::Result<()> {
+    let path = blob_path(input);
+    if input.precompiled.is_none() {
+        let (dir, prefix) = blob_prefix(input);
+        if let Ok(entries) = read_dir(&dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                let stale = entry.path() != path && entry.path() != deps_path(&path);
+                if name.starts_with(&prefix) && (name.ends_with(".cso") || name.ends_with(".cso.deps")) && stale {
+                    let _ = remove_file(entry.path());
+                }
+            }
+        }
+    }
+    let write_err = |path: &Path, e: std::io::Error| Error::new(input.src_span, format!("failed to write precompiled shader `{}`. {}", path.display(), e));
+    write(&path, bytes).map_err(|e| write_err(&path, e))?;
+    let listing_path = deps_path(&path);
+    deps_listing(deps).and_then(|listing| write(&listing_path, listing)).map_err(|e| write_err(&listing_path, e))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use std::fs::{create_dir_all, write};
+
+    use crate::precompiled::{blob_path, blob_prefix, crate_relative, deps_listing, input_hash, load};
+    use crate::ShaderMacroInput;
+
+    fn parse(src: &str) -> ShaderMacroInput {
+        syn::parse_str(src).unwrap()
+    }
+
+    #[test]
+    fn test_blob_path() {
+        let input = parse(r#"src: "float4 main() : SV_Target { return 0; }", src_name: "shaders/blur_ps.hlsl", entry_point: "main", target: "ps_5_0""#);
+        let path = blob_path(&input);
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(path.parent(), Some(root.join("shaders").as_path()));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("blur_ps.main.ps_5_0.") && name.ends_with(".cso"), "{}", name);
+
+        let input = parse(r#"src: "", entry_point: "main", target: "ps_5_0", precompiled: "blobs/blur.cso""#);
+        assert_eq!(blob_path(&input), root.join("blobs/blur.cso"));
+
+        let input = parse(r#"src: "", entry_point: "main", target: "ps_5_0""#);
+        assert_eq!(blob_path(&input).parent(), Some(root.as_path()));
+    }
+
+    #[test]
+    fn test_input_hash() {
+        let base = input_hash(&parse(r#"src: "a", entry_point: "main", target: "ps_5_0""#));
+        assert_eq!(base, input_hash(&parse(r#"src: "a", entry_point: "main", target: "ps_5_0""#)));
+        for other in [
+            r#"src: "b", entry_point: "main", target: "ps_5_0""#,
+            r#"src: "a", entry_point: "main2", target: "ps_5_0""#,
+            r#"src: "a", entry_point: "main", target: "ps_5_1""#,
+            r#"src: "a", entry_point: "main", target: "ps_5_0", defines: {["A", "1"]}"#,
+            r#"src: "a", entry_point: "main", target: "ps_5_0", flags1: 1"#,
+            r#"src: "a", entry_point: "main", target: "ps_5_0", compiler: "dxc""#,
+        ] {
+            assert_ne!(base, input_hash(&parse(other)), "{}", other);
+        }
+    }
+
+    #[test]
+    fn test_load_checks_includes() {
+        let dir = std::env::temp_dir().join(format!("shader_macro_precompiled_{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let header = dir.join("colors.hlsli");
+        write(&header, "float3 f() { return 0; }\r\n").unwrap();
+        let blob = dir.join("main.cso");
+        write(&blob, [1u8, 2, 3]).unwrap();
+        let input = parse(&format!(r#"src: "", entry_point: "main", target: "ps_5_0", precompiled: {:?}"#, blob.to_str().unwrap()));
+
+        let err = |input: &ShaderMacroInput| match load(input) {
+            Ok(_) => panic!("expected load to fail"),
+            Err(e) => e.to_string(),
+        };
+        assert!(err(&input).contains("include list"), "{}", err(&input));
+
+        write(dir.join("main.cso.deps"), deps_listing(&[header.clone(), header.clone()]).unwrap()).unwrap();
+        let (bytes, deps) = load(&input).unwrap();
+        assert_eq!(bytes, [1, 2, 3]);
+        assert_eq!(deps.len(), 3);
+        assert!(deps[2].ends_with("colors.hlsli"));
+
+        // line endings don't matter, content does
+        write(&header, "float3 f() { return 0; }\n").unwrap();
+        load(&input).unwrap();
+        write(&header, "float3 f() { return 1; }\n").unwrap();
+        assert!(err(&input).contains("changed since the precompiled shader"), "{}", err(&input));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_crate_relative() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(crate_relative(&root.join("src").join("lib.rs")), "src/lib.rs");
+    }
+
+    #[test]
+    fn test_blob_prefix() {
+        let base = blob_prefix(&parse(r#"src: "a", entry_point: "main", target: "ps_5_0""#));
+        // new versions of the source replace the old blob
+        assert_eq!(base, blob_prefix(&parse(r#"src: "b", entry_point: "main", target: "ps_5_0""#)));
+        // other defines of the same source are kept side by side
+        for other in [
+            r#"src: "a", entry_point: "main", target: "ps_5_0", defines: {["A", "1"]}"#,
+            r#"src: "a", entry_point: "main", target: "ps_5_0", defines: {["A", "2"]}"#,
+            r#"src: "a", entry_point: "main", target: "ps_5_0", flags1: 1"#,
+            r#"src: "a", entry_point: "main", target: "ps_5_0", compiler: "dxc""#,
+            r#"src: "a", entry_point: "main", target: "ps_5_0", compiler: "dxc", args: ["-enable-16bit-types"]"#,
+        ] {
+            let prefix = blob_prefix(&parse(other));
+            assert_ne!(base, prefix, "{}", other);
+            assert!(!prefix.1.starts_with(&base.1) && !base.1.starts_with(&prefix.1), "{}", other);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! `generate_shader_variants!`: compiles a shader once per combination of define values and
+//! looks the bytecode up by an enum key.
+//!
+//! Every `variants` axis names a define, the enum selecting its value and the value for each enum
+//! variant. Combinations are laid out row-major, the last axis varying fastest, so the generated
+//! lookup is a sum of one `match` per axis.
+use std::ffi::CString;
+
+use proc_macro2::{Delimiter, Group, Span, TokenStream};
+use quote::quote;
+use syn::{braced, Error, Ident, Index, LitStr, Path, Token};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+
+use crate::{c_string, Compiler, ShaderMacroInput};
+
+#[cfg(test)]
+mod test {
+    use crate::ShaderMacroInput;
+    use crate::variants::{combinations, VariantsMacroInput};
+
+    #[test]
+    fn test_parse_variants() {
+        let input: ShaderMacroInput = syn::parse_str(r#"
+            src: "", entry_point: "main", target: "ps_5_0",
+            variants: {
+                YUV_MATRIX: crate::color::YuvMatrix { Bt601 => "601", Bt709 => "709", Bt2020 => "2020" },
+                FULL: Range { Limited => "0", Full => "1", },
+            },
+        "#).unwrap();
+        assert_eq!(input.variants.len(), 2);
+        let axis = &input.variants[0];
+        assert_eq!(axis.define.to_str().unwrap(), "YUV_MATRIX");
+        let ty = &axis.ty;
+        assert_eq!(quote::quote!(#ty).to_string().replace(' ', ""), "crate::color::YuvMatrix");
+        let values: Vec<_> = axis.values.iter().map(|(variant, def)| (variant.to_string(), def.to_str().unwrap().to_owned())).collect();
+        assert_eq!(values, vec![("Bt601".to_owned(), "601".to_owned()), ("Bt709".to_owned(), "709".to_owned()), ("Bt2020".to_owned(), "2020".to_owned())]);
+        assert_eq!(input.variants[1].values.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_variants_errors() {
+        let with = |variants: &str| {
+            let src = format!(r#"src: "", entry_point: "main", target: "ps_5_0", defines: {{ ["A", "1"] }}, variants: {{ {} }}"#, variants);
+            match syn::parse_str::<ShaderMacroInput>(&src) {
+                Ok(_) => panic!("expected {} to fail", variants),
+                Err(e) => e.to_string(),
+            }
+        };
+        assert_eq!(with("B: Mode {}"), "variant axis `B` has no values");
+        assert_eq!(with(r#"B: Mode { X => "0", X => "1" }"#), "duplicate variant `X`");
+        assert_eq!(with(r#"B: Mode { X => "0" }, B: Other { Y => "0" }"#), "`B` is defined more than once");
+        assert_eq!(with(r#"A: Mode { X => "0" }"#), "`A` is defined more than once");
+        assert!(with(r#"B: Mode { X => "\0" }"#).contains("nul bytes"));
+        with(r#"B: Mode { X: "0" }"#);
+        with(r#"B: Mode { X => 0 }"#);
+        with(r#""B": Mode { X => "0" }"#);
+    }
+
+    #[test]
+    fn test_combinations() {
+        assert_eq!(combinations(&[3]), vec![vec![0], vec![1], vec![2]]);
+        assert_eq!(combinations(&[2, 3]), vec![
+            vec![0, 0], vec![0, 1], vec![0, 2],
+            vec![1, 0], vec![1, 1], vec![1, 2],
+        ]);
+        assert_eq!(combinations(&[]), vec![Vec::<usize>::new()]);
+    }
+
+    #[test]
+    fn test_parse_macro_input() {
+        let input: VariantsMacroInput = syn::parse_str(r#"to_y ps {
+            src: "", entry_point: "main", target: "ps_5_0",
+            variants: { M: Matrix { A => "0", B => "1" } }
+        }"#).unwrap();
+        assert_eq!(input.name.to_string(), "to_y");
+        assert_eq!(input.shader.to_string(), "PixelShader");
+
+        let err = |src: &str| match syn::parse_str::<VariantsMacroInput>(src) {
+            Ok(_) => panic!("expected {} to fail", src),
+            Err(e) => e.to_string(),
+        };
+        assert!(err(r#"f xs { src: "", entry_point: "main", target: "ps_5_0", variants: { M: T { A => "0" } } }"#)
+            .starts_with("unknown shader kind `xs`"));
+        assert_eq!(err(r#"f ps { src: "", entry_point: "main", target: "ps_5_0" }"#), "missing `variants`");
+        assert!(err(r#"f ps { src: "", entry_point: "main", target: "ps_5_0", reflect: true, variants: { M: T { A => "0" } } }"#)
+            .contains("`reflect` is not supported"));
+        assert!(err(r#"f cs { src: "", entry_point: "main", target: "cs_6_0", compiler: "dxc", variants: { M: T { A => "0" } } }"#)
+            .contains("can't be created from the DXIL"));
+        err(r#"f ps ( src: "", entry_point: "main", target: "ps_5_0", variants: { M: T { A => "0" } } )"#);
+    }
+}
+
+/// `DEFINE: path::to::Enum { Variant => "value", .. }` entry of `variants`.
+#[derive(Clone)]
+pub(crate) struct VariantAxis {
+    pub define: CString,
+    pub span: Span,
+    pub ty: Path,
+    pub values: Vec<(Ident, CString)>,
+}
+
+impl Parse for VariantAxis {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let define: Ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.call(Path::parse_mod_style)?;
+        let content;
+        braced!(content in input);
+        let entries: Punctuated<VariantValue, Token![,]> = content.parse_terminated(VariantValue::parse, Comma)?;
+        if entries.is_empty() {
+            return Err(Error::new(define.span(), format!("variant axis `{}` has no values", define)));
+        }
+        let mut values: Vec<(Ident, CString)> = Vec::new();
+        for entry in entries {
+            if values.iter().any(|(variant, _)| *variant == entry.variant) {
+                return Err(Error::new(entry.variant.span(), format!("duplicate variant `{}`", entry.variant)));
+            }
+            values.push((entry.variant, entry.value));
+        }
+        Ok(Self {
+            define: CString::new(define.to_string()).expect("identifiers have no nul bytes"),
+            span: define.span(),
+            ty,
+            values,
+        })
+    }
+}
+
+/// `Variant => "value"`
+struct VariantValue {
+    variant: Ident,
+    value: CString,
+}
+
+impl Parse for VariantValue {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let variant: Ident = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let value = c_string(&input.parse::<LitStr>()?)?;
+        Ok(Self { variant, value })
+    }
+}
+
+/// input of `generate_shader_variants!`: `fn_name kind { compile_shader! input with variants }`.
+pub(crate) struct VariantsMacroInput {
+    pub name: Ident,
+    /// `dxfilter::shader` struct produced for the shader kind.
+    pub shader: Ident,
+    /// the braced `compile_shader!` input, passed on to `compile_shader_variants!` as is.
+    pub content: Group,
+    pub input: ShaderMacroInput,
+}
+
+/// shader kinds of `generate_shader!` with the struct they produce.
+const KINDS: &[(&str, &str)] = &[("vs", "VertexShader"), ("ps", "PixelShader"), ("cs", "ComputeShader"),
+    ("gs", "GeometryShader"), ("hs", "HullShader"), ("ds", "DomainShader")];
+
+impl Parse for VariantsMacroInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        let kind: Ident = input.parse()?;
+        let shader = KINDS.iter().find(|(k, _)| kind == k)
+            .map(|(_, shader)| Ident::new(shader, kind.span()))
+            .ok_or_else(|| Error::new(kind.span(), format!("unknown shader kind `{}`. expected one of {}",
+                                                          kind, KINDS.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(", "))))?;
+        let content: Group = input.parse()?;
+        if content.delimiter() != Delimiter::Brace {
+            return Err(Error::new(content.span(), "expected `{ .. }`"));
+        }
+        let shader_input: ShaderMacroInput = syn::parse2(content.stream())?;
+        if shader_input.variants.is_empty() {
+            return Err(Error::new(content.span(), "missing `variants`"));
+        }
+        if shader_input.reflect {
+            return Err(Error::new(content.span(), "`reflect` is not supported by `generate_shader_variants!`"));
+        }
+        if shader_input.compiler == Compiler::Dxc {
+            return Err(Error::new(content.span(), "D3D11 shaders can't be created from the DXIL of `compiler: \"dxc\"`"));
+        }
+        Ok(Self { name, shader, content, input: shader_input })
+    }
+}
+
+/// every combination of value indices of axes with the given lengths, last axis fastest.
+pub(crate) fn combinations(lens: &[usize]) -> Vec<Vec<usize>> {
+    let mut all = vec![Vec::new()];
+    for &len in lens {
+        all = all.into_iter()
+            .flat_map(|prefix| (0..len).map(move |i| {
+                let mut combination = prefix.clone();
+                combination.push(i);
+                combination
+            }))
+            .collect();
+    }
+    all
+}
+
+/// `input` with the defines of one combination added.
+pub(crate) fn variant_input(input: &ShaderMacroInput, combination: &[usize]) -> ShaderMacroInput {
+    let mut variant = input.clone();
+    for (axis, &i) in input.variants.iter().zip(combination) {
+        variant.macros.push(crate::ShaderMacro { name: axis.define.clone(), def: axis.values[i].1.clone() });
+    }
+    variant.variants.clear();
+    variant
+}
+
+/// `A=1, B=2` for messages about a combination.
+pub(crate) fn describe(input: &ShaderMacroInput, combination: &[usize]) -> String {
+    input.variants.iter().zip(combination)
+        .map(|(axis, &i)| format!("{}={}", axis.define.to_string_lossy(), axis.values[i].1.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// the lookup function of `generate_shader_variants!`.
+pub(crate) fn generate(input: &VariantsMacroInput) -> TokenStream {
+    let VariantsMacroInput { name, shader, content, input } = input;
+    let axes = &input.variants;
+    let count = axes.iter().map(|axis| axis.values.len()).product::<usize>();
+
+    let tys = axes.iter().map(|axis| &axis.ty);
+    let key_ty = if axes.len() == 1 { quote!(#(#tys)*) } else { quote!((#(#tys),*)) };
+
+    let mut stride = count;
+    let terms = axes.iter().enumerate().map(|(i, axis)| {
+        stride /= axis.values.len();
+        let field = if axes.len() == 1 { quote!(key) } else {
+            let i = Index::from(i);
+            quote!(key.#i)
+        };
+        let ty = &axis.ty;
+        let arms = axis.values.iter().enumerate().map(|(j, (variant, _))| quote!(#ty::#variant => #j,));
+        quote!((match #field { #(#arms)* }) * #stride)
+    }).collect::<Vec<_>>();
+
+    quote! {
+        fn #name(device: ::dxfilter::ID3D11Device4, key: #key_ty) -> ::dxfilter::Result<::dxfilter::shader::#shader> {
+            #[cfg(not(doc))]
+            {
+                const BLOBS: [&[u8]; #count] = ::dxfilter::compile_shader_variants! #content;
+                ::dxfilter::shader::#shader::new(BLOBS[#(#terms)+*], device)
+            }
+            #[cfg(doc)]
+            unimplemented!()
+        }
+    }
+}
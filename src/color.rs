@@ -1,3 +1,25 @@
 //! contains color definitions and tools for converting color from one format to another
 //! 
-pub use win_desktop_duplication::texture::ColorFormat;
\ No newline at end of file
+pub use win_desktop_duplication::texture::ColorFormat;
+
+/// Y'CbCr matrix used when converting RGB to YUV or NV12.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum YuvMatrix {
+    /// ITU-R BT.601, standard definition video.
+    #[default]
+    Bt601,
+    /// ITU-R BT.709, HD video.
+    Bt709,
+    /// ITU-R BT.2020 non constant luminance, UHD video.
+    Bt2020,
+}
+
+/// range of the Y'CbCr values written when converting RGB to YUV or NV12.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum YuvRange {
+    /// studio range, Y in 16-235 and CbCr in 16-240 for 8 bit output.
+    #[default]
+    Limited,
+    /// Y and CbCr use the whole 0-255 range for 8 bit output.
+    Full,
+}
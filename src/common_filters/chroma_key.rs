@@ -0,0 +1,268 @@
+use win_desktop_duplication::texture::{ColorFormat, Texture};
+use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP;
+use windows::Win32::Graphics::Direct3D11::{D3D11_VIEWPORT, ID3D11Device4, ID3D11DeviceContext4, ID3D11RenderTargetView, ID3D11SamplerState, ID3D11ShaderResourceView};
+
+use crate::{DxFilter, Result};
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{PixelShader, ResourceKind, ShaderReflectionExt, ShaderStage, VertexShader};
+
+use super::{create_rtv, create_srv, create_tex_sampler, rgba_view_format, simple_vs};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::tex_reader::TextureReader;
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{ChromaKeyARGB, ChromaKeyParams};
+    use crate::DxFilter;
+    use crate::utils::{create_input_texture, create_output_texture};
+
+    #[test]
+    fn test_chroma_key() {
+        let (device, ctx) = build_device_and_ctx();
+
+        // BGRA pixels: pure green backdrop followed by a red subject.
+        let width = 2u32;
+        let height = 1u32;
+        let src = vec![0, 255, 0, 255, 0, 0, 255, 255];
+
+        let desc = TextureDesc { width, height, format: ColorFormat::ARGB8UNorm };
+        let input_tex = create_input_texture(&device, desc, Some(src)).unwrap();
+        let output_tex = create_output_texture(&device, desc, None).unwrap();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+
+        let filter = ChromaKeyARGB::new(&input_tex, &output_tex, ChromaKeyParams::default(), &device).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+
+        let mut out = Vec::new();
+        reader.get_data(&mut out, &output_tex).unwrap();
+
+        // the backdrop becomes fully transparent, premultiplied colour is black.
+        assert_eq!(&out[0..4], &[0, 0, 0, 0]);
+        // the subject stays opaque and keeps its colour.
+        assert_eq!(out[7], 255);
+        assert!(out[6] >= 250 && out[5] <= 5, "subject colour changed {:?}", &out[4..8]);
+
+        assert!(ChromaKeyARGB::new(&input_tex, &output_tex, ChromaKeyParams { softness: -1.0, ..Default::default() }, &device).is_err());
+    }
+}
+
+/// checks the registers and constants of the shader against what [ChromaKeyARGB] binds, so a
+/// mismatch fails at construction instead of rendering garbage.
+fn chroma_key_ps(device: ID3D11Device4) -> Result<PixelShader> {
+    #[cfg(not(doc))]
+    {
+        let (bytecode, reflection) = crate::compile_shader! {
+            src_file: "src/common_filters/shaders/chroma_key_ps.hlsl",
+            entry_point: "main",
+            target: "ps_5_0",
+            reflect: true
+        };
+        reflection.check_bindings(&[(ResourceKind::Texture, 0), (ResourceKind::Sampler, 0), (ResourceKind::ConstantBuffer, 0)])?;
+        reflection.check_constants::<ChromaKeyConstants>(0)?;
+        PixelShader::new(&bytecode, device)
+    }
+    #[cfg(doc)]
+    unimplemented!()
+}
+
+/// Keying parameters used by [ChromaKeyARGB]. Distances are measured between CbCr chroma
+/// coordinates, which range roughly over `-0.5..=0.5`, so a tolerance of `0.2` is already wide.
+/// [Default] keys a pure green screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChromaKeyParams {
+    /// colour of the backdrop as normalized `[r, g, b]`.
+    pub key_color: [f32; 3],
+    /// chroma distance below which pixels become fully transparent.
+    pub tolerance: f32,
+    /// width of the ramp from transparent to opaque beyond `tolerance`. `0.0` gives a hard edge.
+    pub softness: f32,
+    /// how much of the key colour is removed from remaining pixels. `0.0` disables spill
+    /// suppression, `1.0` removes all of it.
+    pub spill_suppression: f32,
+}
+
+impl Default for ChromaKeyParams {
+    fn default() -> Self {
+        Self {
+            key_color: [0.0, 1.0, 0.0],
+            tolerance: 0.15,
+            softness: 0.1,
+            spill_suppression: 0.5,
+        }
+    }
+}
+
+impl ChromaKeyParams {
+    fn validate(&self) -> Result<()> {
+        if !self.key_color.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err(DxFilterErr::BadParam(format!("key color must be within [0,1], found {:?}", self.key_color)));
+        }
+        if !(self.tolerance >= 0.0 && self.softness >= 0.0 && self.tolerance.is_finite() && self.softness.is_finite()) {
+            return Err(DxFilterErr::BadParam(format!("tolerance and softness must be non-negative numbers, found {:?}", self)));
+        }
+        if !(0.0..=1.0).contains(&self.spill_suppression) {
+            return Err(DxFilterErr::BadParam(format!("spill suppression must be within [0,1], found {}", self.spill_suppression)));
+        }
+        Ok(())
+    }
+}
+
+/// layout of `ChromaKeyConstants` cbuffer in chroma_key_ps.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct ChromaKeyConstants {
+    key_chroma: [f32; 2],
+    tolerance: f32,
+    softness: f32,
+    spill_suppression: f32,
+    _padding: [f32; 3],
+}
+
+impl From<&ChromaKeyParams> for ChromaKeyConstants {
+    fn from(params: &ChromaKeyParams) -> Self {
+        // BT.601 CbCr, same as ToChroma in the shader
+        let [r, g, b] = params.key_color;
+        Self {
+            key_chroma: [
+                -0.168736 * r - 0.331264 * g + 0.5 * b,
+                0.5 * r - 0.418688 * g - 0.081312 * b,
+            ],
+            tolerance: params.tolerance,
+            softness: params.softness,
+            spill_suppression: params.spill_suppression,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// Filter for keying out a backdrop colour (green screen) from [ARGBUNorm][ColorFormat::ARGB8UNorm]
+/// or [ABGRUNorm][ColorFormat::ABGR8UNorm] textures, e.g. a presenter camera. filter also scales
+/// automatically based on input and output textures.
+///
+/// output has **premultiplied** alpha, so it can be drawn over captured frames with
+/// `D3D11_BLEND_ONE` / `D3D11_BLEND_INV_SRC_ALPHA` blending without fringes around the subject.
+pub struct ChromaKeyARGB {
+    device: ID3D11Device4,
+    vs: VertexShader,
+    ps: PixelShader,
+
+    params: ChromaKeyParams,
+    cb: ConstantBuffer<ChromaKeyConstants>,
+
+    _in_tex: Texture,
+    _out_tex: Texture,
+
+    srv: ID3D11ShaderResourceView,
+    rtv: ID3D11RenderTargetView,
+    sampler: ID3D11SamplerState,
+}
+
+impl ChromaKeyARGB {
+    /// create new instance of ChromaKeyARGB filter. After creation, filter takes ARGB or ABGR
+    /// input from `input_tex` and writes premultiplied ARGB or ABGR to `out_tex`.
+    pub fn new(input_tex: &Texture, out_tex: &Texture, params: ChromaKeyParams, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        Self::validate_output(out_tex)?;
+        params.validate()?;
+
+        let ps = chroma_key_ps(device.clone())?;
+        let vs = simple_vs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &ChromaKeyConstants::default())?;
+
+        let srv = create_srv(device, input_tex, rgba_view_format(input_tex.desc().format))?;
+        let sampler = create_tex_sampler(device)?;
+        let rtv = create_rtv(device, out_tex, rgba_view_format(out_tex.desc().format))?;
+
+        return Ok(Self {
+            device: device.clone(),
+            vs,
+            ps,
+            params,
+            cb,
+            _in_tex: input_tex.clone(),
+            _out_tex: out_tex.clone(),
+            srv,
+            rtv,
+            sampler,
+        });
+    }
+
+    /// current keying parameters.
+    pub fn params(&self) -> ChromaKeyParams {
+        return self.params;
+    }
+
+    /// change keying parameters. takes effect on the next [apply_filter][DxFilter::apply_filter].
+    pub fn set_params(&mut self, params: ChromaKeyParams) -> Result<()> {
+        params.validate()?;
+        self.params = params;
+        return Ok(());
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format)))
+            }
+        }
+    }
+    fn validate_output(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format)))
+            }
+        }
+    }
+}
+
+impl DxFilter for ChromaKeyARGB {
+    fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        let out_desc = self._out_tex.desc();
+        let vp = D3D11_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: out_desc.width as _,
+            Height: out_desc.height as _,
+            MinDepth: 0.0,
+            MaxDepth: 0.0,
+        };
+        self.cb.update(ctx, &ChromaKeyConstants::from(&self.params))?;
+        unsafe {
+            ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
+            ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
+            ctx.PSSetShader(self.ps.as_raw_ref(), Some(&[]));
+            self.cb.bind(ctx, ShaderStage::Pixel, 0);
+            ctx.PSSetSamplers(0, Some(&[Some(self.sampler.clone())]));
+            ctx.PSSetShaderResources(0, Some(&[Some(self.srv.clone())]));
+            ctx.RSSetViewports(Some(&[vp]));
+            ctx.OMSetRenderTargets(Some(&[Some(self.rtv.clone())]), None);
+            ctx.Draw(4, 0);
+        }
+        return Ok(());
+    }
+
+    fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        ChromaKeyARGB::validate_input(tex)?;
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+
+    fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
+        ChromaKeyARGB::validate_output(tex)?;
+        self._out_tex = tex.clone();
+        self.rtv = create_rtv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,276 @@
+use win_desktop_duplication::texture::{ColorFormat, Texture};
+use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP;
+use windows::Win32::Graphics::Direct3D11::{D3D11_VIEWPORT, ID3D11Device4, ID3D11DeviceContext4, ID3D11RenderTargetView, ID3D11SamplerState, ID3D11ShaderResourceView};
+
+use crate::{DxFilter, Result};
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{PixelShader, ShaderStage, VertexShader};
+
+use super::{create_rtv, create_srv, create_tex_sampler, rgba_view_format, simple_vs};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::tex_reader::TextureReader;
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{ColorAdjustARGB, ColorAdjustParams};
+    use crate::DxFilter;
+    use crate::utils::{create_input_texture, create_output_texture};
+
+    use super::ColorAdjustConstants;
+
+    #[test]
+    fn test_hue_rotation() {
+        let identity = ColorAdjustConstants::from(&ColorAdjustParams::default());
+        assert!((identity.hue_row0[0] - 1.0).abs() < 1e-6);
+        assert!(identity.hue_row0[1].abs() < 1e-6);
+
+        // rotating hue by 120 degrees turns red into green.
+        let rotated = ColorAdjustConstants::from(&ColorAdjustParams { hue: 120.0, ..Default::default() });
+        let red = [1.0f32, 0.0, 0.0];
+        let dot = |row: [f32; 4]| row[0] * red[0] + row[1] * red[1] + row[2] * red[2];
+        assert!(dot(rotated.hue_row0).abs() < 1e-5);
+        assert!((dot(rotated.hue_row1) - 1.0).abs() < 1e-5);
+        assert!(dot(rotated.hue_row2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_color_adjust() {
+        let (device, ctx) = build_device_and_ctx();
+
+        let desc = TextureDesc { width: 4, height: 4, format: ColorFormat::ARGB8UNorm };
+        let input_tex = create_input_texture(&device, desc, Some(vec![64; 4 * 4 * 4])).unwrap();
+        let output_tex = create_output_texture(&device, desc, None).unwrap();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+
+        let mut filter = ColorAdjustARGB::new(&input_tex, &output_tex, &device).unwrap();
+        let mut out = Vec::new();
+
+        // default parameters leave the picture untouched.
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert_eq!(out[0], 64);
+
+        // brightness is added in normalized units.
+        filter.set_params(ColorAdjustParams { brightness: 0.25, ..Default::default() }).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert!((127..=129).contains(&out[0]), "unexpected brightness result {}", out[0]);
+
+        assert!(filter.set_params(ColorAdjustParams { gamma: 0.0, ..Default::default() }).is_err());
+    }
+}
+
+generate_shader!(color_adjust_ps ps {
+    src_file: "src/common_filters/shaders/color_adjust_ps.hlsl",
+    entry_point: "main",
+    target: "ps_5_0"
+});
+
+/// Picture controls used by [ColorAdjustARGB]. All values operate on gamma-encoded RGB
+/// normalized to `[0,1]`. [Default] leaves the picture unchanged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorAdjustParams {
+    /// offset added to every channel. `0.0` is neutral, typically within `-1.0..=1.0`.
+    pub brightness: f32,
+    /// scale around mid grey. `1.0` is neutral, `0.0` gives flat grey.
+    pub contrast: f32,
+    /// `1.0` is neutral, `0.0` gives greyscale, above `1.0` boosts colors.
+    pub saturation: f32,
+    /// hue rotation in degrees. `0.0` is neutral.
+    pub hue: f32,
+    /// gamma applied last as `c^(1/gamma)`. `1.0` is neutral, above `1.0` lifts dark areas.
+    pub gamma: f32,
+}
+
+impl Default for ColorAdjustParams {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ColorAdjustParams {
+    fn validate(&self) -> Result<()> {
+        let finite = [self.brightness, self.contrast, self.saturation, self.hue, self.gamma]
+            .iter().all(|v| v.is_finite());
+        if !finite {
+            return Err(DxFilterErr::BadParam(format!("color adjust parameters must be finite, found {:?}", self)));
+        }
+        if self.contrast < 0.0 || self.saturation < 0.0 {
+            return Err(DxFilterErr::BadParam(format!("contrast and saturation must not be negative, found {:?}", self)));
+        }
+        if self.gamma <= 0.0 {
+            return Err(DxFilterErr::BadParam(format!("gamma must be positive, found {}", self.gamma)));
+        }
+        Ok(())
+    }
+}
+
+/// layout of `ColorAdjustConstants` cbuffer in color_adjust_ps.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct ColorAdjustConstants {
+    hue_row0: [f32; 4],
+    hue_row1: [f32; 4],
+    hue_row2: [f32; 4],
+    brightness: f32,
+    contrast: f32,
+    saturation: f32,
+    inv_gamma: f32,
+}
+
+impl From<&ColorAdjustParams> for ColorAdjustConstants {
+    fn from(params: &ColorAdjustParams) -> Self {
+        // rotation around the grey axis (1,1,1)/sqrt(3)
+        let (sin, cos) = params.hue.to_radians().sin_cos();
+        let diag = cos + (1.0 - cos) / 3.0;
+        let a = (1.0 - cos) / 3.0 - (1.0f32 / 3.0).sqrt() * sin;
+        let b = (1.0 - cos) / 3.0 + (1.0f32 / 3.0).sqrt() * sin;
+        Self {
+            hue_row0: [diag, a, b, 0.0],
+            hue_row1: [b, diag, a, 0.0],
+            hue_row2: [a, b, diag, 0.0],
+            brightness: params.brightness,
+            contrast: params.contrast,
+            saturation: params.saturation,
+            inv_gamma: 1.0 / params.gamma,
+        }
+    }
+}
+
+/// Filter for adjusting brightness, contrast, saturation, hue and gamma of
+/// [ARGBUNorm][ColorFormat::ARGB8UNorm] or [ABGRUNorm][ColorFormat::ABGR8UNorm] textures.
+/// filter also scales automatically based on input and output textures.
+///
+/// Parameters live in a constant buffer uploaded on every [apply_filter][DxFilter::apply_filter],
+/// so [set_params][ColorAdjustARGB::set_params] can be called every frame (e.g. while a slider
+/// is dragged) without re-creating any shaders.
+pub struct ColorAdjustARGB {
+    device: ID3D11Device4,
+    vs: VertexShader,
+    ps: PixelShader,
+
+    params: ColorAdjustParams,
+    cb: ConstantBuffer<ColorAdjustConstants>,
+
+    _in_tex: Texture,
+    _out_tex: Texture,
+
+    srv: ID3D11ShaderResourceView,
+    rtv: ID3D11RenderTargetView,
+    sampler: ID3D11SamplerState,
+}
+
+impl ColorAdjustARGB {
+    /// create new instance of ColorAdjustARGB filter with neutral parameters. After creation,
+    /// filter takes ARGB or ABGR input from `input_tex` and writes to ARGB or ABGR `out_tex`.
+    pub fn new(input_tex: &Texture, out_tex: &Texture, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        Self::validate_output(out_tex)?;
+
+        let ps = color_adjust_ps(device.clone())?;
+        let vs = simple_vs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &ColorAdjustConstants::default())?;
+
+        let srv = create_srv(device, input_tex, input_tex.desc().format.into())?;
+        let sampler = create_tex_sampler(device)?;
+        let rtv = create_rtv(device, out_tex, rgba_view_format(out_tex.desc().format))?;
+
+        return Ok(Self {
+            device: device.clone(),
+            vs,
+            ps,
+            params: ColorAdjustParams::default(),
+            cb,
+            _in_tex: input_tex.clone(),
+            _out_tex: out_tex.clone(),
+            srv,
+            rtv,
+            sampler,
+        });
+    }
+
+    /// current picture controls.
+    pub fn params(&self) -> ColorAdjustParams {
+        return self.params;
+    }
+
+    /// change picture controls. takes effect on the next [apply_filter][DxFilter::apply_filter].
+    pub fn set_params(&mut self, params: ColorAdjustParams) -> Result<()> {
+        params.validate()?;
+        self.params = params;
+        return Ok(());
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format)))
+            }
+        }
+    }
+    fn validate_output(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format)))
+            }
+        }
+    }
+}
+
+impl DxFilter for ColorAdjustARGB {
+    fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        let out_desc = self._out_tex.desc();
+        let vp = D3D11_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: out_desc.width as _,
+            Height: out_desc.height as _,
+            MinDepth: 0.0,
+            MaxDepth: 0.0,
+        };
+        self.cb.update(ctx, &ColorAdjustConstants::from(&self.params))?;
+        unsafe {
+            ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
+            ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
+            ctx.PSSetShader(self.ps.as_raw_ref(), Some(&[]));
+            self.cb.bind(ctx, ShaderStage::Pixel, 0);
+            ctx.PSSetSamplers(0, Some(&[Some(self.sampler.clone())]));
+            ctx.PSSetShaderResources(0, Some(&[Some(self.srv.clone())]));
+            ctx.RSSetViewports(Some(&[vp]));
+            ctx.OMSetRenderTargets(Some(&[Some(self.rtv.clone())]), None);
+            ctx.Draw(4, 0);
+        }
+        return Ok(());
+    }
+
+    fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        ColorAdjustARGB::validate_input(tex)?;
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, tex.desc().format.into())?;
+        return Ok(());
+    }
+
+    fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
+        ColorAdjustARGB::validate_output(tex)?;
+        self._out_tex = tex.clone();
+        self.rtv = create_rtv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+}
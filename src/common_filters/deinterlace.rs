@@ -0,0 +1,298 @@
+use std::cell::Cell;
+
+use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
+use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP;
+use windows::Win32::Graphics::Direct3D11::{D3D11_VIEWPORT, ID3D11Device4, ID3D11DeviceContext4, ID3D11RenderTargetView, ID3D11ShaderResourceView};
+
+use crate::{DxFilter, Result};
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{PixelShader, ShaderStage, VertexShader};
+
+use super::{create_history, create_rtv, create_srv, rgba_view_format, same_desc, simple_vs};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::tex_reader::TextureReader;
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{DeinterlaceARGB, DeinterlaceMode, FieldOrder};
+    use crate::DxFilter;
+    use crate::utils::{create_input_texture, create_output_texture};
+
+    #[test]
+    fn test_deinterlace() {
+        let (device, ctx) = build_device_and_ctx();
+
+        // even lines 200, odd lines 100: fully combed.
+        let width = 4u32;
+        let height = 4u32;
+        let mut src = Vec::new();
+        for y in 0..height {
+            let v = if y % 2 == 0 { 200u8 } else { 100u8 };
+            src.extend_from_slice(&[v, v, v, 255].repeat(width as usize));
+        }
+        let desc = TextureDesc { width, height, format: ColorFormat::ARGB8UNorm };
+        let input_tex = create_input_texture(&device, desc, Some(src)).unwrap();
+        let output_tex = create_output_texture(&device, desc, None).unwrap();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+        let row = (width * 4) as usize;
+        let mut out = Vec::new();
+
+        // bob keeps the top field and interpolates the bottom one from it.
+        let mut filter = DeinterlaceARGB::new(&input_tex, &output_tex, DeinterlaceMode::Bob, FieldOrder::TopFieldFirst, &device).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert_eq!(out[0], 200);
+        assert_eq!(out[row], 200);
+
+        // double rate outputs the bottom field on the second call.
+        filter.set_double_rate(true);
+        filter.apply_filter(&ctx).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert_eq!(out[0], 100);
+        assert_eq!(out[row], 100);
+
+        // a static image is woven back together by the motion adaptive mode once history exists.
+        filter.set_double_rate(false);
+        filter.set_mode(DeinterlaceMode::MotionAdaptive);
+        filter.apply_filter(&ctx).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert_eq!(out[0], 200);
+        assert_eq!(out[row], 100);
+    }
+}
+
+generate_shader!(deinterlace_ps ps {
+    src_file: "src/common_filters/shaders/deinterlace_ps.hlsl",
+    entry_point: "main",
+    target: "ps_5_0"
+});
+
+/// how [DeinterlaceARGB] rebuilds the lines of the missing field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeinterlaceMode {
+    /// interpolate missing lines from the lines above and below (field doubling). no combing but
+    /// half the vertical resolution. Use with [set_double_rate][DeinterlaceARGB::set_double_rate]
+    /// to output every field.
+    Bob,
+    /// blend every line with its neighbours. cheap and stable, slightly soft.
+    LinearBlend,
+    /// weave static areas from both fields and bob areas that moved since the previous frame.
+    /// keeps the previous input frame internally.
+    MotionAdaptive,
+}
+
+/// temporal order of the two fields in an interlaced frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FieldOrder {
+    /// top field (even lines, counting from 0) is displayed first. common for 1080i.
+    TopFieldFirst,
+    /// bottom field (odd lines) is displayed first. common for NTSC DV.
+    BottomFieldFirst,
+}
+
+/// layout of `DeinterlaceConstants` cbuffer in deinterlace_ps.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct DeinterlaceConstants {
+    mode: u32,
+    keep_odd_lines: u32,
+    has_history: u32,
+    motion_threshold: f32,
+}
+
+/// default difference (in normalized units) above which [DeinterlaceMode::MotionAdaptive] treats a pixel as moving.
+pub const DEFAULT_MOTION_THRESHOLD: f32 = 12.0 / 255.0;
+
+/// Filter for deinterlacing [ARGBUNorm][ColorFormat::ARGB8UNorm], [ABGRUNorm][ColorFormat::ABGR8UNorm] or
+/// [AYUV][ColorFormat::AYUV] frames, e.g. 1080i capture card input, before colour conversion.
+/// Output texture must have the same format and size as the input.
+///
+/// By default each [apply_filter][DxFilter::apply_filter] call outputs the first field (as given by
+/// [FieldOrder]) of the current input frame. With [set_double_rate][DeinterlaceARGB::set_double_rate]
+/// calls alternate between the first and the second field, so call it twice per input frame to get
+/// the full field rate.
+pub struct DeinterlaceARGB {
+    device: ID3D11Device4,
+    vs: VertexShader,
+    ps: PixelShader,
+
+    mode: DeinterlaceMode,
+    field_order: FieldOrder,
+    motion_threshold: f32,
+    double_rate: bool,
+    cb: ConstantBuffer<DeinterlaceConstants>,
+
+    /// true when the next call outputs the second field in double rate mode.
+    second_field: Cell<bool>,
+    /// true once `prev_tex` holds a frame.
+    has_history: Cell<bool>,
+    prev_tex: Texture,
+    prev_srv: ID3D11ShaderResourceView,
+
+    _in_tex: Texture,
+    _out_tex: Texture,
+
+    srv: ID3D11ShaderResourceView,
+    rtv: ID3D11RenderTargetView,
+}
+
+impl DeinterlaceARGB {
+    /// create new instance of DeinterlaceARGB filter. After creation, filter takes interlaced input
+    /// from `input_tex` and writes progressive frames to `out_tex`.
+    pub fn new(input_tex: &Texture, out_tex: &Texture, mode: DeinterlaceMode, field_order: FieldOrder, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        Self::validate_output(input_tex.desc(), out_tex)?;
+
+        let ps = deinterlace_ps(device.clone())?;
+        let vs = simple_vs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &DeinterlaceConstants::default())?;
+
+        let (prev_tex, prev_srv) = create_history(device, input_tex.desc())?;
+        let srv = create_srv(device, input_tex, rgba_view_format(input_tex.desc().format))?;
+        let rtv = create_rtv(device, out_tex, rgba_view_format(out_tex.desc().format))?;
+
+        return Ok(Self {
+            device: device.clone(),
+            vs,
+            ps,
+            mode,
+            field_order,
+            motion_threshold: DEFAULT_MOTION_THRESHOLD,
+            double_rate: false,
+            cb,
+            second_field: Cell::new(false),
+            has_history: Cell::new(false),
+            prev_tex,
+            prev_srv,
+            _in_tex: input_tex.clone(),
+            _out_tex: out_tex.clone(),
+            srv,
+            rtv,
+        });
+    }
+
+    /// change deinterlacing mode.
+    pub fn set_mode(&mut self, mode: DeinterlaceMode) {
+        self.mode = mode;
+    }
+
+    /// change which field comes first.
+    pub fn set_field_order(&mut self, field_order: FieldOrder) {
+        self.field_order = field_order;
+        self.second_field.set(false);
+    }
+
+    /// alternate between first and second field on every call instead of always outputting the first.
+    pub fn set_double_rate(&mut self, double_rate: bool) {
+        self.double_rate = double_rate;
+        self.second_field.set(false);
+    }
+
+    /// difference in normalized units above which [DeinterlaceMode::MotionAdaptive] starts treating a
+    /// pixel as moving. defaults to [DEFAULT_MOTION_THRESHOLD].
+    pub fn set_motion_threshold(&mut self, threshold: f32) -> Result<()> {
+        if !(threshold.is_finite() && threshold > 0.0) {
+            return Err(DxFilterErr::BadParam(format!("motion threshold must be positive, found {}", threshold)));
+        }
+        self.motion_threshold = threshold;
+        return Ok(());
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::AYUV => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR or AYUV format found {:?}", desc.format)))
+            }
+        }
+    }
+    fn validate_output(input: TextureDesc, tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        if !same_desc(&input, &desc) {
+            return Err(DxFilterErr::BadParam(format!("expected output of same format and size as input ({:?} {}x{}), found {:?} {}x{}",
+                                                     input.format, input.width, input.height, desc.format, desc.width, desc.height)));
+        }
+        Ok(())
+    }
+}
+
+impl DxFilter for DeinterlaceARGB {
+    fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        let out_desc = self._out_tex.desc();
+        let vp = D3D11_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: out_desc.width as _,
+            Height: out_desc.height as _,
+            MinDepth: 0.0,
+            MaxDepth: 0.0,
+        };
+
+        let second_field = self.double_rate && self.second_field.get();
+        let top_field = (self.field_order == FieldOrder::TopFieldFirst) != second_field;
+        let constants = DeinterlaceConstants {
+            mode: match self.mode {
+                DeinterlaceMode::Bob => 0,
+                DeinterlaceMode::LinearBlend => 1,
+                DeinterlaceMode::MotionAdaptive => 2,
+            },
+            keep_odd_lines: if top_field { 0 } else { 1 },
+            has_history: self.has_history.get() as u32,
+            motion_threshold: self.motion_threshold,
+        };
+        self.cb.update(ctx, &constants)?;
+
+        unsafe {
+            ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
+            ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
+            ctx.PSSetShader(self.ps.as_raw_ref(), Some(&[]));
+            self.cb.bind(ctx, ShaderStage::Pixel, 0);
+            ctx.PSSetShaderResources(0, Some(&[Some(self.srv.clone()), Some(self.prev_srv.clone())]));
+            ctx.RSSetViewports(Some(&[vp]));
+            ctx.OMSetRenderTargets(Some(&[Some(self.rtv.clone())]), None);
+            ctx.Draw(4, 0);
+        }
+
+        // the current frame becomes history once all of its fields were output
+        let frame_done = !self.double_rate || second_field;
+        if self.double_rate {
+            self.second_field.set(!second_field);
+        }
+        if frame_done && self.mode == DeinterlaceMode::MotionAdaptive {
+            unsafe {
+                // unbind history before writing to it
+                ctx.PSSetShaderResources(1, Some(&[None]));
+                ctx.CopyResource(self.prev_tex.as_raw_ref(), self._in_tex.as_raw_ref());
+            }
+            self.has_history.set(true);
+        }
+        return Ok(());
+    }
+
+    fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        DeinterlaceARGB::validate_input(tex)?;
+        DeinterlaceARGB::validate_output(tex.desc(), &self._out_tex)?;
+        if !same_desc(&tex.desc(), &self._in_tex.desc()) {
+            (self.prev_tex, self.prev_srv) = create_history(&self.device, tex.desc())?;
+            self.has_history.set(false);
+        }
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+
+    fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
+        DeinterlaceARGB::validate_output(self._in_tex.desc(), tex)?;
+        self._out_tex = tex.clone();
+        self.rtv = create_rtv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,355 @@
+use std::cell::{Cell, RefCell};
+
+use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Buffer, ID3D11Device4, ID3D11DeviceContext4, ID3D11ShaderResourceView, ID3D11UnorderedAccessView};
+
+use crate::Result;
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{ComputeShader, ShaderStage};
+
+use super::{create_history, create_srv, create_staging_buffer, create_uav_buffer, read_staging_buffer, rgba_view_format, same_desc};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{DirtyRegionDetector, DirtyTiles};
+    use crate::utils::create_input_texture;
+
+    #[test]
+    fn test_dirty_tiles_bitmap() {
+        // 40x20 frame in 16x16 tiles gives 3x2 tiles, the last column and row are partial.
+        let tiles = DirtyTiles::new(40, 20, 16, vec![0b100010]);
+        assert_eq!((tiles.tiles_x(), tiles.tiles_y()), (3, 2));
+        assert!(tiles.any());
+        assert_eq!(tiles.count(), 2);
+        assert!(tiles.is_dirty(1, 0));
+        assert!(tiles.is_dirty(2, 1));
+        assert!(!tiles.is_dirty(0, 0));
+        assert_eq!(tiles.iter().collect::<Vec<_>>(), vec![(1, 0), (2, 1)]);
+        assert_eq!(tiles.tile_rect(2, 1), (32, 16, 40, 20));
+
+        let clean = DirtyTiles::new(40, 20, 16, vec![0]);
+        assert!(!clean.any());
+        assert_eq!(clean.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_dirty_region_detector() {
+        let (device, ctx) = build_device_and_ctx();
+
+        let desc = TextureDesc { width: 64, height: 32, format: ColorFormat::ARGB8UNorm };
+        let frame_a = create_input_texture(&device, desc, Some(vec![32; 64 * 32 * 4])).unwrap();
+        // same as frame_a except for one pixel in the second tile
+        let mut data = vec![32; 64 * 32 * 4];
+        data[(20 * 64 + 40) * 4] = 200;
+        let frame_b = create_input_texture(&device, desc, Some(data)).unwrap();
+
+        let mut detector = DirtyRegionDetector::new(&frame_a, 32, &device).unwrap();
+        assert_eq!(detector.tiles(), (2, 1));
+
+        // without history everything is dirty
+        assert!(detector.detect(&ctx).unwrap());
+        assert_eq!(detector.dirty_tiles(&ctx).unwrap().count(), 2);
+
+        // same frame again
+        assert!(!detector.detect(&ctx).unwrap());
+        assert!(!detector.dirty_tiles(&ctx).unwrap().any());
+
+        detector.set_input_tex(&frame_b).unwrap();
+        assert!(detector.detect(&ctx).unwrap());
+        let tiles = detector.dirty_tiles(&ctx).unwrap();
+        assert_eq!(tiles.iter().collect::<Vec<_>>(), vec![(1, 0)]);
+
+        assert!(DirtyRegionDetector::new(&frame_a, 12, &device).is_err());
+    }
+}
+
+/// Tile changes found by [DirtyRegionDetector]. Tiles are numbered from the top left corner, the
+/// last column and row may be cut off by the frame edge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirtyTiles {
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    tiles_x: u32,
+    tiles_y: u32,
+    /// one bit per tile in row-major order, 32 tiles per word starting at the lowest bit.
+    bits: Vec<u32>,
+}
+
+impl DirtyTiles {
+    fn new(width: u32, height: u32, tile_size: u32, bits: Vec<u32>) -> Self {
+        Self {
+            width,
+            height,
+            tile_size,
+            tiles_x: (width + tile_size - 1) / tile_size,
+            tiles_y: (height + tile_size - 1) / tile_size,
+            bits,
+        }
+    }
+
+    /// number of tile columns.
+    pub fn tiles_x(&self) -> u32 {
+        self.tiles_x
+    }
+
+    /// number of tile rows.
+    pub fn tiles_y(&self) -> u32 {
+        self.tiles_y
+    }
+
+    /// edge length of a tile in pixels.
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// true when the tile in column `x` and row `y` changed.
+    pub fn is_dirty(&self, x: u32, y: u32) -> bool {
+        let idx = y * self.tiles_x + x;
+        self.bits[(idx / 32) as usize] & (1 << (idx % 32)) != 0
+    }
+
+    /// true when any tile changed.
+    pub fn any(&self) -> bool {
+        self.bits.iter().any(|&w| w != 0)
+    }
+
+    /// number of changed tiles.
+    pub fn count(&self) -> u32 {
+        self.bits.iter().map(|w| w.count_ones()).sum()
+    }
+
+    /// `(column, row)` of every changed tile in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item=(u32, u32)> + '_ {
+        let tiles_x = self.tiles_x;
+        (0..tiles_x * self.tiles_y)
+            .filter(move |&idx| self.bits[(idx / 32) as usize] & (1 << (idx % 32)) != 0)
+            .map(move |idx| (idx % tiles_x, idx / tiles_x))
+    }
+
+    /// pixel rectangle `(left, top, right, bottom)` covered by a tile, clipped to the frame.
+    pub fn tile_rect(&self, x: u32, y: u32) -> (u32, u32, u32, u32) {
+        let left = x * self.tile_size;
+        let top = y * self.tile_size;
+        (left, top, (left + self.tile_size).min(self.width), (top + self.tile_size).min(self.height))
+    }
+}
+
+generate_shader!(dirty_tiles_cs cs {
+    src_file: "src/common_filters/shaders/dirty_tiles_cs.hlsl",
+    entry_point: "main",
+    target: "cs_5_0"
+});
+
+/// layout of `DirtyTileConstants` cbuffer in dirty_tiles_cs.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct DirtyTileConstants {
+    size: [u32; 2],
+    tile_size: u32,
+    tiles_x: u32,
+    threshold: f32,
+    has_history: u32,
+    _padding: [u32; 2],
+}
+
+/// Detects which parts of a frame changed since the previous call, e.g. to skip encoding static
+/// frames in remote desktop or to only send changed tiles. Works on
+/// [ARGBUNorm][ColorFormat::ARGB8UNorm], [ABGRUNorm][ColorFormat::ABGR8UNorm], [AYUV][ColorFormat::AYUV],
+/// [ARGB10UNorm][ColorFormat::ARGB10UNorm] and [ARGB16Float][ColorFormat::ARGB16Float] textures.
+///
+/// Comparison runs in a compute shader against a copy of the previous input kept by the detector.
+/// [detect][DirtyRegionDetector::detect] reads back only a single changed flag through a 4 byte
+/// staging buffer; the per tile bitmap is transferred only when
+/// [dirty_tiles][DirtyRegionDetector::dirty_tiles] is called. Both calls wait for the gpu to finish
+/// the comparison.
+///
+/// This is not a [DxFilter][crate::DxFilter] as it produces no output texture.
+pub struct DirtyRegionDetector {
+    device: ID3D11Device4,
+    cs: ComputeShader,
+    cb: ConstantBuffer<DirtyTileConstants>,
+
+    tile_size: u32,
+    threshold: f32,
+
+    /// true once `prev_tex` holds a frame.
+    has_history: Cell<bool>,
+    prev_tex: Texture,
+    prev_srv: ID3D11ShaderResourceView,
+
+    _in_tex: Texture,
+    srv: ID3D11ShaderResourceView,
+
+    buffers: TileBuffers,
+    /// scratch space for reading back the bitmap.
+    tile_words: RefCell<Vec<u32>>,
+}
+
+/// gpu side tile bitmap and changed flag, with staging copies for readback.
+struct TileBuffers {
+    words: u32,
+    tile_buf: ID3D11Buffer,
+    tile_uav: ID3D11UnorderedAccessView,
+    tile_staging: ID3D11Buffer,
+    flag_buf: ID3D11Buffer,
+    flag_uav: ID3D11UnorderedAccessView,
+    flag_staging: ID3D11Buffer,
+}
+
+impl TileBuffers {
+    fn new(device: &ID3D11Device4, desc: TextureDesc, tile_size: u32) -> Result<Self> {
+        let tiles = DirtyTiles::new(desc.width, desc.height, tile_size, Vec::new());
+        let words = (tiles.tiles_x() * tiles.tiles_y() + 31) / 32;
+        let (tile_buf, tile_uav) = create_uav_buffer(device, words)?;
+        let (flag_buf, flag_uav) = create_uav_buffer(device, 1)?;
+        Ok(Self {
+            words,
+            tile_buf,
+            tile_uav,
+            tile_staging: create_staging_buffer(device, words)?,
+            flag_buf,
+            flag_uav,
+            flag_staging: create_staging_buffer(device, 1)?,
+        })
+    }
+}
+
+impl DirtyRegionDetector {
+    /// create new instance of DirtyRegionDetector for frames like `input_tex`. `tile_size` is the
+    /// edge length of a tile in pixels and must be a non-zero multiple of 8.
+    pub fn new(input_tex: &Texture, tile_size: u32, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        if tile_size == 0 || tile_size % 8 != 0 {
+            return Err(DxFilterErr::BadParam(format!("tile size must be a non-zero multiple of 8, found {}", tile_size)));
+        }
+
+        let cs = dirty_tiles_cs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &DirtyTileConstants::default())?;
+        let (prev_tex, prev_srv) = create_history(device, input_tex.desc())?;
+        let srv = create_srv(device, input_tex, rgba_view_format(input_tex.desc().format))?;
+        let buffers = TileBuffers::new(device, input_tex.desc(), tile_size)?;
+
+        return Ok(Self {
+            device: device.clone(),
+            cs,
+            cb,
+            tile_size,
+            threshold: 0.0,
+            has_history: Cell::new(false),
+            prev_tex,
+            prev_srv,
+            _in_tex: input_tex.clone(),
+            srv,
+            tile_words: RefCell::new(vec![0; buffers.words as usize]),
+            buffers,
+        });
+    }
+
+    /// number of tile columns and rows.
+    pub fn tiles(&self) -> (u32, u32) {
+        let desc = self._in_tex.desc();
+        let tiles = DirtyTiles::new(desc.width, desc.height, self.tile_size, Vec::new());
+        return (tiles.tiles_x(), tiles.tiles_y());
+    }
+
+    /// largest per channel difference in normalized units that is still treated as unchanged.
+    /// defaults to `0.0`, i.e. any change marks the tile dirty.
+    pub fn set_threshold(&mut self, threshold: f32) -> Result<()> {
+        if !(threshold.is_finite() && threshold >= 0.0) {
+            return Err(DxFilterErr::BadParam(format!("threshold must be a non-negative number, found {}", threshold)));
+        }
+        self.threshold = threshold;
+        return Ok(());
+    }
+
+    /// forget the previous frame so that the next [detect][DirtyRegionDetector::detect] reports
+    /// every tile as dirty, e.g. after the encoder requested a key frame.
+    pub fn reset(&self) {
+        self.has_history.set(false);
+    }
+
+    /// compare current content of the input texture with the frame seen by the previous call and
+    /// return true if anything changed. the first call after creation or [reset][DirtyRegionDetector::reset]
+    /// always returns true.
+    pub fn detect(&self, ctx: &ID3D11DeviceContext4) -> Result<bool> {
+        let desc = self._in_tex.desc();
+        let (tiles_x, _) = self.tiles();
+        let constants = DirtyTileConstants {
+            size: [desc.width, desc.height],
+            tile_size: self.tile_size,
+            tiles_x,
+            threshold: self.threshold,
+            has_history: self.has_history.get() as u32,
+            _padding: [0; 2],
+        };
+        self.cb.update(ctx, &constants)?;
+
+        let buffers = &self.buffers;
+        unsafe {
+            ctx.ClearUnorderedAccessViewUint(&buffers.tile_uav, &[0; 4]);
+            ctx.ClearUnorderedAccessViewUint(&buffers.flag_uav, &[0; 4]);
+
+            ctx.CSSetShader(self.cs.as_raw_ref(), None);
+            self.cb.bind(ctx, ShaderStage::Compute, 0);
+            ctx.CSSetShaderResources(0, Some(&[Some(self.srv.clone()), Some(self.prev_srv.clone())]));
+            let uavs = [Some(buffers.tile_uav.clone()), Some(buffers.flag_uav.clone())];
+            ctx.CSSetUnorderedAccessViews(0, uavs.len() as _, Some(uavs.as_ptr()), None);
+            ctx.Dispatch((desc.width + 7) / 8, (desc.height + 7) / 8, 1);
+
+            // unbind so that the buffers can be copied and history can be written
+            ctx.CSSetShaderResources(0, Some(&[None, None]));
+            let no_uavs: [Option<ID3D11UnorderedAccessView>; 2] = [None, None];
+            ctx.CSSetUnorderedAccessViews(0, no_uavs.len() as _, Some(no_uavs.as_ptr()), None);
+
+            ctx.CopyResource(&buffers.tile_staging, &buffers.tile_buf);
+            ctx.CopyResource(&buffers.flag_staging, &buffers.flag_buf);
+            ctx.CopyResource(self.prev_tex.as_raw_ref(), self._in_tex.as_raw_ref());
+        }
+        self.has_history.set(true);
+
+        let mut flag = [0u32];
+        read_staging_buffer(ctx, &buffers.flag_staging, &mut flag)?;
+        return Ok(flag[0] != 0);
+    }
+
+    /// per tile changes found by the last [detect][DirtyRegionDetector::detect] call.
+    pub fn dirty_tiles(&self, ctx: &ID3D11DeviceContext4) -> Result<DirtyTiles> {
+        let mut words = self.tile_words.borrow_mut();
+        read_staging_buffer(ctx, &self.buffers.tile_staging, &mut words)?;
+        let desc = self._in_tex.desc();
+        return Ok(DirtyTiles::new(desc.width, desc.height, self.tile_size, words.clone()));
+    }
+
+    /// configure the detector to use different input texture. history is kept if the new texture
+    /// has the same size and format, so a ring of capture textures can be compared frame to frame.
+    pub fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        Self::validate_input(tex)?;
+        if !same_desc(&tex.desc(), &self._in_tex.desc()) {
+            (self.prev_tex, self.prev_srv) = create_history(&self.device, tex.desc())?;
+            self.buffers = TileBuffers::new(&self.device, tex.desc(), self.tile_size)?;
+            self.tile_words = RefCell::new(vec![0; self.buffers.words as usize]);
+            self.has_history.set(false);
+        }
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::AYUV |
+            ColorFormat::ARGB10UNorm | ColorFormat::ARGB16Float => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR, AYUV, ARGB10 or ARGB16F format found {:?}", desc.format)))
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::cell::Cell;
+
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device4, ID3D11DeviceContext4};
+
+use crate::Result;
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::shader::ShaderStage;
+
+/// Dithering applied when a filter writes fewer bits per channel than its input carries, e.g.
+/// 10 or 16 bit captures converted to 8 bit ARGB or NV12. Without dithering smooth gradients
+/// band after quantization.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DitherMode {
+    /// quantize without dithering.
+    #[default]
+    None,
+    /// 8x8 Bayer ordered dither.
+    Ordered,
+    /// interleaved gradient noise. blue-noise-like, less structured than [DitherMode::Ordered].
+    BlueNoise,
+}
+
+/// layout of `DitherConstants` cbuffer in dither.hlsli
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct DitherConstants {
+    mode: u32,
+    frame_index: u32,
+    levels: f32,
+    _padding: f32,
+}
+
+/// dither settings and constant buffer for shaders including dither.hlsli. the pattern moves
+/// every frame so the noise averages out over time.
+pub(super) struct DitherPass {
+    mode: DitherMode,
+    cb: ConstantBuffer<DitherConstants>,
+    frame_index: Cell<u32>,
+}
+
+impl DitherPass {
+    pub(super) fn new(device: &ID3D11Device4) -> Result<Self> {
+        return Ok(Self {
+            mode: DitherMode::None,
+            cb: ConstantBuffer::new(device, &DitherConstants::default())?,
+            frame_index: Cell::new(0),
+        });
+    }
+
+    pub(super) fn mode(&self) -> DitherMode {
+        return self.mode;
+    }
+
+    pub(super) fn set_mode(&mut self, mode: DitherMode) {
+        self.mode = mode;
+    }
+
+    /// uploads constants for an 8 bit render target and binds them to pixel shader slot `b0`.
+    /// advances the frame index used to vary the pattern.
+    pub(super) fn bind(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        self.upload(ctx)?;
+        self.cb.bind(ctx, ShaderStage::Pixel, 0);
+        return Ok(());
+    }
+
+    /// same as [bind][DitherPass::bind] for compute shaders writing 8 bit UAVs.
+    pub(super) fn bind_cs(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        self.upload(ctx)?;
+        self.cb.bind(ctx, ShaderStage::Compute, 0);
+        return Ok(());
+    }
+
+    fn upload(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        let frame_index = self.frame_index.get();
+        self.frame_index.set(frame_index.wrapping_add(1));
+        let constants = DitherConstants {
+            mode: match self.mode {
+                DitherMode::None => 0,
+                DitherMode::Ordered => 1,
+                DitherMode::BlueNoise => 2,
+            },
+            frame_index,
+            levels: 255.0,
+            _padding: 0.0,
+        };
+        return self.cb.update(ctx, &constants);
+    }
+}
@@ -0,0 +1,237 @@
+use std::cell::{Cell, RefCell};
+
+use win_desktop_duplication::texture::{ColorFormat, Texture};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Buffer, ID3D11Device4, ID3D11DeviceContext4, ID3D11ShaderResourceView, ID3D11UnorderedAccessView};
+
+use crate::Result;
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{ComputeShader, ShaderStage};
+
+use super::{create_history, create_srv, create_staging_buffer, create_uav_buffer, read_staging_buffer, rgba_view_format, same_desc};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{FrameAnalyzer, FrameStatistics};
+    use crate::utils::create_input_texture;
+
+    #[test]
+    fn test_statistics_from_histograms() {
+        let mut luma = [0u32; 256];
+        luma[16] = 3;
+        luma[235] = 1;
+        let mut diff = [0u32; 256];
+        diff[0] = 2;
+        diff[10] = 2;
+
+        let stats = FrameStatistics::from_histograms(4, luma, Some(&diff));
+        assert_eq!(stats.pixel_count, 4);
+        assert!((stats.mean_luma - (16.0 * 3.0 + 235.0) / 4.0).abs() < 1e-4);
+        assert_eq!(stats.mean_abs_diff, Some(5.0));
+
+        let first = FrameStatistics::from_histograms(4, luma, None);
+        assert_eq!(first.mean_abs_diff, None);
+    }
+
+    #[test]
+    fn test_frame_analyzer() {
+        let (device, ctx) = build_device_and_ctx();
+
+        let desc = TextureDesc { width: 32, height: 32, format: ColorFormat::ARGB8UNorm };
+        let black = create_input_texture(&device, desc, Some(vec![0; 32 * 32 * 4])).unwrap();
+        let white = create_input_texture(&device, desc, Some(vec![255; 32 * 32 * 4])).unwrap();
+
+        let mut analyzer = FrameAnalyzer::new(&black, &device).unwrap();
+        let stats = analyzer.analyze(&ctx).unwrap();
+        assert_eq!(stats.pixel_count, 32 * 32);
+        assert_eq!(stats.luma_histogram[16], 32 * 32);
+        assert!((stats.mean_luma - 16.0).abs() < 1e-4);
+        assert_eq!(stats.mean_abs_diff, None);
+
+        analyzer.set_input_tex(&white).unwrap();
+        let stats = analyzer.analyze(&ctx).unwrap();
+        assert_eq!(stats.luma_histogram[235], 32 * 32);
+        assert!((stats.mean_abs_diff.unwrap() - 219.0).abs() < 1e-4);
+    }
+}
+
+/// Per frame statistics produced by [FrameAnalyzer]. Luma uses the same BT.601 limited range
+/// coefficients as the NV12 / AYUV conversion filters and is given in 8 bit code values, so black
+/// is `16` and white is `235`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameStatistics {
+    /// number of pixels in the frame.
+    pub pixel_count: u32,
+    /// average luma.
+    pub mean_luma: f32,
+    /// number of pixels for every luma value.
+    pub luma_histogram: [u32; 256],
+    /// average absolute luma difference to the previous frame. `None` when there is no previous
+    /// frame. large jumps usually indicate a scene change.
+    pub mean_abs_diff: Option<f32>,
+}
+
+impl FrameStatistics {
+    fn from_histograms(pixel_count: u32, luma_histogram: [u32; 256], diff_histogram: Option<&[u32]>) -> Self {
+        let weighted_mean = |hist: &[u32]| {
+            let sum: u64 = hist.iter().enumerate().map(|(v, &n)| v as u64 * n as u64).sum();
+            (sum as f64 / pixel_count.max(1) as f64) as f32
+        };
+        Self {
+            pixel_count,
+            mean_luma: weighted_mean(&luma_histogram),
+            luma_histogram,
+            mean_abs_diff: diff_histogram.map(weighted_mean),
+        }
+    }
+}
+
+generate_shader!(frame_stats_cs cs {
+    src_file: "src/common_filters/shaders/frame_stats_cs.hlsl",
+    entry_point: "main",
+    target: "cs_5_0"
+});
+
+/// layout of `FrameStatsConstants` cbuffer in frame_stats_cs.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct FrameStatsConstants {
+    size: [u32; 2],
+    has_history: u32,
+    _padding: u32,
+}
+
+/// luma histogram followed by the difference histogram.
+const HISTOGRAM_WORDS: u32 = 512;
+
+/// Computes [FrameStatistics] for rate control from the same [ARGBUNorm][ColorFormat::ARGB8UNorm],
+/// [ABGRUNorm][ColorFormat::ABGR8UNorm] or [ARGB10UNorm][ColorFormat::ARGB10UNorm] textures the
+/// conversion filters take as input.
+///
+/// Histograms are built in a single compute pass and read back through a 2 KiB staging buffer.
+/// The previous frame is copied inside the analyzer for the difference statistics.
+///
+/// This is not a [DxFilter][crate::DxFilter] as it produces no output texture.
+pub struct FrameAnalyzer {
+    device: ID3D11Device4,
+    cs: ComputeShader,
+    cb: ConstantBuffer<FrameStatsConstants>,
+
+    /// true once `prev_tex` holds a frame.
+    has_history: Cell<bool>,
+    prev_tex: Texture,
+    prev_srv: ID3D11ShaderResourceView,
+
+    _in_tex: Texture,
+    srv: ID3D11ShaderResourceView,
+
+    hist_buf: ID3D11Buffer,
+    hist_uav: ID3D11UnorderedAccessView,
+    hist_staging: ID3D11Buffer,
+    /// scratch space for reading back the histograms.
+    hist_words: RefCell<Vec<u32>>,
+}
+
+impl FrameAnalyzer {
+    /// create new instance of FrameAnalyzer for frames in `input_tex`.
+    pub fn new(input_tex: &Texture, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+
+        let cs = frame_stats_cs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &FrameStatsConstants::default())?;
+        let (prev_tex, prev_srv) = create_history(device, input_tex.desc())?;
+        let srv = create_srv(device, input_tex, rgba_view_format(input_tex.desc().format))?;
+        let (hist_buf, hist_uav) = create_uav_buffer(device, HISTOGRAM_WORDS)?;
+        let hist_staging = create_staging_buffer(device, HISTOGRAM_WORDS)?;
+
+        return Ok(Self {
+            device: device.clone(),
+            cs,
+            cb,
+            has_history: Cell::new(false),
+            prev_tex,
+            prev_srv,
+            _in_tex: input_tex.clone(),
+            srv,
+            hist_buf,
+            hist_uav,
+            hist_staging,
+            hist_words: RefCell::new(vec![0; HISTOGRAM_WORDS as usize]),
+        });
+    }
+
+    /// forget the previous frame, e.g. after seeking. the next result has no
+    /// [mean_abs_diff][FrameStatistics::mean_abs_diff].
+    pub fn reset(&self) {
+        self.has_history.set(false);
+    }
+
+    /// compute statistics of the current content of the input texture and remember it as the
+    /// previous frame for the next call. waits for the gpu to finish.
+    pub fn analyze(&self, ctx: &ID3D11DeviceContext4) -> Result<FrameStatistics> {
+        let desc = self._in_tex.desc();
+        let has_history = self.has_history.get();
+        let constants = FrameStatsConstants {
+            size: [desc.width, desc.height],
+            has_history: has_history as u32,
+            _padding: 0,
+        };
+        self.cb.update(ctx, &constants)?;
+
+        unsafe {
+            ctx.ClearUnorderedAccessViewUint(&self.hist_uav, &[0; 4]);
+
+            ctx.CSSetShader(self.cs.as_raw_ref(), None);
+            self.cb.bind(ctx, ShaderStage::Compute, 0);
+            ctx.CSSetShaderResources(0, Some(&[Some(self.srv.clone()), Some(self.prev_srv.clone())]));
+            let uavs = [Some(self.hist_uav.clone())];
+            ctx.CSSetUnorderedAccessViews(0, uavs.len() as _, Some(uavs.as_ptr()), None);
+            ctx.Dispatch((desc.width + 15) / 16, (desc.height + 15) / 16, 1);
+
+            // unbind so that the buffer can be copied and history can be written
+            ctx.CSSetShaderResources(0, Some(&[None, None]));
+            let no_uavs: [Option<ID3D11UnorderedAccessView>; 1] = [None];
+            ctx.CSSetUnorderedAccessViews(0, no_uavs.len() as _, Some(no_uavs.as_ptr()), None);
+
+            ctx.CopyResource(&self.hist_staging, &self.hist_buf);
+            ctx.CopyResource(self.prev_tex.as_raw_ref(), self._in_tex.as_raw_ref());
+        }
+        self.has_history.set(true);
+
+        let mut words = self.hist_words.borrow_mut();
+        read_staging_buffer(ctx, &self.hist_staging, &mut words)?;
+        let mut luma_histogram = [0u32; 256];
+        luma_histogram.copy_from_slice(&words[..256]);
+        let diff_histogram = if has_history { Some(&words[256..]) } else { None };
+
+        return Ok(FrameStatistics::from_histograms(desc.width * desc.height, luma_histogram, diff_histogram));
+    }
+
+    /// configure the analyzer to use different input texture. history is kept if the new texture
+    /// has the same size and format.
+    pub fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        Self::validate_input(tex)?;
+        if !same_desc(&tex.desc(), &self._in_tex.desc()) {
+            (self.prev_tex, self.prev_srv) = create_history(&self.device, tex.desc())?;
+            self.has_history.set(false);
+        }
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::ARGB10UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR or ARGB10 format found {:?}", desc.format)))
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::cell::RefCell;
+
+use win_desktop_duplication::texture::{ColorFormat, Texture};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Buffer, ID3D11Device4, ID3D11DeviceContext4, ID3D11ShaderResourceView, ID3D11UnorderedAccessView};
+
+use crate::Result;
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{ComputeShader, ShaderStage};
+
+use super::{create_srv, create_staging_buffer, create_uav_buffer, read_staging_buffer, rgba_view_format};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{HistogramParams, LuminanceAnalyzer, LuminanceHistogram};
+    use crate::utils::create_input_texture;
+
+    #[test]
+    fn test_bin_edges() {
+        // 4 bins over 1..10000 nits, one decade each
+        let hist = LuminanceHistogram::new(vec![0; 4], 1.0, 10000.0);
+        let (lo, hi) = hist.bin_range(1);
+        assert!((lo - 10.0).abs() < 1e-3 && (hi - 100.0).abs() < 1e-2);
+        assert_eq!(hist.bin_of(0.0), 0);
+        assert_eq!(hist.bin_of(50.0), 1);
+        assert_eq!(hist.bin_of(1e6), 3);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let hist = LuminanceHistogram::new(vec![50, 0, 49, 1], 1.0, 10000.0);
+        assert_eq!(hist.total(), 100);
+        // half of the pixels are in the first decade
+        assert!(hist.percentile(0.5) <= 10.0 + 1e-3);
+        assert!((100.0..=1000.0).contains(&hist.percentile(0.98)));
+        // brightest pixel is in the last decade
+        let max = hist.max_nits();
+        assert!((max - 10000.0).abs() < 1e-1, "{}", max);
+        assert_eq!(LuminanceHistogram::new(vec![0; 4], 1.0, 10000.0).max_nits(), 0.0);
+
+        assert_eq!(hist.clone().into_vec(), vec![50, 0, 49, 1]);
+    }
+
+    #[test]
+    fn test_luminance_analyzer() {
+        let (device, ctx) = build_device_and_ctx();
+
+        // sRGB white at the default sdr white level of 80 nits
+        let desc = TextureDesc { width: 16, height: 16, format: ColorFormat::ARGB8UNorm };
+        let white = create_input_texture(&device, desc, Some(vec![255; 16 * 16 * 4])).unwrap();
+
+        let analyzer = LuminanceAnalyzer::new(&white, HistogramParams::default(), &device).unwrap();
+        let hist = analyzer.compute(&ctx).unwrap();
+        assert_eq!(hist.bins.len(), HistogramParams::default().bins as usize);
+        assert_eq!(hist.total(), 16 * 16);
+        assert_eq!(hist.bins[hist.bin_of(80.0)], 16 * 16);
+
+        assert!(LuminanceAnalyzer::new(&white, HistogramParams { bins: 0, ..Default::default() }, &device).is_err());
+    }
+}
+
+/// largest supported [HistogramParams::bins].
+pub const MAX_HISTOGRAM_BINS: u32 = 1024;
+
+/// Histogram layout used by [LuminanceAnalyzer]. Bins are spaced logarithmically between
+/// `min_nits` and `max_nits`; darker pixels (including black) count towards the first bin,
+/// brighter ones towards the last.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HistogramParams {
+    /// number of bins, `1..=MAX_HISTOGRAM_BINS`.
+    pub bins: u32,
+    /// lower edge of the first bin in nits.
+    pub min_nits: f32,
+    /// upper edge of the last bin in nits.
+    pub max_nits: f32,
+    /// luminance of sRGB white for [ARGB8UNorm][ColorFormat::ARGB8UNorm] and
+    /// [ARGB10UNorm][ColorFormat::ARGB10UNorm] input. `80.0` matches scRGB `1.0`.
+    pub sdr_white_nits: f32,
+}
+
+impl Default for HistogramParams {
+    fn default() -> Self {
+        Self {
+            bins: 256,
+            min_nits: 0.01,
+            max_nits: 10000.0,
+            sdr_white_nits: 80.0,
+        }
+    }
+}
+
+impl HistogramParams {
+    fn validate(&self) -> Result<()> {
+        if self.bins == 0 || self.bins > MAX_HISTOGRAM_BINS {
+            return Err(DxFilterErr::BadParam(format!("bin count must be within 1..={}, found {}", MAX_HISTOGRAM_BINS, self.bins)));
+        }
+        if !(self.min_nits > 0.0 && self.max_nits > self.min_nits && self.max_nits.is_finite()) {
+            return Err(DxFilterErr::BadParam(format!("expected 0 < min_nits < max_nits, found {} and {}", self.min_nits, self.max_nits)));
+        }
+        if !(self.sdr_white_nits > 0.0 && self.sdr_white_nits.is_finite()) {
+            return Err(DxFilterErr::BadParam(format!("sdr white level must be positive, found {}", self.sdr_white_nits)));
+        }
+        Ok(())
+    }
+}
+
+/// Luminance histogram produced by [LuminanceAnalyzer].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LuminanceHistogram {
+    /// pixel count of every bin, darkest first.
+    pub bins: Vec<u32>,
+    min_nits: f32,
+    max_nits: f32,
+}
+
+impl LuminanceHistogram {
+    fn new(bins: Vec<u32>, min_nits: f32, max_nits: f32) -> Self {
+        Self { bins, min_nits, max_nits }
+    }
+
+    /// take the raw bin counts.
+    pub fn into_vec(self) -> Vec<u32> {
+        self.bins
+    }
+
+    /// number of pixels counted.
+    pub fn total(&self) -> u64 {
+        self.bins.iter().map(|&n| n as u64).sum()
+    }
+
+    /// lower and upper edge of a bin in nits.
+    pub fn bin_range(&self, bin: usize) -> (f32, f32) {
+        (self.edge(bin as f32), self.edge(bin as f32 + 1.0))
+    }
+
+    /// bin a luminance in nits is counted in.
+    pub fn bin_of(&self, nits: f32) -> usize {
+        if nits <= 0.0 {
+            return 0;
+        }
+        let t = (nits.log2() - self.min_nits.log2()) / (self.max_nits.log2() - self.min_nits.log2());
+        (t * self.bins.len() as f32).clamp(0.0, (self.bins.len() - 1) as f32) as usize
+    }
+
+    /// luminance in nits below which fraction `p` (`0.0..=1.0`) of the pixels lie, interpolated
+    /// logarithmically within the bin. `percentile(0.99)` is a robust alternative to
+    /// [max_nits][LuminanceHistogram::max_nits] for tone mapping.
+    pub fn percentile(&self, p: f32) -> f32 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        let target = (p.clamp(0.0, 1.0) as f64 * total as f64).max(1.0);
+        let mut seen = 0.0;
+        for (i, &n) in self.bins.iter().enumerate() {
+            if n == 0 {
+                continue;
+            }
+            if seen + n as f64 >= target {
+                let frac = ((target - seen) / n as f64) as f32;
+                return self.edge(i as f32 + frac);
+            }
+            seen += n as f64;
+        }
+        self.edge(self.bins.len() as f32)
+    }
+
+    /// upper edge of the brightest non-empty bin in nits, `0.0` for an empty histogram.
+    pub fn max_nits(&self) -> f32 {
+        match self.bins.iter().rposition(|&n| n != 0) {
+            Some(i) => self.bin_range(i).1,
+            None => 0.0,
+        }
+    }
+
+    /// luminance at fractional bin position `pos`.
+    fn edge(&self, pos: f32) -> f32 {
+        let t = pos / self.bins.len() as f32;
+        (self.min_nits.log2() + t * (self.max_nits.log2() - self.min_nits.log2())).exp2()
+    }
+}
+
+generate_shader!(luminance_histogram_cs cs {
+    src_file: "src/common_filters/shaders/luminance_histogram_cs.hlsl",
+    entry_point: "main",
+    target: "cs_5_0"
+});
+
+/// layout of `HistogramConstants` cbuffer in luminance_histogram_cs.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct HistogramConstants {
+    size: [u32; 2],
+    bin_count: u32,
+    is_linear: u32,
+    log_min_nits: f32,
+    inv_log_range: f32,
+    sdr_white_nits: f32,
+    _padding: f32,
+}
+
+/// Builds a [LuminanceHistogram] of [ARGB16Float][ColorFormat::ARGB16Float] (scRGB),
+/// [ARGB10UNorm][ColorFormat::ARGB10UNorm] or [ARGB8UNorm][ColorFormat::ARGB8UNorm] frames, e.g.
+/// to drive automatic exposure when tone mapping HDR captures.
+///
+/// The histogram is computed in a single compute pass; only the bins are read back.
+///
+/// This is not a [DxFilter][crate::DxFilter] as it produces no output texture.
+pub struct LuminanceAnalyzer {
+    device: ID3D11Device4,
+    cs: ComputeShader,
+    cb: ConstantBuffer<HistogramConstants>,
+
+    params: HistogramParams,
+
+    _in_tex: Texture,
+    srv: ID3D11ShaderResourceView,
+
+    bin_buf: ID3D11Buffer,
+    bin_uav: ID3D11UnorderedAccessView,
+    bin_staging: ID3D11Buffer,
+    /// scratch space for reading back the bins.
+    bin_words: RefCell<Vec<u32>>,
+}
+
+impl LuminanceAnalyzer {
+    /// create new instance of LuminanceAnalyzer for frames in `input_tex`.
+    pub fn new(input_tex: &Texture, params: HistogramParams, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        params.validate()?;
+
+        let cs = luminance_histogram_cs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &HistogramConstants::default())?;
+        let srv = create_srv(device, input_tex, rgba_view_format(input_tex.desc().format))?;
+        let (bin_buf, bin_uav) = create_uav_buffer(device, params.bins)?;
+
+        return Ok(Self {
+            device: device.clone(),
+            cs,
+            cb,
+            params,
+            _in_tex: input_tex.clone(),
+            srv,
+            bin_buf,
+            bin_uav,
+            bin_staging: create_staging_buffer(device, params.bins)?,
+            bin_words: RefCell::new(vec![0; params.bins as usize]),
+        });
+    }
+
+    /// current histogram layout.
+    pub fn params(&self) -> HistogramParams {
+        return self.params;
+    }
+
+    /// change histogram layout. takes effect on the next [compute][LuminanceAnalyzer::compute].
+    pub fn set_params(&mut self, params: HistogramParams) -> Result<()> {
+        params.validate()?;
+        if params.bins != self.params.bins {
+            (self.bin_buf, self.bin_uav) = create_uav_buffer(&self.device, params.bins)?;
+            self.bin_staging = create_staging_buffer(&self.device, params.bins)?;
+            self.bin_words = RefCell::new(vec![0; params.bins as usize]);
+        }
+        self.params = params;
+        return Ok(());
+    }
+
+    /// compute the histogram of the current content of the input texture. waits for the gpu to finish.
+    pub fn compute(&self, ctx: &ID3D11DeviceContext4) -> Result<LuminanceHistogram> {
+        let desc = self._in_tex.desc();
+        let params = &self.params;
+        let constants = HistogramConstants {
+            size: [desc.width, desc.height],
+            bin_count: params.bins,
+            is_linear: matches!(desc.format, ColorFormat::ARGB16Float) as u32,
+            log_min_nits: params.min_nits.log2(),
+            inv_log_range: 1.0 / (params.max_nits.log2() - params.min_nits.log2()),
+            sdr_white_nits: params.sdr_white_nits,
+            _padding: 0.0,
+        };
+        self.cb.update(ctx, &constants)?;
+
+        unsafe {
+            ctx.ClearUnorderedAccessViewUint(&self.bin_uav, &[0; 4]);
+
+            ctx.CSSetShader(self.cs.as_raw_ref(), None);
+            self.cb.bind(ctx, ShaderStage::Compute, 0);
+            ctx.CSSetShaderResources(0, Some(&[Some(self.srv.clone())]));
+            let uavs = [Some(self.bin_uav.clone())];
+            ctx.CSSetUnorderedAccessViews(0, uavs.len() as _, Some(uavs.as_ptr()), None);
+            ctx.Dispatch((desc.width + 15) / 16, (desc.height + 15) / 16, 1);
+
+            ctx.CSSetShaderResources(0, Some(&[None]));
+            let no_uavs: [Option<ID3D11UnorderedAccessView>; 1] = [None];
+            ctx.CSSetUnorderedAccessViews(0, no_uavs.len() as _, Some(no_uavs.as_ptr()), None);
+
+            ctx.CopyResource(&self.bin_staging, &self.bin_buf);
+        }
+
+        let mut words = self.bin_words.borrow_mut();
+        read_staging_buffer(ctx, &self.bin_staging, &mut words)?;
+        return Ok(LuminanceHistogram::new(words.clone(), params.min_nits, params.max_nits));
+    }
+
+    /// configure the analyzer to use different input texture.
+    pub fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        Self::validate_input(tex)?;
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB16Float | ColorFormat::ARGB10UNorm | ColorFormat::ARGB8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB16F, ARGB10 or ARGB format found {:?}", desc.format)))
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use win_desktop_duplication::texture::{ColorFormat, Texture};
+use windows::Win32::Graphics::Direct3D::{D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP, D3D_SRV_DIMENSION_TEXTURE3D};
+use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_SHADER_RESOURCE, D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_SUBRESOURCE_DATA, D3D11_TEX3D_SRV, D3D11_TEXTURE3D_DESC, D3D11_USAGE_IMMUTABLE, D3D11_VIEWPORT, ID3D11Device4, ID3D11DeviceContext4, ID3D11RenderTargetView, ID3D11SamplerState, ID3D11ShaderResourceView};
+use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_R32G32B32A32_FLOAT;
+
+use crate::{DxFilter, Result};
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::lut::CubeLut;
+use crate::shader::{PixelShader, ShaderStage, VertexShader};
+
+use super::{create_rtv, create_srv, create_tex_sampler, rgba_view_format, simple_vs};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::tex_reader::TextureReader;
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{ApplyLUT3D, LutInterpolation};
+    use crate::DxFilter;
+    use crate::lut::CubeLut;
+    use crate::utils::{create_input_texture, create_output_texture};
+
+    #[test]
+    fn test_lut3d() {
+        let (device, ctx) = build_device_and_ctx();
+
+        let desc = TextureDesc { width: 4, height: 4, format: ColorFormat::ARGB8UNorm };
+        let src: Vec<u8> = [200u8, 100, 50, 255].repeat(16);
+        let input_tex = create_input_texture(&device, desc, Some(src)).unwrap();
+        let output_tex = create_output_texture(&device, desc, None).unwrap();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+
+        // identity lut keeps the colour with both interpolation modes.
+        let identity = CubeLut::identity(17);
+        let mut filter = ApplyLUT3D::new(&input_tex, &output_tex, &identity, LutInterpolation::Trilinear, &device).unwrap();
+        let mut out = Vec::new();
+        for interpolation in [LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+            filter.set_interpolation(interpolation);
+            filter.apply_filter(&ctx).unwrap();
+            reader.get_data(&mut out, &output_tex).unwrap();
+            for (got, expected) in out[0..3].iter().zip([200u8, 100, 50]) {
+                assert!(got.abs_diff(expected) <= 1, "{:?}: {} != {}", interpolation, got, expected);
+            }
+        }
+
+        // inverting lut
+        let mut invert = CubeLut::identity(2);
+        invert.data.iter_mut().for_each(|c| c.iter_mut().for_each(|v| *v = 1.0 - *v));
+        filter.set_lut(&invert).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert!(out[0].abs_diff(55) <= 1, "{}", out[0]);
+    }
+}
+
+generate_shader!(lut3d_trilinear_ps ps {
+    src_file: "src/common_filters/shaders/lut3d_ps.hlsl",
+    entry_point: "main",
+    target: "ps_5_0"
+});
+
+generate_shader!(lut3d_tetrahedral_ps ps {
+    src_file: "src/common_filters/shaders/lut3d_ps.hlsl",
+    entry_point: "main",
+    target: "ps_5_0",
+    defines: {
+        ("TETRAHEDRAL", "1")
+    }
+});
+
+/// interpolation used by [ApplyLUT3D] between lattice points of the LUT.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LutInterpolation {
+    /// hardware trilinear filtering. fastest.
+    Trilinear,
+    /// tetrahedral interpolation from four lattice points. keeps greys neutral on coarse LUTs.
+    Tetrahedral,
+}
+
+/// layout of `LutConstants` cbuffer in lut3d_ps.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct LutConstants {
+    domain_min: [f32; 3],
+    lut_size: f32,
+    domain_max: [f32; 3],
+    _padding: f32,
+}
+
+/// Filter for colour grading [ARGBUNorm][ColorFormat::ARGB8UNorm] or [ABGRUNorm][ColorFormat::ABGR8UNorm]
+/// textures with a 3D LUT, usually loaded from a `.cube` file with [CubeLut::from_file]. filter also
+/// scales automatically based on input and output textures.
+pub struct ApplyLUT3D {
+    device: ID3D11Device4,
+    vs: VertexShader,
+    trilinear_ps: PixelShader,
+    tetrahedral_ps: PixelShader,
+    interpolation: LutInterpolation,
+
+    constants: LutConstants,
+    cb: ConstantBuffer<LutConstants>,
+    lut_srv: ID3D11ShaderResourceView,
+
+    _in_tex: Texture,
+    _out_tex: Texture,
+
+    srv: ID3D11ShaderResourceView,
+    rtv: ID3D11RenderTargetView,
+    sampler: ID3D11SamplerState,
+}
+
+impl ApplyLUT3D {
+    /// create new instance of ApplyLUT3D filter. After creation, filter takes ARGB or ABGR input from
+    /// `input_tex`, maps it through `lut` and writes to ARGB or ABGR `out_tex`.
+    pub fn new(input_tex: &Texture, out_tex: &Texture, lut: &CubeLut, interpolation: LutInterpolation, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        Self::validate_output(out_tex)?;
+
+        let trilinear_ps = lut3d_trilinear_ps(device.clone())?;
+        let tetrahedral_ps = lut3d_tetrahedral_ps(device.clone())?;
+        let vs = simple_vs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &LutConstants::default())?;
+        let lut_srv = create_lut_srv(device, lut)?;
+
+        let srv = create_srv(device, input_tex, input_tex.desc().format.into())?;
+        let sampler = create_tex_sampler(device)?;
+        let rtv = create_rtv(device, out_tex, rgba_view_format(out_tex.desc().format))?;
+
+        return Ok(Self {
+            device: device.clone(),
+            vs,
+            trilinear_ps,
+            tetrahedral_ps,
+            interpolation,
+            constants: lut_constants(lut),
+            cb,
+            lut_srv,
+            _in_tex: input_tex.clone(),
+            _out_tex: out_tex.clone(),
+            srv,
+            rtv,
+            sampler,
+        });
+    }
+
+    /// replace the LUT. uploads a new 3D texture.
+    pub fn set_lut(&mut self, lut: &CubeLut) -> Result<()> {
+        self.lut_srv = create_lut_srv(&self.device, lut)?;
+        self.constants = lut_constants(lut);
+        return Ok(());
+    }
+
+    /// switch between trilinear and tetrahedral interpolation.
+    pub fn set_interpolation(&mut self, interpolation: LutInterpolation) {
+        self.interpolation = interpolation;
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format)))
+            }
+        }
+    }
+    fn validate_output(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format)))
+            }
+        }
+    }
+}
+
+impl DxFilter for ApplyLUT3D {
+    fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        let out_desc = self._out_tex.desc();
+        let vp = D3D11_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: out_desc.width as _,
+            Height: out_desc.height as _,
+            MinDepth: 0.0,
+            MaxDepth: 0.0,
+        };
+        let ps = match self.interpolation {
+            LutInterpolation::Trilinear => &self.trilinear_ps,
+            LutInterpolation::Tetrahedral => &self.tetrahedral_ps,
+        };
+        self.cb.update(ctx, &self.constants)?;
+        unsafe {
+            ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
+            ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
+            ctx.PSSetShader(ps.as_raw_ref(), Some(&[]));
+            self.cb.bind(ctx, ShaderStage::Pixel, 0);
+            ctx.PSSetSamplers(0, Some(&[Some(self.sampler.clone())]));
+            ctx.PSSetShaderResources(0, Some(&[Some(self.srv.clone()), Some(self.lut_srv.clone())]));
+            ctx.RSSetViewports(Some(&[vp]));
+            ctx.OMSetRenderTargets(Some(&[Some(self.rtv.clone())]), None);
+            ctx.Draw(4, 0);
+        }
+        return Ok(());
+    }
+
+    fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        ApplyLUT3D::validate_input(tex)?;
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, tex.desc().format.into())?;
+        return Ok(());
+    }
+
+    fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
+        ApplyLUT3D::validate_output(tex)?;
+        self._out_tex = tex.clone();
+        self.rtv = create_rtv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+}
+
+fn lut_constants(lut: &CubeLut) -> LutConstants {
+    LutConstants {
+        domain_min: lut.domain_min,
+        lut_size: lut.size as f32,
+        domain_max: lut.domain_max,
+        _padding: 0.0,
+    }
+}
+
+/// upload `lut` into an immutable RGBA32F 3D texture and create a view for it.
+fn create_lut_srv(dev: &ID3D11Device4, lut: &CubeLut) -> Result<ID3D11ShaderResourceView> {
+    let size = lut.size;
+    if size < 2 || lut.data.len() != (size * size * size) as usize {
+        return Err(DxFilterErr::BadParam(format!("lut of size {} must contain {} entries, found {}", size, size * size * size, lut.data.len())));
+    }
+    let texels: Vec<[f32; 4]> = lut.data.iter().map(|c| [c[0], c[1], c[2], 1.0]).collect();
+
+    let desc = D3D11_TEXTURE3D_DESC {
+        Width: size,
+        Height: size,
+        Depth: size,
+        MipLevels: 1,
+        Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+        Usage: D3D11_USAGE_IMMUTABLE,
+        BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as _,
+        CPUAccessFlags: 0,
+        MiscFlags: 0,
+    };
+    let init = D3D11_SUBRESOURCE_DATA {
+        pSysMem: texels.as_ptr() as _,
+        SysMemPitch: size * 16,
+        SysMemSlicePitch: size * size * 16,
+    };
+    let mut tex = None;
+    if let Err(e) = unsafe { dev.CreateTexture3D(&desc, Some(&init), Some(&mut tex)) } {
+        return Err(DxFilterErr::Unknown(format!("failed to create lut texture. {:?}", e)));
+    }
+
+    let mut srv_desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
+        Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+        ViewDimension: D3D_SRV_DIMENSION_TEXTURE3D,
+        Anonymous: Default::default(),
+    };
+    srv_desc.Anonymous.Texture3D = D3D11_TEX3D_SRV {
+        MostDetailedMip: 0,
+        MipLevels: 1,
+    };
+    let mut srv = None;
+    if let Err(e) = unsafe { dev.CreateShaderResourceView(tex.as_ref().unwrap(), Some(&srv_desc), Some(&mut srv)) } {
+        Err(DxFilterErr::Unknown(format!("failed to create lut shader resource view. {:?}", e)))
+    } else {
+        Ok(srv.unwrap())
+    }
+}
@@ -1,13 +1,49 @@
 use core::default::Default;
+use core::mem::size_of;
+use core::ptr::copy_nonoverlapping;
 
-use win_desktop_duplication::texture::{ColorFormat, Texture};
+use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
 use windows::Win32::Graphics::Direct3D::{D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP, D3D_SRV_DIMENSION_TEXTURE2D};
-use windows::Win32::Graphics::Direct3D11::{D3D11_COMPARISON_ALWAYS, D3D11_COMPARISON_GREATER, D3D11_COMPARISON_NEVER, D3D11_FILTER_COMPARISON_ANISOTROPIC, D3D11_FILTER_MAXIMUM_ANISOTROPIC, D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_FLOAT32_MAX, D3D11_RENDER_TARGET_VIEW_DESC, D3D11_RTV_DIMENSION_TEXTURE2D, D3D11_SAMPLER_DESC, D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_TEX2D_RTV, D3D11_TEX2D_SRV, D3D11_TEXTURE_ADDRESS_CLAMP, D3D11_VIEWPORT, ID3D11Device4, ID3D11DeviceContext4, ID3D11RenderTargetView, ID3D11SamplerState, ID3D11ShaderResourceView};
-use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_R8_UNORM, DXGI_FORMAT_R8G8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM};
+use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_UNORDERED_ACCESS, D3D11_BUFFER_DESC, D3D11_BUFFER_UAV, D3D11_COMPARISON_ALWAYS, D3D11_COMPARISON_GREATER, D3D11_COMPARISON_NEVER, D3D11_CPU_ACCESS_READ, D3D11_FILTER, D3D11_FILTER_COMPARISON_ANISOTROPIC, D3D11_FILTER_MAXIMUM_ANISOTROPIC, D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_FLOAT32_MAX, D3D11_MAP_READ, D3D11_MAPPED_SUBRESOURCE, D3D11_RENDER_TARGET_VIEW_DESC, D3D11_RESOURCE_MISC_BUFFER_STRUCTURED, D3D11_RTV_DIMENSION_TEXTURE2D, D3D11_SAMPLER_DESC, D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_TEX2D_RTV, D3D11_TEX2D_SRV, D3D11_TEX2D_UAV, D3D11_TEXTURE_ADDRESS_CLAMP, D3D11_UAV_DIMENSION_BUFFER, D3D11_UAV_DIMENSION_TEXTURE2D, D3D11_UNORDERED_ACCESS_VIEW_DESC, D3D11_USAGE, D3D11_USAGE_DEFAULT, D3D11_USAGE_STAGING, D3D11_VIEWPORT, ID3D11Buffer, ID3D11Device4, ID3D11DeviceContext4, ID3D11RenderTargetView, ID3D11SamplerState, ID3D11ShaderResourceView, ID3D11UnorderedAccessView};
+use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_R8_UNORM, DXGI_FORMAT_R8G8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_UNKNOWN};
 
-use crate::{DxFilter, Result};
+use crate::{DxFilter, generate_shader_variants, Result};
+use crate::color::{YuvMatrix, YuvRange};
 use crate::error::DxFilterErr;
 use crate::shader::{PixelShader, VertexShader};
+use crate::utils::create_input_texture;
+
+pub use chroma_key::*;
+pub use color_adjust::*;
+pub use deinterlace::*;
+pub use dirty_regions::*;
+pub use dither::DitherMode;
+pub use frame_stats::*;
+pub use lut3d::*;
+pub use luminance::*;
+pub use nv12_compute::NV12Backend;
+pub use quality::*;
+pub use scale::ScaleAlgorithm;
+pub use sharpen::*;
+pub use simulcast::*;
+use dither::DitherPass;
+use nv12_compute::NV12ComputePass;
+use scale::ScalePass;
+use sharpen::SharpenPass;
+
+mod chroma_key;
+mod color_adjust;
+mod deinterlace;
+mod dirty_regions;
+mod dither;
+mod frame_stats;
+mod lut3d;
+mod luminance;
+mod nv12_compute;
+mod quality;
+mod scale;
+mod sharpen;
+mod simulcast;
 
 #[cfg(test)]
 mod test {
@@ -15,14 +51,16 @@ mod test {
 
     use win_desktop_duplication::devices::AdapterFactory;
     use win_desktop_duplication::tex_reader::TextureReader;
-    use win_desktop_duplication::texture::Texture;
+    use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
     use windows::core::Interface;
     use windows::Win32::Graphics::Direct3D::{D3D_DRIVER_TYPE_UNKNOWN, D3D_FEATURE_LEVEL_11_1};
     use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_SDK_VERSION, D3D11_SUBRESOURCE_DATA, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11CreateDevice, ID3D11Device4, ID3D11DeviceContext4};
     use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_AYUV, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_NV12, DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_SAMPLE_DESC};
 
-    use crate::common_filters::{ConvertARGBToAYUV, ConvertARGBToNV12, ConvertHighBitToARGB8};
+    use crate::common_filters::{ConvertARGBToAYUV, ConvertARGBToNV12, ConvertHighBitToARGB8, DitherMode, NV12Backend};
+    use crate::color::{YuvMatrix, YuvRange};
     use crate::DxFilter;
+    use crate::utils::{create_input_texture, create_output_texture, create_uav_output_texture};
 
     const SOURCE_IMG: [u8; 1920 * 1080 * 4] = [10; 1920 * 1080 * 4];
     const TARGET_PIX: [u8; 4] = [127, 127, 24, 10];
@@ -173,7 +211,92 @@ mod test {
         assert_eq!(out[1280 * 720..(1280 * 720) + 2], TARGET_PIX[0..2]);
     }
 
-    fn build_device_and_ctx() -> (ID3D11Device4, ID3D11DeviceContext4) {
+    #[test]
+    fn test_argb_to_nv12_compute() {
+        let (device, ctx) = build_device_and_ctx();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+
+        // horizontal and vertical gradients so the 2x2 chroma averaging matters
+        let (width, height) = (64u32, 32u32);
+        let mut src = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                src.extend_from_slice(&[(x * 4) as u8, (y * 8) as u8, 200 - x as u8, 255]);
+            }
+        }
+        let input_tex = create_input_texture(&device, TextureDesc { width, height, format: ColorFormat::ARGB8UNorm }, Some(src)).unwrap();
+        let nv12_desc = TextureDesc { width, height, format: ColorFormat::NV12 };
+        let raster_tex = create_output_texture(&device, nv12_desc, None).unwrap();
+        let compute_tex = create_uav_output_texture(&device, nv12_desc, None).unwrap();
+
+        let raster = ConvertARGBToNV12::new(&input_tex, &raster_tex, &device).unwrap();
+        let compute = ConvertARGBToNV12::new_with_backend(&input_tex, &compute_tex, NV12Backend::Compute, &device).unwrap();
+        assert_eq!(raster.backend(), NV12Backend::Raster);
+        assert_eq!(compute.backend(), NV12Backend::Compute);
+        raster.apply_filter(&ctx).unwrap();
+        compute.apply_filter(&ctx).unwrap();
+
+        let mut expected = Vec::new();
+        let mut out = Vec::new();
+        reader.get_data(&mut expected, &raster_tex).unwrap();
+        reader.get_data(&mut out, &compute_tex).unwrap();
+        assert_eq!(out.len(), expected.len());
+        for (i, (a, b)) in out.iter().zip(expected.iter()).enumerate() {
+            assert!((*a as i32 - *b as i32).abs() <= 1, "byte {} differs: {} vs {}", i, a, b);
+        }
+
+        // output without unordered access can't be written by the compute backend
+        assert!(ConvertARGBToNV12::new_with_backend(&input_tex, &raster_tex, NV12Backend::Compute, &device).is_err());
+    }
+
+    #[test]
+    fn test_argb_to_ayuv_color_space() {
+        let (device, ctx) = build_device_and_ctx();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+
+        // pure red, BGRA in memory
+        let (width, height) = (16u32, 16u32);
+        let src = [0u8, 0, 255, 255].repeat((width * height) as usize);
+        let input_tex = create_input_texture(&device, TextureDesc { width, height, format: ColorFormat::ARGB8UNorm }, Some(src)).unwrap();
+        let output_tex = create_output_texture(&device, TextureDesc { width, height, format: ColorFormat::AYUV }, None).unwrap();
+
+        let mut filter = ConvertARGBToAYUV::new(&input_tex, &output_tex, &device).unwrap();
+        assert_eq!(filter.color_space(), (YuvMatrix::Bt601, YuvRange::Limited));
+
+        // V, U, Y, A for BT.601 limited, BT.709 full and BT.2020 full
+        for (matrix, range, expected) in [
+            (YuvMatrix::Bt601, YuvRange::Limited, [240, 90, 81]),
+            (YuvMatrix::Bt709, YuvRange::Full, [255, 98, 54]),
+            (YuvMatrix::Bt2020, YuvRange::Full, [255, 92, 67]),
+        ] {
+            filter.set_color_space(matrix, range).unwrap();
+            assert_eq!(filter.color_space(), (matrix, range));
+            filter.apply_filter(&ctx).unwrap();
+            let mut out = Vec::new();
+            reader.get_data(&mut out, &output_tex).unwrap();
+            for (i, want) in expected.iter().enumerate() {
+                assert!((out[i] as i32 - *want).abs() <= 1, "{:?} {:?} channel {}: {} vs {}", matrix, range, i, out[i], want);
+            }
+        }
+
+        // both nv12 backends follow the color space
+        let nv12_desc = TextureDesc { width, height, format: ColorFormat::NV12 };
+        let raster_tex = create_output_texture(&device, nv12_desc, None).unwrap();
+        let compute_tex = create_uav_output_texture(&device, nv12_desc, None).unwrap();
+        let mut raster = ConvertARGBToNV12::new(&input_tex, &raster_tex, &device).unwrap();
+        let mut compute = ConvertARGBToNV12::new_with_backend(&input_tex, &compute_tex, NV12Backend::Compute, &device).unwrap();
+        for (filter, tex) in [(&mut raster, &raster_tex), (&mut compute, &compute_tex)] {
+            filter.set_color_space(YuvMatrix::Bt709, YuvRange::Full).unwrap();
+            filter.apply_filter(&ctx).unwrap();
+            let mut out = Vec::new();
+            reader.get_data(&mut out, tex).unwrap();
+            let uv = (width * height) as usize;
+            assert!((out[0] as i32 - 54).abs() <= 1, "Y {}", out[0]);
+            assert!((out[uv] as i32 - 98).abs() <= 1 && out[uv + 1] >= 254, "UV {} {}", out[uv], out[uv + 1]);
+        }
+    }
+
+    pub(super) fn build_device_and_ctx() -> (ID3D11Device4, ID3D11DeviceContext4) {
         let adapter = AdapterFactory::new().get_adapter_by_idx(0).unwrap();
         let feature_levels = [D3D_FEATURE_LEVEL_11_1];
         let mut device = None;
@@ -252,6 +375,34 @@ mod test {
         assert_eq!(out[3], 255); // A (forced opaque)
     }
 
+    #[test]
+    fn test_r10_to_argb8_dither() {
+        let (device, ctx) = build_device_and_ctx();
+
+        // 10 bit value 514 sits at ~128.12 in 8 bit code values. plain quantization gives 128
+        // everywhere; an 8x8 ordered dither mixes in a few 129s.
+        let desc = TextureDesc { width: 8, height: 8, format: ColorFormat::ARGB10UNorm };
+        let packed: u32 = 3u32 << 30 | 514u32 << 20 | 514u32 << 10 | 514u32;
+        let src: Vec<u8> = packed.to_le_bytes().repeat(64);
+        let input_tex = create_input_texture(&device, desc, Some(src)).unwrap();
+        let output_tex = create_output_texture(&device, TextureDesc { format: ColorFormat::ARGB8UNorm, ..desc }, None).unwrap();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+
+        let mut filter = ConvertHighBitToARGB8::new(&input_tex, &output_tex, &device).unwrap();
+        let mut out = Vec::new();
+
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert!(out.chunks(4).all(|px| px[0] == 128));
+
+        filter.set_dither(DitherMode::Ordered);
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert!(out.chunks(4).all(|px| px[0] == 128 || px[0] == 129));
+        let high = out.chunks(4).filter(|px| px[0] == 129).count();
+        assert!((4..=12).contains(&high), "unexpected dither distribution: {} of 64", high);
+    }
+
     #[test]
     fn test_r16f_to_argb8() {
         let (device, ctx) = build_device_and_ctx();
@@ -325,58 +476,75 @@ mod test {
 
 
 generate_shader!(simple_vs vs {
-    src_file: "src\\common_filters\\shaders\\simple_vs.hlsl",
+    src_file: "src/common_filters/shaders/simple_vs.hlsl",
     entry_point: "main",
     target: "vs_5_0"
 });
 
 generate_shader!(simple_ps ps {
-    src_file: "src\\common_filters\\shaders\\simple_ps.hlsl",
+    src_file: "src/common_filters/shaders/simple_ps.hlsl",
     entry_point: "main",
     target: "ps_5_0"
 });
 
 // generate_shader!(argb_to_yuv_pl ps {
-//     src_file: "src\\common_filters\\shaders\\argb_to_yuv_pl_ps.hlsl",
+//     src_file: "src/common_filters/shaders/argb_to_yuv_pl_ps.hlsl",
 //     entry_point: "main",
 //     target: "ps_5_0"
 // });
-generate_shader!(argb_to_ayuv ps {
-    src_file: "src\\common_filters\\shaders\\argb_to_ayuv_ps.hlsl",
+generate_shader_variants!(argb_to_ayuv ps {
+    src_file: "src/common_filters/shaders/argb_to_ayuv_ps.hlsl",
     entry_point: "main",
-    target: "ps_5_0"
+    target: "ps_5_0",
+    variants: {
+        YUV_MATRIX: YuvMatrix { Bt601 => "601", Bt709 => "709", Bt2020 => "2020" },
+        YUV_FULL_RANGE: YuvRange { Limited => "0", Full => "1" },
+    }
 });
 
-generate_shader!(argb_to_y ps {
-    src_file: "src\\common_filters\\shaders\\argb_to_y_ps.hlsl",
+generate_shader_variants!(argb_to_y ps {
+    src_file: "src/common_filters/shaders/argb_to_y_ps.hlsl",
     entry_point: "main",
-    target: "ps_5_0"
+    target: "ps_5_0",
+    variants: {
+        YUV_MATRIX: YuvMatrix { Bt601 => "601", Bt709 => "709", Bt2020 => "2020" },
+        YUV_FULL_RANGE: YuvRange { Limited => "0", Full => "1" },
+    }
 });
 
-generate_shader!(argb_to_uv ps {
-    src_file: "src\\common_filters\\shaders\\argb_to_uv_ps.hlsl",
+generate_shader_variants!(argb_to_uv ps {
+    src_file: "src/common_filters/shaders/argb_to_uv_ps.hlsl",
     entry_point: "main",
-    target: "ps_5_0"
+    target: "ps_5_0",
+    variants: {
+        YUV_MATRIX: YuvMatrix { Bt601 => "601", Bt709 => "709", Bt2020 => "2020" },
+        YUV_FULL_RANGE: YuvRange { Limited => "0", Full => "1" },
+    }
 });
 
 generate_shader!(r10_to_argb8 ps {
-    src_file: "src\\common_filters\\shaders\\r10_to_argb8_ps.hlsl",
+    src_file: "src/common_filters/shaders/r10_to_argb8_ps.hlsl",
     entry_point: "main",
     target: "ps_5_0"
 });
 
 generate_shader!(r16f_to_argb8 ps {
-    src_file: "src\\common_filters\\shaders\\r16f_to_argb8_ps.hlsl",
+    src_file: "src/common_filters/shaders/r16f_to_argb8_ps.hlsl",
     entry_point: "main",
     target: "ps_5_0"
 });
 
-/// Filter for converting [ARGBUNorm][ColorFormat::ARGB8UNorm] or [ABGRUNorm][ColorFormat::ABGR8UNorm]
-/// into [AYUV][ColorFormat::AYUV] format. filter also scales automatically based on input and output textures.
+/// Filter for converting [ARGBUNorm][ColorFormat::ARGB8UNorm], [ABGRUNorm][ColorFormat::ABGR8UNorm]
+/// or [ARGB10UNorm][ColorFormat::ARGB10UNorm] into [AYUV][ColorFormat::AYUV] format. filter also scales
+/// automatically based on input and output textures. see [set_dither][ConvertARGBToAYUV::set_dither]
+/// for dithering 10 bit input and [set_color_space][ConvertARGBToAYUV::set_color_space] for
+/// choosing the Y'CbCr matrix and range.
 pub struct ConvertARGBToAYUV {
     device: ID3D11Device4,
     vs: VertexShader,
     ps: PixelShader,
+    color_space: (YuvMatrix, YuvRange),
+    dither: DitherPass,
 
     _in_tex: Texture,
     _out_tex: Texture,
@@ -393,8 +561,10 @@ impl ConvertARGBToAYUV {
         Self::validate_input(input_tex)?;
         Self::validate_output(out_tex)?;
 
-        let ps = argb_to_ayuv(device.clone())?;
+        let color_space = Default::default();
+        let ps = argb_to_ayuv(device.clone(), color_space)?;
         let vs = simple_vs(device.clone())?;
+        let dither = DitherPass::new(device)?;
 
         let srv = create_srv(device, input_tex, input_tex.desc().format.into())?;
         let sampler = create_tex_sampler(device)?;
@@ -404,6 +574,8 @@ impl ConvertARGBToAYUV {
             device: device.clone(),
             vs,
             ps,
+            color_space,
+            dither,
             _in_tex: input_tex.clone(),
             _out_tex: out_tex.clone(),
             srv,
@@ -412,15 +584,47 @@ impl ConvertARGBToAYUV {
         });
     }
 
+    /// create new instance of ConvertARGBToAYUV filter which converts with given Y'CbCr `matrix`
+    /// and `range`.
+    pub fn new_with_color_space(input_tex: &Texture, out_tex: &Texture, matrix: YuvMatrix, range: YuvRange, device: &ID3D11Device4) -> Result<Self> {
+        let mut filter = Self::new(input_tex, out_tex, device)?;
+        filter.set_color_space(matrix, range)?;
+        return Ok(filter);
+    }
+
+    /// current Y'CbCr matrix and range.
+    pub fn color_space(&self) -> (YuvMatrix, YuvRange) {
+        return self.color_space;
+    }
+
+    /// change Y'CbCr matrix and range. defaults to [YuvMatrix::Bt601] with [YuvRange::Limited].
+    pub fn set_color_space(&mut self, matrix: YuvMatrix, range: YuvRange) -> Result<()> {
+        if (matrix, range) != self.color_space {
+            self.ps = argb_to_ayuv(self.device.clone(), (matrix, range))?;
+            self.color_space = (matrix, range);
+        }
+        return Ok(());
+    }
+
+    /// current dithering mode.
+    pub fn dither(&self) -> DitherMode {
+        return self.dither.mode();
+    }
+
+    /// set dithering applied when writing the 8 bit output. defaults to [DitherMode::None].
+    pub fn set_dither(&mut self, mode: DitherMode) {
+        self.dither.set_mode(mode);
+    }
+
 
     fn validate_input(tex: &Texture) -> Result<()> {
         let desc = tex.desc();
         match desc.format {
-            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::ARGB10UNorm => {
                 Ok(())
             }
             _ => {
-                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format).to_owned()))
+                Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR or ARGB10UNorm format found {:?}", desc.format).to_owned()))
             }
         }
     }
@@ -448,6 +652,7 @@ impl DxFilter for ConvertARGBToAYUV {
             MinDepth: 0.0,
             MaxDepth: 0.0,
         };
+        self.dither.bind(ctx)?;
         unsafe {
             ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
             ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
@@ -477,61 +682,125 @@ impl DxFilter for ConvertARGBToAYUV {
 }
 
 
-/// Filter for converting [ARGBUNorm][ColorFormat::ARGB8UNorm] or [ABGRUNorm][ColorFormat::ABGR8UNorm]
-/// into [NV12][ColorFormat::NV12] format. filter also scales automatically based on input and output textures.
+/// Filter for converting [ARGBUNorm][ColorFormat::ARGB8UNorm], [ABGRUNorm][ColorFormat::ABGR8UNorm]
+/// or [ARGB10UNorm][ColorFormat::ARGB10UNorm] into [NV12][ColorFormat::NV12] format. filter also scales
+/// automatically based on input and output textures. see [set_dither][ConvertARGBToNV12::set_dither]
+/// for dithering 10 bit input, [new_with_backend][ConvertARGBToNV12::new_with_backend] for
+/// converting with a compute shader and [set_color_space][ConvertARGBToNV12::set_color_space] for
+/// choosing the Y'CbCr matrix and range.
 pub struct ConvertARGBToNV12 {
     device: ID3D11Device4,
-    vs: VertexShader,
-    y_ps: PixelShader,
-    uv_ps: PixelShader,
+    writer: NV12Writer,
+    color_space: (YuvMatrix, YuvRange),
+    dither: DitherPass,
 
     _in_tex: Texture,
     _out_tex: Texture,
 
     srv: ID3D11ShaderResourceView,
-    rtv_y: ID3D11RenderTargetView,
-    rtv_uv: ID3D11RenderTargetView,
 
     sampler: ID3D11SamplerState,
 }
 
+/// shaders and output views of the [NV12Backend] in use.
+enum NV12Writer {
+    Raster {
+        vs: VertexShader,
+        y_ps: PixelShader,
+        uv_ps: PixelShader,
+        rtv_y: ID3D11RenderTargetView,
+        rtv_uv: ID3D11RenderTargetView,
+    },
+    Compute(NV12ComputePass),
+}
+
 impl ConvertARGBToNV12 {
     /// create new instance of ConvertARGBToANV12 filter. After creation, filter takes RGB input from
     /// `input_tex` and writes to NV12 `out_tex`.
     pub fn new(input_tex: &Texture, out_tex: &Texture, device: &ID3D11Device4) -> Result<Self> {
+        return Self::new_with_backend(input_tex, out_tex, NV12Backend::Raster, device);
+    }
+
+    /// create new instance of ConvertARGBToNV12 filter which writes the output with given `backend`.
+    /// [NV12Backend::Compute] needs an output texture with unordered access.
+    pub fn new_with_backend(input_tex: &Texture, out_tex: &Texture, backend: NV12Backend, device: &ID3D11Device4) -> Result<Self> {
         Self::validate_input(input_tex)?;
         Self::validate_output(out_tex)?;
-        let y_ps = argb_to_y(device.clone())?;
-        let uv_ps = argb_to_uv(device.clone())?;
-        let vs = simple_vs(device.clone())?;
+        let color_space = Default::default();
+        let writer = match backend {
+            NV12Backend::Raster => NV12Writer::Raster {
+                vs: simple_vs(device.clone())?,
+                y_ps: argb_to_y(device.clone(), color_space)?,
+                uv_ps: argb_to_uv(device.clone(), color_space)?,
+                rtv_y: create_rtv(device, out_tex, DXGI_FORMAT_R8_UNORM)?,
+                rtv_uv: create_rtv(device, out_tex, DXGI_FORMAT_R8G8_UNORM)?,
+            },
+            NV12Backend::Compute => NV12Writer::Compute(NV12ComputePass::new(device, out_tex, color_space)?),
+        };
+        let dither = DitherPass::new(device)?;
 
         let srv = create_srv(device, input_tex, input_tex.desc().format.into())?;
         let sampler = create_tex_sampler(device)?;
-        let rtv_y = create_rtv(device, out_tex, DXGI_FORMAT_R8_UNORM)?;
-        let rtv_uv = create_rtv(device, out_tex, DXGI_FORMAT_R8G8_UNORM)?;
 
         return Ok(Self {
             device: device.clone(),
-            vs,
-            y_ps,
-            uv_ps,
+            writer,
+            color_space,
+            dither,
             _in_tex: input_tex.clone(),
             _out_tex: out_tex.clone(),
             srv,
-            rtv_y,
-            rtv_uv,
             sampler,
         });
     }
 
+    /// backend chosen at construction.
+    pub fn backend(&self) -> NV12Backend {
+        return match self.writer {
+            NV12Writer::Raster { .. } => NV12Backend::Raster,
+            NV12Writer::Compute(_) => NV12Backend::Compute,
+        };
+    }
+
+    /// current Y'CbCr matrix and range.
+    pub fn color_space(&self) -> (YuvMatrix, YuvRange) {
+        return self.color_space;
+    }
+
+    /// change Y'CbCr matrix and range. defaults to [YuvMatrix::Bt601] with [YuvRange::Limited].
+    pub fn set_color_space(&mut self, matrix: YuvMatrix, range: YuvRange) -> Result<()> {
+        if (matrix, range) == self.color_space {
+            return Ok(());
+        }
+        match &mut self.writer {
+            NV12Writer::Raster { y_ps, uv_ps, .. } => {
+                *y_ps = argb_to_y(self.device.clone(), (matrix, range))?;
+                *uv_ps = argb_to_uv(self.device.clone(), (matrix, range))?;
+            }
+            NV12Writer::Compute(pass) => pass.set_color_space(&self.device, (matrix, range))?,
+        }
+        self.color_space = (matrix, range);
+        return Ok(());
+    }
+
+    /// current dithering mode.
+    pub fn dither(&self) -> DitherMode {
+        return self.dither.mode();
+    }
+
+    /// set dithering applied when writing the 8 bit Y and UV planes. defaults to [DitherMode::None].
+    pub fn set_dither(&mut self, mode: DitherMode) {
+        self.dither.set_mode(mode);
+    }
+
     fn validate_input(tex: &Texture) -> Result<()> {
         let desc = tex.desc();
         match desc.format {
-            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::ARGB10UNorm => {
                 Ok(())
             }
             _ => {
-                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format).to_owned()))
+                Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR or ARGB10UNorm format found {:?}", desc.format).to_owned()))
             }
         }
     }
@@ -550,6 +819,12 @@ impl ConvertARGBToNV12 {
 
 impl DxFilter for ConvertARGBToNV12 {
     fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        let (vs, y_ps, uv_ps, rtv_y, rtv_uv) = match &self.writer {
+            NV12Writer::Raster { vs, y_ps, uv_ps, rtv_y, rtv_uv } => (vs, y_ps, uv_ps, rtv_y, rtv_uv),
+            NV12Writer::Compute(pass) => {
+                return pass.dispatch(ctx, &self.srv, &self.sampler, &self.dither, &self._out_tex);
+            }
+        };
         let out_desc = self._out_tex.desc();
         let vp_y = D3D11_VIEWPORT {
             TopLeftX: 0.0,
@@ -568,18 +843,19 @@ impl DxFilter for ConvertARGBToNV12 {
             MaxDepth: 0.0,
         };
 
+        self.dither.bind(ctx)?;
         unsafe {
             ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
-            ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
-            ctx.PSSetShader(self.y_ps.as_raw_ref(), Some(&[]));
+            ctx.VSSetShader(vs.as_raw_ref(), Some(&[]));
+            ctx.PSSetShader(y_ps.as_raw_ref(), Some(&[]));
             ctx.PSSetSamplers(0, Some(&[Some(self.sampler.clone())]));
             ctx.PSSetShaderResources(0, Some(&[Some(self.srv.clone())]));
             ctx.RSSetViewports(Some(&[vp_y]));
-            ctx.OMSetRenderTargets(Some(&[Some(self.rtv_y.clone())]), None);
+            ctx.OMSetRenderTargets(Some(&[Some(rtv_y.clone())]), None);
             ctx.Draw(4, 0);
-            ctx.PSSetShader(self.uv_ps.as_raw_ref(), Some(&[]));
+            ctx.PSSetShader(uv_ps.as_raw_ref(), Some(&[]));
             ctx.RSSetViewports(Some(&[vp_uv]));
-            ctx.OMSetRenderTargets(Some(&[Some(self.rtv_uv.clone())]), None);
+            ctx.OMSetRenderTargets(Some(&[Some(rtv_uv.clone())]), None);
             ctx.Draw(4, 0);
         }
         return Ok(());
@@ -594,27 +870,34 @@ impl DxFilter for ConvertARGBToNV12 {
 
     fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
         ConvertARGBToNV12::validate_output(tex)?;
+        match &mut self.writer {
+            NV12Writer::Raster { rtv_y, rtv_uv, .. } => {
+                *rtv_y = create_rtv(&self.device, tex, DXGI_FORMAT_R8_UNORM)?;
+                *rtv_uv = create_rtv(&self.device, tex, DXGI_FORMAT_R8G8_UNORM)?;
+            }
+            NV12Writer::Compute(pass) => pass.set_output_tex(&self.device, tex)?,
+        }
         self._out_tex = tex.clone();
-        self.rtv_y = create_rtv(&self.device, tex, DXGI_FORMAT_R8_UNORM)?;
-        self.rtv_uv = create_rtv(&self.device, tex, DXGI_FORMAT_R8G8_UNORM)?;
         return Ok(());
     }
 }
 
 
 /// Filter for simple scaling of [ARGBUNorm][ColorFormat::ARGB8UNorm] or [ABGRUNorm][ColorFormat::ABGR8UNorm] or [AYUV][ColorFormat::AYUV]
-/// formats.
+/// formats. Scales bilinearly unless another [ScaleAlgorithm] is chosen with [set_algorithm][ScaleARGBOrAYUV::set_algorithm].
+/// Optionally sharpens the scaled image in the same pass, see [new_with_sharpen][ScaleARGBOrAYUV::new_with_sharpen].
 pub struct ScaleARGBOrAYUV {
     device: ID3D11Device4,
     vs: VertexShader,
-    ps: PixelShader,
+    scale: ScalePass,
+    /// fused unsharp mask. replaces the pixel shader of `scale` when present.
+    sharpen: Option<SharpenPass>,
 
     _in_tex: Texture,
     _out_tex: Texture,
 
     srv: ID3D11ShaderResourceView,
     rtv: ID3D11RenderTargetView,
-    sampler: ID3D11SamplerState,
 }
 
 impl ScaleARGBOrAYUV {
@@ -624,25 +907,63 @@ impl ScaleARGBOrAYUV {
         Self::validate_input(input_tex)?;
         Self::validate_output(out_tex)?;
 
-        let ps = simple_ps(device.clone())?;
+        let scale = ScalePass::new(device, ScaleAlgorithm::Bilinear)?;
         let vs = simple_vs(device.clone())?;
 
-        let srv = create_srv(device, input_tex, DXGI_FORMAT_R8G8B8A8_UNORM)?;
-        let sampler = create_tex_sampler(device)?;
-        let rtv = create_rtv(device, out_tex, DXGI_FORMAT_R8G8B8A8_UNORM)?;
+        let srv = create_srv(device, input_tex, rgba_view_format(input_tex.desc().format))?;
+        let rtv = create_rtv(device, out_tex, rgba_view_format(out_tex.desc().format))?;
 
         return Ok(Self {
             device: device.clone(),
             vs,
-            ps,
+            scale,
+            sharpen: None,
             _in_tex: input_tex.clone(),
             _out_tex: out_tex.clone(),
             srv,
             rtv,
-            sampler,
         });
     }
 
+    /// create new instance of ScaleARGBOrAYUV filter which scales with given `algorithm`.
+    pub fn new_with_algorithm(input_tex: &Texture, out_tex: &Texture, algorithm: ScaleAlgorithm, device: &ID3D11Device4) -> Result<Self> {
+        let mut filter = Self::new(input_tex, out_tex, device)?;
+        filter.set_algorithm(algorithm)?;
+        return Ok(filter);
+    }
+
+    /// current scaling algorithm.
+    pub fn algorithm(&self) -> ScaleAlgorithm {
+        return self.scale.algorithm();
+    }
+
+    /// change scaling algorithm. when fused sharpening is enabled, only the sampler of the algorithm
+    /// is used.
+    pub fn set_algorithm(&mut self, algorithm: ScaleAlgorithm) -> Result<()> {
+        if algorithm != self.scale.algorithm() {
+            self.scale = ScalePass::new(&self.device, algorithm)?;
+        }
+        return Ok(());
+    }
+
+    /// create new instance of ScaleARGBOrAYUV filter that also sharpens the scaled image with the
+    /// given `strength`, without rendering an extra pass. See [SharpenARGB] for the meaning of `strength`.
+    pub fn new_with_sharpen(input_tex: &Texture, out_tex: &Texture, strength: f32, device: &ID3D11Device4) -> Result<Self> {
+        let mut filter = Self::new(input_tex, out_tex, device)?;
+        filter.set_sharpen(Some(strength))?;
+        return Ok(filter);
+    }
+
+    /// enable fused sharpening with given strength or disable it with `None`.
+    pub fn set_sharpen(&mut self, strength: Option<f32>) -> Result<()> {
+        match (strength, &mut self.sharpen) {
+            (Some(strength), Some(sharpen)) => sharpen.set_strength(strength)?,
+            (Some(strength), None) => self.sharpen = Some(SharpenPass::new(&self.device, strength)?),
+            (None, _) => self.sharpen = None,
+        }
+        return Ok(());
+    }
+
 
     fn validate_input(tex: &Texture) -> Result<()> {
         let desc = tex.desc();
@@ -682,8 +1003,12 @@ impl DxFilter for ScaleARGBOrAYUV {
         unsafe {
             ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
             ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
-            ctx.PSSetShader(self.ps.as_raw_ref(), Some(&[]));
-            ctx.PSSetSamplers(0, Some(&[Some(self.sampler.clone())]));
+            if let Some(sharpen) = &self.sharpen {
+                sharpen.bind(ctx, out_desc.width, out_desc.height)?;
+            } else {
+                ctx.PSSetShader(self.scale.ps().as_raw_ref(), Some(&[]));
+            }
+            ctx.PSSetSamplers(0, Some(&[Some(self.scale.sampler().clone())]));
             ctx.PSSetShaderResources(0, Some(&[Some(self.srv.clone())]));
             ctx.RSSetViewports(Some(&[vp]));
             ctx.OMSetRenderTargets(Some(&[Some(self.rtv.clone())]), None);
@@ -695,14 +1020,14 @@ impl DxFilter for ScaleARGBOrAYUV {
     fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
         ScaleARGBOrAYUV::validate_input(tex)?;
         self._in_tex = tex.clone();
-        self.srv = create_srv(&self.device, tex, tex.desc().format.into())?;
+        self.srv = create_srv(&self.device, tex, rgba_view_format(tex.desc().format))?;
         return Ok(());
     }
 
     fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
         ScaleARGBOrAYUV::validate_output(tex)?;
         self._out_tex = tex.clone();
-        self.rtv = create_rtv(&self.device, tex, tex.desc().format.into())?;
+        self.rtv = create_rtv(&self.device, tex, rgba_view_format(tex.desc().format))?;
         return Ok(());
     }
 }
@@ -826,10 +1151,12 @@ impl DxFilter for ConvertARGBToYUV444 {
 ///
 /// The active pixel shader is selected from the input format at construction and on
 /// [`set_input_tex`][DxFilter::set_input_tex]. The filter also scales automatically
-/// based on the input and output texture dimensions.
+/// based on the input and output texture dimensions. Use [`set_dither`][ConvertHighBitToARGB8::set_dither]
+/// to dither instead of plainly quantizing to 8 bits.
 pub struct ConvertHighBitToARGB8 {
     device: ID3D11Device4,
     vs: VertexShader,
+    dither: DitherPass,
 
     r10_ps: PixelShader,
     r16f_ps: PixelShader,
@@ -855,6 +1182,7 @@ impl ConvertHighBitToARGB8 {
         let r10_ps = r10_to_argb8(device.clone())?;
         let r16f_ps = r16f_to_argb8(device.clone())?;
         let vs = simple_vs(device.clone())?;
+        let dither = DitherPass::new(device)?;
 
         let is_r16f = match input_tex.desc().format {
             ColorFormat::ARGB16Float => true,
@@ -865,11 +1193,12 @@ impl ConvertHighBitToARGB8 {
 
         let srv = create_srv(device, input_tex, input_tex.desc().format.into())?;
         let sampler = create_tex_sampler(device)?;
-        let rtv = create_rtv(device, out_tex, DXGI_FORMAT_R8G8B8A8_UNORM)?;
+        let rtv = create_rtv(device, out_tex, rgba_view_format(out_tex.desc().format))?;
 
         return Ok(Self {
             device: device.clone(),
             vs,
+            dither,
             r10_ps,
             r16f_ps,
             is_r16f,
@@ -881,6 +1210,16 @@ impl ConvertHighBitToARGB8 {
         });
     }
 
+    /// current dithering mode.
+    pub fn dither(&self) -> DitherMode {
+        return self.dither.mode();
+    }
+
+    /// set dithering applied when writing the 8 bit output. defaults to [DitherMode::None].
+    pub fn set_dither(&mut self, mode: DitherMode) {
+        self.dither.set_mode(mode);
+    }
+
     fn validate_input(tex: &Texture) -> Result<()> {
         let desc = tex.desc();
         match desc.format {
@@ -917,6 +1256,7 @@ impl DxFilter for ConvertHighBitToARGB8 {
             MaxDepth: 0.0,
         };
         let ps = if self.is_r16f { &self.r16f_ps } else { &self.r10_ps };
+        self.dither.bind(ctx)?;
         unsafe {
             ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
             ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
@@ -945,11 +1285,25 @@ impl DxFilter for ConvertHighBitToARGB8 {
     fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
         ConvertHighBitToARGB8::validate_output(tex)?;
         self._out_tex = tex.clone();
-        self.rtv = create_rtv(&self.device, tex, DXGI_FORMAT_R8G8B8A8_UNORM)?;
+        self.rtv = create_rtv(&self.device, tex, rgba_view_format(tex.desc().format))?;
         return Ok(());
     }
 }
 
+/// true when both descriptions have the same size and format.
+pub(crate) fn same_desc(a: &TextureDesc, b: &TextureDesc) -> bool {
+    a.width == b.width && a.height == b.height && DXGI_FORMAT::from(a.format) == DXGI_FORMAT::from(b.format)
+}
+
+/// format for viewing a texture of given color format as RGBA. AYUV textures only allow
+/// `R8G8B8A8_UNORM` views; other formats are viewed as they are.
+fn rgba_view_format(format: ColorFormat) -> DXGI_FORMAT {
+    match format {
+        ColorFormat::AYUV => DXGI_FORMAT_R8G8B8A8_UNORM,
+        f => f.into(),
+    }
+}
+
 fn create_srv(dev: &ID3D11Device4, tex: &Texture, format: DXGI_FORMAT) -> Result<ID3D11ShaderResourceView> {
     let mut srv_desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
         Format: format,
@@ -986,9 +1340,34 @@ fn create_rtv(dev: &ID3D11Device4, tex: &Texture, format: DXGI_FORMAT) -> Result
     }
 }
 
+/// create a 2d unordered access view of `tex`. for NV12 textures `R8_UNORM` views the luma plane
+/// and `R8G8_UNORM` the chroma plane.
+fn create_uav(dev: &ID3D11Device4, tex: &Texture, format: DXGI_FORMAT) -> Result<ID3D11UnorderedAccessView> {
+    let mut uav_desc = D3D11_UNORDERED_ACCESS_VIEW_DESC {
+        Format: format,
+        ViewDimension: D3D11_UAV_DIMENSION_TEXTURE2D,
+        Anonymous: Default::default(),
+    };
+    uav_desc.Anonymous.Texture2D = D3D11_TEX2D_UAV {
+        MipSlice: 0,
+    };
+    let mut uav = None;
+
+    if let Err(e) = unsafe { dev.CreateUnorderedAccessView(tex.as_raw_ref(), Some(&uav_desc), Some(&mut uav)) } {
+        Err(DxFilterErr::Unknown(format!("failed to create unordered access view. is the texture created with D3D11_BIND_UNORDERED_ACCESS? {:?}", e)))
+    } else {
+        Ok(uav.unwrap())
+    }
+}
+
 fn create_tex_sampler(dev: &ID3D11Device4) -> Result<ID3D11SamplerState> {
+    create_sampler(dev, D3D11_FILTER_MIN_MAG_MIP_LINEAR)
+}
+
+/// create a clamping sampler with given filter.
+fn create_sampler(dev: &ID3D11Device4, filter: D3D11_FILTER) -> Result<ID3D11SamplerState> {
     let sampler_desc = D3D11_SAMPLER_DESC {
-        Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+        Filter: filter,
         AddressU: D3D11_TEXTURE_ADDRESS_CLAMP,
         AddressV: D3D11_TEXTURE_ADDRESS_CLAMP,
         AddressW: D3D11_TEXTURE_ADDRESS_CLAMP,
@@ -1007,4 +1386,72 @@ fn create_tex_sampler(dev: &ID3D11Device4) -> Result<ID3D11SamplerState> {
     } else {
         Ok(sampler_state.unwrap())
     }
-}
\ No newline at end of file
+}
+/// texture holding the previous input frame.
+fn create_history(device: &ID3D11Device4, desc: TextureDesc) -> Result<(Texture, ID3D11ShaderResourceView)> {
+    let tex = create_input_texture(device, desc, None)?;
+    let srv = create_srv(device, &tex, rgba_view_format(desc.format))?;
+    Ok((tex, srv))
+}
+
+fn structured_buffer_desc(elements: u32, usage: D3D11_USAGE, bind_flags: u32, cpu_access_flags: u32) -> D3D11_BUFFER_DESC {
+    D3D11_BUFFER_DESC {
+        ByteWidth: elements * size_of::<u32>() as u32,
+        Usage: usage,
+        BindFlags: bind_flags,
+        CPUAccessFlags: cpu_access_flags,
+        MiscFlags: D3D11_RESOURCE_MISC_BUFFER_STRUCTURED.0 as _,
+        StructureByteStride: size_of::<u32>() as _,
+    }
+}
+
+/// create a `RWStructuredBuffer<uint>` with `elements` entries and its unordered access view.
+fn create_uav_buffer(dev: &ID3D11Device4, elements: u32) -> Result<(ID3D11Buffer, ID3D11UnorderedAccessView)> {
+    let desc = structured_buffer_desc(elements, D3D11_USAGE_DEFAULT, D3D11_BIND_UNORDERED_ACCESS.0 as _, 0);
+    let mut buffer = None;
+    if let Err(e) = unsafe { dev.CreateBuffer(&desc, None, Some(&mut buffer)) } {
+        return Err(DxFilterErr::Unknown(format!("failed to create uav buffer. {:?}", e)));
+    }
+    let buffer = buffer.unwrap();
+
+    let mut uav_desc = D3D11_UNORDERED_ACCESS_VIEW_DESC {
+        Format: DXGI_FORMAT_UNKNOWN,
+        ViewDimension: D3D11_UAV_DIMENSION_BUFFER,
+        Anonymous: Default::default(),
+    };
+    uav_desc.Anonymous.Buffer = D3D11_BUFFER_UAV {
+        FirstElement: 0,
+        NumElements: elements,
+        Flags: 0,
+    };
+    let mut uav = None;
+    if let Err(e) = unsafe { dev.CreateUnorderedAccessView(&buffer, Some(&uav_desc), Some(&mut uav)) } {
+        Err(DxFilterErr::Unknown(format!("failed to create unordered access view. {:?}", e)))
+    } else {
+        Ok((buffer, uav.unwrap()))
+    }
+}
+
+/// create a cpu readable copy target for a buffer created with [create_uav_buffer].
+fn create_staging_buffer(dev: &ID3D11Device4, elements: u32) -> Result<ID3D11Buffer> {
+    let desc = structured_buffer_desc(elements, D3D11_USAGE_STAGING, 0, D3D11_CPU_ACCESS_READ.0 as _);
+    let mut buffer = None;
+    if let Err(e) = unsafe { dev.CreateBuffer(&desc, None, Some(&mut buffer)) } {
+        Err(DxFilterErr::Unknown(format!("failed to create staging buffer. {:?}", e)))
+    } else {
+        Ok(buffer.unwrap())
+    }
+}
+
+/// copy the contents of a staging buffer into `out`. blocks until the gpu has finished writing it.
+fn read_staging_buffer(ctx: &ID3D11DeviceContext4, buffer: &ID3D11Buffer, out: &mut [u32]) -> Result<()> {
+    let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+    if let Err(e) = unsafe { ctx.Map(buffer, 0, D3D11_MAP_READ, 0, Some(&mut mapped)) } {
+        return Err(DxFilterErr::Unknown(format!("failed to map staging buffer. {:?}", e)));
+    }
+    unsafe {
+        copy_nonoverlapping(mapped.pData as *const u32, out.as_mut_ptr(), out.len());
+        ctx.Unmap(buffer, 0);
+    }
+    Ok(())
+}
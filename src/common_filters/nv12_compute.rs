@@ -0,0 +1,83 @@
+use win_desktop_duplication::texture::Texture;
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device4, ID3D11DeviceContext4, ID3D11SamplerState, ID3D11ShaderResourceView, ID3D11UnorderedAccessView};
+use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_R8_UNORM, DXGI_FORMAT_R8G8_UNORM};
+
+use crate::{generate_shader_variants, Result};
+use crate::color::{YuvMatrix, YuvRange};
+use crate::shader::ComputeShader;
+
+use super::{create_uav, DitherPass};
+
+generate_shader_variants!(argb_to_nv12_cs cs {
+    src_file: "src/common_filters/shaders/argb_to_nv12_cs.hlsl",
+    entry_point: "main",
+    target: "cs_5_0",
+    variants: {
+        YUV_MATRIX: YuvMatrix { Bt601 => "601", Bt709 => "709", Bt2020 => "2020" },
+        YUV_FULL_RANGE: YuvRange { Limited => "0", Full => "1" },
+    }
+});
+
+/// How [ConvertARGBToNV12][super::ConvertARGBToNV12] writes its output.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NV12Backend {
+    /// two draws, one per plane. each draw samples the whole input. output needs
+    /// `D3D11_BIND_RENDER_TARGET`.
+    #[default]
+    Raster,
+    /// a single compute dispatch writing both planes through UAVs. every 2x2 block of the output
+    /// is converted from one set of source samples. output needs `D3D11_BIND_UNORDERED_ACCESS`,
+    /// see [create_uav_output_texture][crate::utils::create_uav_output_texture].
+    Compute,
+}
+
+/// compute shader and plane UAVs for [NV12Backend::Compute].
+pub(super) struct NV12ComputePass {
+    cs: ComputeShader,
+    uav_y: ID3D11UnorderedAccessView,
+    uav_uv: ID3D11UnorderedAccessView,
+}
+
+impl NV12ComputePass {
+    pub(super) fn new(device: &ID3D11Device4, out_tex: &Texture, color_space: (YuvMatrix, YuvRange)) -> Result<Self> {
+        return Ok(Self {
+            cs: argb_to_nv12_cs(device.clone(), color_space)?,
+            uav_y: create_uav(device, out_tex, DXGI_FORMAT_R8_UNORM)?,
+            uav_uv: create_uav(device, out_tex, DXGI_FORMAT_R8G8_UNORM)?,
+        });
+    }
+
+    pub(super) fn set_color_space(&mut self, device: &ID3D11Device4, color_space: (YuvMatrix, YuvRange)) -> Result<()> {
+        self.cs = argb_to_nv12_cs(device.clone(), color_space)?;
+        return Ok(());
+    }
+
+    pub(super) fn set_output_tex(&mut self, device: &ID3D11Device4, tex: &Texture) -> Result<()> {
+        self.uav_y = create_uav(device, tex, DXGI_FORMAT_R8_UNORM)?;
+        self.uav_uv = create_uav(device, tex, DXGI_FORMAT_R8G8_UNORM)?;
+        return Ok(());
+    }
+
+    pub(super) fn dispatch(&self, ctx: &ID3D11DeviceContext4, srv: &ID3D11ShaderResourceView, sampler: &ID3D11SamplerState,
+                           dither: &DitherPass, out_tex: &Texture) -> Result<()> {
+        let desc = out_tex.desc();
+        // one thread per 2x2 block, 8x8 threads per group
+        let (blocks_x, blocks_y) = (desc.width / 2, desc.height / 2);
+
+        dither.bind_cs(ctx)?;
+        unsafe {
+            ctx.CSSetShader(self.cs.as_raw_ref(), None);
+            ctx.CSSetSamplers(0, Some(&[Some(sampler.clone())]));
+            ctx.CSSetShaderResources(0, Some(&[Some(srv.clone())]));
+            let uavs = [Some(self.uav_y.clone()), Some(self.uav_uv.clone())];
+            ctx.CSSetUnorderedAccessViews(0, uavs.len() as _, Some(uavs.as_ptr()), None);
+            ctx.Dispatch((blocks_x + 7) / 8, (blocks_y + 7) / 8, 1);
+
+            // unbind so the output can be used as a shader resource or render target
+            ctx.CSSetShaderResources(0, Some(&[None]));
+            let no_uavs: [Option<ID3D11UnorderedAccessView>; 2] = [None, None];
+            ctx.CSSetUnorderedAccessViews(0, no_uavs.len() as _, Some(no_uavs.as_ptr()), None);
+        }
+        return Ok(());
+    }
+}
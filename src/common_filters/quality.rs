@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+
+use win_desktop_duplication::texture::{ColorFormat, Texture};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Buffer, ID3D11Device4, ID3D11DeviceContext4, ID3D11ShaderResourceView, ID3D11UnorderedAccessView};
+use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_R8_UNORM, DXGI_FORMAT_R8G8_UNORM};
+
+use crate::Result;
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{ComputeShader, ShaderStage};
+use crate::metrics::{aggregate_blocks, BlockStats, QualityMetrics, SSIM_BLOCK_SIZE};
+
+use super::{create_srv, create_staging_buffer, create_uav_buffer, read_staging_buffer, rgba_view_format, same_desc};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::TextureComparator;
+    use crate::metrics::compare_frames;
+    use crate::utils::create_input_texture;
+
+    #[test]
+    fn test_gpu_matches_cpu() {
+        let (device, ctx) = build_device_and_ctx();
+
+        let (width, height) = (20u32, 12u32);
+        let reference: Vec<u8> = (0..width * height * 4).map(|i| (i * 13 % 251) as u8).collect();
+        let distorted: Vec<u8> = reference.iter().enumerate()
+            .map(|(i, &v)| if i % 3 == 0 { v.saturating_add(9) } else { v }).collect();
+
+        let desc = TextureDesc { width, height, format: ColorFormat::ARGB8UNorm };
+        let ref_tex = create_input_texture(&device, desc, Some(reference.clone())).unwrap();
+        let dist_tex = create_input_texture(&device, desc, Some(distorted.clone())).unwrap();
+
+        let comparator = TextureComparator::new(&ref_tex, &dist_tex, &device).unwrap();
+        let gpu = comparator.compare(&ctx).unwrap();
+        let cpu = compare_frames(ColorFormat::ARGB8UNorm, width, height, &reference, &distorted).unwrap();
+        for i in 0..3 {
+            assert!((gpu.psnr[i] - cpu.psnr[i]).abs() < 1e-9, "{:?} {:?}", gpu, cpu);
+            assert!((gpu.ssim[i] - cpu.ssim[i]).abs() < 1e-4, "{:?} {:?}", gpu, cpu);
+        }
+    }
+}
+
+generate_shader!(quality_metrics_cs cs {
+    src_file: "src/common_filters/shaders/quality_metrics_cs.hlsl",
+    entry_point: "main",
+    target: "cs_5_0"
+});
+
+/// layout of `QualityConstants` cbuffer in quality_metrics_cs.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct QualityConstants {
+    luma_size: [u32; 2],
+    block_count: [u32; 2],
+    format: u32,
+    _padding: [u32; 3],
+}
+
+/// words written per block: sample count, squared error and ssim.
+const BLOCK_WORDS: u32 = 3;
+
+/// shader resource views of one frame. `chroma` is only used for NV12.
+struct FrameViews {
+    main: ID3D11ShaderResourceView,
+    chroma: Option<ID3D11ShaderResourceView>,
+}
+
+impl FrameViews {
+    fn new(device: &ID3D11Device4, tex: &Texture) -> Result<Self> {
+        let format = tex.desc().format;
+        if let ColorFormat::NV12 = format {
+            Ok(Self {
+                main: create_srv(device, tex, DXGI_FORMAT_R8_UNORM)?,
+                chroma: Some(create_srv(device, tex, DXGI_FORMAT_R8G8_UNORM)?),
+            })
+        } else {
+            Ok(Self {
+                main: create_srv(device, tex, rgba_view_format(format))?,
+                chroma: None,
+            })
+        }
+    }
+}
+
+/// Computes [QualityMetrics] between a reference and a distorted texture on the gpu, e.g. the
+/// source capture and the output of a scaler or colour conversion. Both textures must have the
+/// same size and one of the formats [ARGB8UNorm][ColorFormat::ARGB8UNorm],
+/// [ABGR8UNorm][ColorFormat::ABGR8UNorm], [AYUV][ColorFormat::AYUV] or [NV12][ColorFormat::NV12],
+/// and must be created with `D3D11_BIND_SHADER_RESOURCE`.
+///
+/// Every 8x8 block is reduced in a compute shader and only per block results are read back, see
+/// [metrics][crate::metrics] for the cpu implementation the results match.
+pub struct TextureComparator {
+    device: ID3D11Device4,
+    cs: ComputeShader,
+    cb: ConstantBuffer<QualityConstants>,
+
+    _ref_tex: Texture,
+    _dist_tex: Texture,
+    ref_views: FrameViews,
+    dist_views: FrameViews,
+
+    block_count: [u32; 2],
+    block_buf: ID3D11Buffer,
+    block_uav: ID3D11UnorderedAccessView,
+    block_staging: ID3D11Buffer,
+    /// scratch space for reading back block results.
+    block_words: RefCell<Vec<u32>>,
+}
+
+impl TextureComparator {
+    /// create new instance of TextureComparator comparing `distorted_tex` against `reference_tex`.
+    pub fn new(reference_tex: &Texture, distorted_tex: &Texture, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_textures(reference_tex, distorted_tex)?;
+
+        let cs = quality_metrics_cs(device.clone())?;
+        let cb = ConstantBuffer::new(device, &QualityConstants::default())?;
+        let desc = reference_tex.desc();
+        let block_count = [
+            (desc.width + SSIM_BLOCK_SIZE - 1) / SSIM_BLOCK_SIZE,
+            (desc.height + SSIM_BLOCK_SIZE - 1) / SSIM_BLOCK_SIZE,
+        ];
+        let words = block_count[0] * block_count[1] * 3 * BLOCK_WORDS;
+        let (block_buf, block_uav) = create_uav_buffer(device, words)?;
+
+        return Ok(Self {
+            device: device.clone(),
+            cs,
+            cb,
+            _ref_tex: reference_tex.clone(),
+            _dist_tex: distorted_tex.clone(),
+            ref_views: FrameViews::new(device, reference_tex)?,
+            dist_views: FrameViews::new(device, distorted_tex)?,
+            block_count,
+            block_buf,
+            block_uav,
+            block_staging: create_staging_buffer(device, words)?,
+            block_words: RefCell::new(vec![0; words as usize]),
+        });
+    }
+
+    /// compute PSNR and SSIM of the current texture contents. waits for the gpu to finish.
+    pub fn compare(&self, ctx: &ID3D11DeviceContext4) -> Result<QualityMetrics> {
+        let desc = self._ref_tex.desc();
+        let constants = QualityConstants {
+            luma_size: [desc.width, desc.height],
+            block_count: self.block_count,
+            format: match desc.format {
+                ColorFormat::AYUV => 1,
+                ColorFormat::NV12 => 2,
+                _ => 0,
+            },
+            _padding: [0; 3],
+        };
+        self.cb.update(ctx, &constants)?;
+
+        unsafe {
+            ctx.CSSetShader(self.cs.as_raw_ref(), None);
+            self.cb.bind(ctx, ShaderStage::Compute, 0);
+            ctx.CSSetShaderResources(0, Some(&[
+                Some(self.ref_views.main.clone()),
+                Some(self.dist_views.main.clone()),
+                self.ref_views.chroma.clone(),
+                self.dist_views.chroma.clone(),
+            ]));
+            let uavs = [Some(self.block_uav.clone())];
+            ctx.CSSetUnorderedAccessViews(0, uavs.len() as _, Some(uavs.as_ptr()), None);
+            ctx.Dispatch(self.block_count[0], self.block_count[1], 3);
+
+            ctx.CSSetShaderResources(0, Some(&[None, None, None, None]));
+            let no_uavs: [Option<ID3D11UnorderedAccessView>; 1] = [None];
+            ctx.CSSetUnorderedAccessViews(0, no_uavs.len() as _, Some(no_uavs.as_ptr()), None);
+
+            ctx.CopyResource(&self.block_staging, &self.block_buf);
+        }
+
+        let mut words = self.block_words.borrow_mut();
+        read_staging_buffer(ctx, &self.block_staging, &mut words)?;
+        let blocks: Vec<BlockStats> = words.chunks_exact(BLOCK_WORDS as usize)
+            .map(|w| BlockStats { count: w[0], sse: w[1] as u64, ssim: f32::from_bits(w[2]) as f64 })
+            .collect();
+        let per_plane = (self.block_count[0] * self.block_count[1]) as usize;
+        let (y, rest) = blocks.split_at(per_plane);
+        let (u, v) = rest.split_at(per_plane);
+        return Ok(aggregate_blocks([y, u, v]));
+    }
+
+    /// configure the comparator to use different textures of the same size and format.
+    pub fn set_textures(&mut self, reference_tex: &Texture, distorted_tex: &Texture) -> Result<()> {
+        Self::validate_textures(reference_tex, distorted_tex)?;
+        if !same_desc(&reference_tex.desc(), &self._ref_tex.desc()) {
+            return Err(DxFilterErr::BadParam("textures must keep the size and format the comparator was created with".to_owned()));
+        }
+        self.ref_views = FrameViews::new(&self.device, reference_tex)?;
+        self.dist_views = FrameViews::new(&self.device, distorted_tex)?;
+        self._ref_tex = reference_tex.clone();
+        self._dist_tex = distorted_tex.clone();
+        return Ok(());
+    }
+
+    fn validate_textures(reference: &Texture, distorted: &Texture) -> Result<()> {
+        let desc = reference.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::AYUV | ColorFormat::NV12 => {}
+            _ => {
+                return Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR, AYUV or NV12 format found {:?}", desc.format)));
+            }
+        }
+        let other = distorted.desc();
+        if !same_desc(&desc, &other) {
+            return Err(DxFilterErr::BadParam(format!("expected textures of same format and size, found {:?} {}x{} and {:?} {}x{}",
+                                                     desc.format, desc.width, desc.height, other.format, other.width, other.height)));
+        }
+        Ok(())
+    }
+}
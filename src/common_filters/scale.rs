@@ -0,0 +1,62 @@
+use windows::Win32::Graphics::Direct3D11::{D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_FILTER_MIN_MAG_MIP_POINT, ID3D11Device4, ID3D11SamplerState};
+
+use crate::Result;
+use crate::shader::PixelShader;
+
+use super::{create_sampler, simple_ps};
+
+generate_shader!(bicubic_ps ps {
+    src_file: "src/common_filters/shaders/bicubic_ps.hlsl",
+    entry_point: "main",
+    target: "ps_5_0"
+});
+
+/// Resampling used by [ScaleARGBOrAYUV][super::ScaleARGBOrAYUV] and the rungs of
+/// [SimulcastNV12][super::SimulcastNV12].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ScaleAlgorithm {
+    /// nearest neighbour. fastest, keeps hard pixel edges e.g. for integer upscaling.
+    Point,
+    /// bilinear filtering. blurs when downscaling by more than 2x in one step.
+    #[default]
+    Bilinear,
+    /// Catmull-Rom bicubic. sharper than bilinear for moderate scale factors.
+    Bicubic,
+}
+
+/// pixel shader and sampler implementing a [ScaleAlgorithm].
+pub(super) struct ScalePass {
+    algorithm: ScaleAlgorithm,
+    ps: PixelShader,
+    sampler: ID3D11SamplerState,
+}
+
+impl ScalePass {
+    pub(super) fn new(device: &ID3D11Device4, algorithm: ScaleAlgorithm) -> Result<Self> {
+        let ps = match algorithm {
+            ScaleAlgorithm::Point | ScaleAlgorithm::Bilinear => simple_ps(device.clone())?,
+            ScaleAlgorithm::Bicubic => bicubic_ps(device.clone())?,
+        };
+        let filter = match algorithm {
+            ScaleAlgorithm::Point => D3D11_FILTER_MIN_MAG_MIP_POINT,
+            ScaleAlgorithm::Bilinear | ScaleAlgorithm::Bicubic => D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+        };
+        return Ok(Self {
+            algorithm,
+            ps,
+            sampler: create_sampler(device, filter)?,
+        });
+    }
+
+    pub(super) fn algorithm(&self) -> ScaleAlgorithm {
+        return self.algorithm;
+    }
+
+    pub(super) fn ps(&self) -> &PixelShader {
+        return &self.ps;
+    }
+
+    pub(super) fn sampler(&self) -> &ID3D11SamplerState {
+        return &self.sampler;
+    }
+}
@@ -0,0 +1,42 @@
+//--------------------------------------------------------------------------------------
+// argb_to_nv12_cs.hlsl
+//
+// Writes both NV12 planes in one dispatch. Every thread handles a 2x2 block of luma
+// samples: the four source samples are fetched once, converted to Y and averaged for the
+// shared UV sample, which is what the bilinear fetch of the raster UV pass computes when
+// input and output have the same size.
+//--------------------------------------------------------------------------------------
+Texture2D txInput : register(t0);
+
+SamplerState GenericSampler : register(s0);
+
+RWTexture2D<unorm float> OutputY : register(u0);
+RWTexture2D<unorm float2> OutputUV : register(u1);
+
+#include "dither.hlsli"
+#include "yuv.hlsli"
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+	uint2 uvSize;
+	OutputUV.GetDimensions(uvSize.x, uvSize.y);
+	if (any(id.xy >= uvSize))
+	{
+		return;
+	}
+	float2 ySize = uvSize * 2;
+
+	float3 sum = 0.0f;
+	[unroll]
+	for (uint i = 0; i < 4; i++)
+	{
+		uint2 pos = id.xy * 2 + uint2(i & 1, i >> 1);
+		float3 InputColor = txInput.SampleLevel(GenericSampler, (pos + 0.5f) / ySize, 0).rgb;
+
+		OutputY[pos] = saturate(Dither(RgbToY(InputColor), pos + 0.5f));
+		sum += InputColor;
+	}
+
+	OutputUV[id.xy] = saturate(Dither(RgbToUV(sum * 0.25f), id.xy + 0.5f));
+}
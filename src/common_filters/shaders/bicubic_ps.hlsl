@@ -0,0 +1,53 @@
+//--------------------------------------------------------------------------------------
+// bicubic_ps.hlsl
+//
+// Catmull-Rom bicubic resampling from 4x4 source texels. Sharper than bilinear when
+// scaling by less than 2x. Texels are fetched with Load and clamped to the edge, so no
+// sampler is needed.
+//--------------------------------------------------------------------------------------
+Texture2D txInput : register(t0);
+
+struct PS_INPUT
+{
+    float4 Pos : SV_POSITION;
+    float2 Tex : TEXCOORD;
+};
+
+float4 CatmullRom(float f)
+{
+    return float4(f * (-0.5f + f * (1.0f - 0.5f * f)),
+                  1.0f + f * f * (-2.5f + 1.5f * f),
+                  f * (0.5f + f * (2.0f - 1.5f * f)),
+                  f * f * (-0.5f + 0.5f * f));
+}
+
+//--------------------------------------------------------------------------------------
+// Pixel Shader
+//--------------------------------------------------------------------------------------
+float4 main(PS_INPUT input) : SV_Target
+{
+    uint width, height;
+    txInput.GetDimensions(width, height);
+    int2 maxPos = int2(width, height) - 1;
+
+    float2 pos = input.Tex * float2(width, height) - 0.5f;
+    float2 base = floor(pos);
+    float2 f = pos - base;
+    float4 wx = CatmullRom(f.x);
+    float4 wy = CatmullRom(f.y);
+
+    float4 sum = 0.0f;
+    [unroll]
+    for (int j = 0; j < 4; j++)
+    {
+        [unroll]
+        for (int i = 0; i < 4; i++)
+        {
+            int2 p = clamp(int2(base) + int2(i - 1, j - 1), int2(0, 0), maxPos);
+            sum += txInput.Load(int3(p, 0)) * wx[i] * wy[j];
+        }
+    }
+
+    // the negative lobes can overshoot
+    return saturate(sum);
+}
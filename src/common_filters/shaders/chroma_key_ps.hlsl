@@ -0,0 +1,55 @@
+//--------------------------------------------------------------------------------------
+// chroma_key_ps.hlsl
+//
+// Turns pixels close to a key colour into transparency. Distance to the key is measured
+// in the CbCr plane (BT.601) so that shading of the backdrop, which mostly changes luma,
+// does not matter. Pixels within Tolerance are fully transparent and alpha ramps up to
+// opaque over Softness. Spill suppression removes part of the key's chroma from the
+// remaining pixels (green fringes on hair and edges) while preserving their luma.
+// Output is premultiplied alpha, ready to be composited with ONE / INV_SRC_ALPHA blending.
+//--------------------------------------------------------------------------------------
+Texture2D txInput : register(t0);
+
+SamplerState GenericSampler : register(s0);
+
+cbuffer ChromaKeyConstants : register(b0)
+{
+    float2 KeyChroma;
+    float Tolerance;
+    float Softness;
+    float SpillSuppression;
+    float3 Padding;
+};
+
+struct PS_INPUT
+{
+    float4 Pos : SV_POSITION;
+    float2 Tex : TEXCOORD;
+};
+
+float2 ToChroma(float3 c)
+{
+    return float2(-0.168736f * c.r - 0.331264f * c.g + 0.5f * c.b,
+                  0.5f * c.r - 0.418688f * c.g - 0.081312f * c.b);
+}
+
+//--------------------------------------------------------------------------------------
+// Pixel Shader
+//--------------------------------------------------------------------------------------
+float4 main(PS_INPUT input) : SV_Target
+{
+    float4 InputColor = txInput.Sample(GenericSampler, input.Tex);
+
+    float2 chroma = ToChroma(InputColor.rgb);
+    float dist = distance(chroma, KeyChroma);
+    float alpha = saturate((dist - Tolerance) / max(Softness, 1e-5f)) * InputColor.a;
+
+    // remove the part of the chroma pointing towards the key colour
+    float2 keyDir = KeyChroma / max(length(KeyChroma), 1e-5f);
+    float2 delta = -keyDir * max(dot(chroma, keyDir), 0.0f) * SpillSuppression;
+    float3 rgb = InputColor.rgb + float3(1.402f * delta.y,
+                                         -0.344136f * delta.x - 0.714136f * delta.y,
+                                         1.772f * delta.x);
+
+    return float4(saturate(rgb) * alpha, alpha);
+}
@@ -0,0 +1,50 @@
+//--------------------------------------------------------------------------------------
+// color_adjust_ps.hlsl
+//
+// Picture controls applied in gamma-encoded RGB, in the order hue rotation ->
+// saturation -> contrast/brightness -> gamma. Hue rotation is a 3x3 rotation around
+// the grey axis computed on the CPU and passed as three rows. Everything lives in a
+// constant buffer so the controls can change every frame.
+//--------------------------------------------------------------------------------------
+Texture2D txInput : register(t0);
+
+SamplerState GenericSampler : register(s0);
+
+cbuffer ColorAdjustConstants : register(b0)
+{
+    float4 HueRow0;
+    float4 HueRow1;
+    float4 HueRow2;
+    float Brightness;
+    float Contrast;
+    float Saturation;
+    float InvGamma;
+};
+
+struct PS_INPUT
+{
+    float4 Pos : SV_POSITION;
+    float2 Tex : TEXCOORD;
+};
+
+//--------------------------------------------------------------------------------------
+// Pixel Shader
+//--------------------------------------------------------------------------------------
+float4 main(PS_INPUT input) : SV_Target
+{
+    float4 InputColor = txInput.Sample(GenericSampler, input.Tex);
+
+    float3 c = float3(dot(HueRow0.rgb, InputColor.rgb),
+                      dot(HueRow1.rgb, InputColor.rgb),
+                      dot(HueRow2.rgb, InputColor.rgb));
+
+    // BT.709 luma weights
+    float luma = dot(c, float3(0.2126f, 0.7152f, 0.0722f));
+    c = lerp(luma.xxx, c, Saturation);
+
+    c = (c - 0.5f) * Contrast + 0.5f + Brightness;
+
+    c = pow(saturate(c), InvGamma);
+
+    return float4(c, InputColor.a);
+}
@@ -0,0 +1,75 @@
+//--------------------------------------------------------------------------------------
+// deinterlace_ps.hlsl
+//
+// Rebuilds a progressive frame from the lines of one field. Lines belonging to the
+// kept field (KeepOddLines selects which) are copied; the other lines are rebuilt:
+//   Mode 0 (bob):   average of the kept lines above and below.
+//   Mode 1 (blend): every line is blended with its neighbours (1/4, 1/2, 1/4),
+//                   removing combing at the cost of vertical resolution.
+//   Mode 2 (motion adaptive): static pixels are woven from the other field of the
+//                   current frame, moving pixels fall back to bob. Motion is measured
+//                   against the previous frame on the missing line and its neighbours.
+// Works on texel indices (Load) since line parity is what matters; the output has the
+// same size as the input.
+//--------------------------------------------------------------------------------------
+Texture2D txInput : register(t0);
+Texture2D txPrevious : register(t1);
+
+cbuffer DeinterlaceConstants : register(b0)
+{
+    uint Mode;
+    uint KeepOddLines;
+    uint HasHistory;
+    float MotionThreshold;
+};
+
+struct PS_INPUT
+{
+    float4 Pos : SV_POSITION;
+    float2 Tex : TEXCOORD;
+};
+
+// lines outside the image are mirrored back in, which keeps their field parity
+float4 LoadLine(Texture2D tex, int2 pos, int height)
+{
+    int y = pos.y < 0 ? -pos.y : (pos.y >= height ? 2 * (height - 1) - pos.y : pos.y);
+    return tex.Load(int3(pos.x, y, 0));
+}
+
+//--------------------------------------------------------------------------------------
+// Pixel Shader
+//--------------------------------------------------------------------------------------
+float4 main(PS_INPUT input) : SV_Target
+{
+    uint width, height;
+    txInput.GetDimensions(width, height);
+    int2 pos = int2(input.Pos.xy);
+
+    float4 cur = LoadLine(txInput, pos, height);
+    float4 above = LoadLine(txInput, pos + int2(0, -1), height);
+    float4 below = LoadLine(txInput, pos + int2(0, 1), height);
+
+    if (Mode == 1) {
+        return 0.5f * cur + 0.25f * (above + below);
+    }
+
+    if (((uint)pos.y & 1) == KeepOddLines) {
+        return cur;
+    }
+
+    float4 spatial = 0.5f * (above + below);
+    if (Mode == 0 || HasHistory == 0) {
+        return spatial;
+    }
+
+    float4 prev = LoadLine(txPrevious, pos, height);
+    float4 prev_above = LoadLine(txPrevious, pos + int2(0, -1), height);
+    float4 prev_below = LoadLine(txPrevious, pos + int2(0, 1), height);
+
+    float3 diff = max(abs(cur.rgb - prev.rgb), max(abs(above.rgb - prev_above.rgb), abs(below.rgb - prev_below.rgb)));
+    float motion = max(diff.r, max(diff.g, diff.b));
+
+    // fade from weave to bob between one and two times the threshold
+    float t = saturate((motion - MotionThreshold) / MotionThreshold);
+    return lerp(cur, spatial, t);
+}
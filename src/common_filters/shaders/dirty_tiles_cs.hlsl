@@ -0,0 +1,57 @@
+//--------------------------------------------------------------------------------------
+// dirty_tiles_cs.hlsl
+//
+// Compares the current frame with the previous one and marks every tile containing a
+// changed pixel. Tiles are a multiple of the 8x8 thread group, so each group lies within
+// a single tile and only one thread per group touches the output buffers.
+// TileBits holds one bit per tile in row-major order, Changed[0] is set when any tile is dirty.
+//--------------------------------------------------------------------------------------
+Texture2D<float4> txCurrent : register(t0);
+Texture2D<float4> txPrevious : register(t1);
+
+RWStructuredBuffer<uint> TileBits : register(u0);
+RWStructuredBuffer<uint> Changed : register(u1);
+
+cbuffer DirtyTileConstants : register(b0)
+{
+    uint2 Size;
+    uint TileSize;
+    uint TilesX;
+    float Threshold;
+    uint HasHistory;
+    uint2 Padding;
+};
+
+groupshared uint gsChanged;
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID, uint index : SV_GroupIndex)
+{
+    if (index == 0)
+    {
+        gsChanged = 0;
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (all(id.xy < Size))
+    {
+        uint changed = 1;
+        if (HasHistory)
+        {
+            float4 diff = abs(txCurrent.Load(int3(id.xy, 0)) - txPrevious.Load(int3(id.xy, 0)));
+            changed = max(max(diff.r, diff.g), max(diff.b, diff.a)) > Threshold ? 1 : 0;
+        }
+        if (changed)
+        {
+            InterlockedOr(gsChanged, 1u);
+        }
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (index == 0 && gsChanged)
+    {
+        uint tile = (id.y / TileSize) * TilesX + id.x / TileSize;
+        InterlockedOr(TileBits[tile / 32], 1u << (tile % 32));
+        InterlockedOr(Changed[0], 1u);
+    }
+}
@@ -0,0 +1,57 @@
+//--------------------------------------------------------------------------------------
+// dither.hlsli
+//
+// Dithering for shaders that write to a render target with fewer bits than the source.
+// The UNORM conversion on write rounds to the nearest code value, so adding a threshold
+// in [-0.5, 0.5) code values before the write turns banding into fine noise.
+// DitherMode 0 disables dithering, 1 uses an 8x8 Bayer matrix and 2 uses interleaved
+// gradient noise, a blue-noise-like pattern that needs no texture. FrameIndex moves the
+// pattern every frame so it averages out over time instead of sitting still.
+//--------------------------------------------------------------------------------------
+cbuffer DitherConstants : register(b0)
+{
+    uint DitherMode;
+    uint FrameIndex;
+    float DitherLevels;
+    float DitherPadding;
+};
+
+static const float Bayer8x8[64] = {
+     0, 32,  8, 40,  2, 34, 10, 42,
+    48, 16, 56, 24, 50, 18, 58, 26,
+    12, 44,  4, 36, 14, 46,  6, 38,
+    60, 28, 52, 20, 62, 30, 54, 22,
+     3, 35, 11, 43,  1, 33,  9, 41,
+    51, 19, 59, 27, 49, 17, 57, 25,
+    15, 47,  7, 39, 13, 45,  5, 37,
+    63, 31, 55, 23, 61, 29, 53, 21
+};
+
+// threshold in [-0.5, 0.5) for pixel at `pos` (SV_Position.xy)
+float DitherThreshold(float2 pos)
+{
+    if (DitherMode == 1) {
+        uint2 p = (uint2)pos + uint2(FrameIndex * 3, FrameIndex * 5);
+        return (Bayer8x8[(p.y & 7) * 8 + (p.x & 7)] + 0.5f) / 64.0f - 0.5f;
+    }
+    if (DitherMode == 2) {
+        float2 p = pos + 5.588238f * (float)(FrameIndex & 63);
+        return frac(52.9829189f * frac(dot(p, float2(0.06711056f, 0.00583715f)))) - 0.5f;
+    }
+    return 0.0f;
+}
+
+float Dither(float c, float2 pos)
+{
+    return c + DitherThreshold(pos) / DitherLevels;
+}
+
+float2 Dither(float2 c, float2 pos)
+{
+    return c + DitherThreshold(pos) / DitherLevels;
+}
+
+float3 Dither(float3 c, float2 pos)
+{
+    return c + DitherThreshold(pos) / DitherLevels;
+}
@@ -0,0 +1,59 @@
+//--------------------------------------------------------------------------------------
+// frame_stats_cs.hlsl
+//
+// Builds a 256 bin luma histogram of the current frame and a 256 bin histogram of absolute
+// luma differences against the previous frame. Means are derived from the histograms on the
+// cpu, which keeps every counter well within 32 bits even for very large frames.
+// Each 16x16 thread group accumulates into groupshared bins first and then adds them to
+// Histograms[0..256] (luma) and Histograms[256..512] (difference).
+//--------------------------------------------------------------------------------------
+Texture2D<float4> txCurrent : register(t0);
+Texture2D<float4> txPrevious : register(t1);
+
+RWStructuredBuffer<uint> Histograms : register(u0);
+
+cbuffer FrameStatsConstants : register(b0)
+{
+    uint2 Size;
+    uint HasHistory;
+    uint Padding;
+};
+
+groupshared uint gsLuma[256];
+groupshared uint gsDiff[256];
+
+// same coefficients as argb_to_y_ps.hlsl, in 8 bit code values
+uint Luma(float3 c)
+{
+    float y = (0.257f * c.r + 0.504f * c.g + 0.098f * c.b) * 255.0f + 16.0f;
+    return (uint)clamp(y + 0.5f, 0.0f, 255.0f);
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 id : SV_DispatchThreadID, uint index : SV_GroupIndex)
+{
+    gsLuma[index] = 0;
+    gsDiff[index] = 0;
+    GroupMemoryBarrierWithGroupSync();
+
+    if (all(id.xy < Size))
+    {
+        uint y = Luma(txCurrent.Load(int3(id.xy, 0)).rgb);
+        InterlockedAdd(gsLuma[y], 1u);
+        if (HasHistory)
+        {
+            uint prev = Luma(txPrevious.Load(int3(id.xy, 0)).rgb);
+            InterlockedAdd(gsDiff[y > prev ? y - prev : prev - y], 1u);
+        }
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (gsLuma[index])
+    {
+        InterlockedAdd(Histograms[index], gsLuma[index]);
+    }
+    if (gsDiff[index])
+    {
+        InterlockedAdd(Histograms[256 + index], gsDiff[index]);
+    }
+}
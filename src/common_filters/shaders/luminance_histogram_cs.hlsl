@@ -0,0 +1,70 @@
+//--------------------------------------------------------------------------------------
+// luminance_histogram_cs.hlsl
+//
+// Histogram of scene luminance in nits with logarithmically spaced bins between MinNits
+// and MaxNits. Values outside the range land in the first or last bin.
+// scRGB (ARGB16Float) input is linear with 1.0 = 80 nits. sRGB encoded input (ARGB8UNorm,
+// ARGB10UNorm) is linearized and scaled so that 1.0 = SdrWhiteNits.
+// Each 16x16 thread group accumulates into groupshared bins before adding to Bins.
+//--------------------------------------------------------------------------------------
+Texture2D<float4> txInput : register(t0);
+
+RWStructuredBuffer<uint> Bins : register(u0);
+
+cbuffer HistogramConstants : register(b0)
+{
+    uint2 Size;
+    uint BinCount;
+    uint IsLinear;
+    float LogMinNits;
+    float InvLogRange;
+    float SdrWhiteNits;
+    float Padding;
+};
+
+// keep in sync with MAX_HISTOGRAM_BINS
+#define MAX_BINS 1024
+
+groupshared uint gsBins[MAX_BINS];
+
+float3 SrgbToLinear(float3 c)
+{
+    float3 lo = c / 12.92f;
+    float3 hi = pow((c + 0.055f) / 1.055f, 2.4f);
+    return (c <= 0.04045f) ? lo : hi;
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 id : SV_DispatchThreadID, uint index : SV_GroupIndex)
+{
+    for (uint i = index; i < BinCount; i += 256)
+    {
+        gsBins[i] = 0;
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (all(id.xy < Size))
+    {
+        float3 c = txInput.Load(int3(id.xy, 0)).rgb;
+        float nits = IsLinear
+            ? dot(c, float3(0.2126f, 0.7152f, 0.0722f)) * 80.0f
+            : dot(SrgbToLinear(saturate(c)), float3(0.2126f, 0.7152f, 0.0722f)) * SdrWhiteNits;
+
+        uint bin = 0;
+        if (nits > 0.0f)
+        {
+            float t = (log2(nits) - LogMinNits) * InvLogRange;
+            bin = (uint)clamp(t * BinCount, 0.0f, (float)(BinCount - 1));
+        }
+        InterlockedAdd(gsBins[bin], 1u);
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    for (uint j = index; j < BinCount; j += 256)
+    {
+        if (gsBins[j])
+        {
+            InterlockedAdd(Bins[j], gsBins[j]);
+        }
+    }
+}
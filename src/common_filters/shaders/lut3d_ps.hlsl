@@ -0,0 +1,76 @@
+//--------------------------------------------------------------------------------------
+// lut3d_ps.hlsl
+//
+// Applies a 3D colour lookup table stored in a Texture3D (x = red, y = green,
+// z = blue). Input is mapped from [DomainMin, DomainMax] onto the lattice.
+// By default the lattice is sampled with the hardware trilinear filter, which needs
+// the coordinate remapped to texel centres. With TETRAHEDRAL defined the four
+// enclosing lattice points are fetched and blended tetrahedrally, which preserves
+// neutral greys better on coarse LUTs.
+//--------------------------------------------------------------------------------------
+Texture2D txInput : register(t0);
+Texture3D txLut : register(t1);
+
+SamplerState GenericSampler : register(s0);
+
+cbuffer LutConstants : register(b0)
+{
+    float3 DomainMin;
+    float LutSize;
+    float3 DomainMax;
+    float Padding;
+};
+
+struct PS_INPUT
+{
+    float4 Pos : SV_POSITION;
+    float2 Tex : TEXCOORD;
+};
+
+float3 LutFetch(int3 idx)
+{
+    return txLut.Load(int4(idx, 0)).rgb;
+}
+
+//--------------------------------------------------------------------------------------
+// Pixel Shader
+//--------------------------------------------------------------------------------------
+float4 main(PS_INPUT input) : SV_Target
+{
+    float4 InputColor = txInput.Sample(GenericSampler, input.Tex);
+
+    float3 p = saturate((InputColor.rgb - DomainMin) / (DomainMax - DomainMin));
+
+#ifdef TETRAHEDRAL
+    p *= LutSize - 1.0f;
+    int3 i0 = min((int3)floor(p), (int)LutSize - 2);
+    float3 f = p - i0;
+
+    float3 c000 = LutFetch(i0);
+    float3 c111 = LutFetch(i0 + int3(1, 1, 1));
+    float3 c;
+    if (f.r > f.g) {
+        if (f.g > f.b) {
+            c = (1 - f.r) * c000 + (f.r - f.g) * LutFetch(i0 + int3(1, 0, 0)) + (f.g - f.b) * LutFetch(i0 + int3(1, 1, 0)) + f.b * c111;
+        } else if (f.r > f.b) {
+            c = (1 - f.r) * c000 + (f.r - f.b) * LutFetch(i0 + int3(1, 0, 0)) + (f.b - f.g) * LutFetch(i0 + int3(1, 0, 1)) + f.g * c111;
+        } else {
+            c = (1 - f.b) * c000 + (f.b - f.r) * LutFetch(i0 + int3(0, 0, 1)) + (f.r - f.g) * LutFetch(i0 + int3(1, 0, 1)) + f.g * c111;
+        }
+    } else {
+        if (f.b > f.g) {
+            c = (1 - f.b) * c000 + (f.b - f.g) * LutFetch(i0 + int3(0, 0, 1)) + (f.g - f.r) * LutFetch(i0 + int3(0, 1, 1)) + f.r * c111;
+        } else if (f.b > f.r) {
+            c = (1 - f.g) * c000 + (f.g - f.b) * LutFetch(i0 + int3(0, 1, 0)) + (f.b - f.r) * LutFetch(i0 + int3(0, 1, 1)) + f.r * c111;
+        } else {
+            c = (1 - f.g) * c000 + (f.g - f.r) * LutFetch(i0 + int3(0, 1, 0)) + (f.r - f.b) * LutFetch(i0 + int3(1, 1, 0)) + f.b * c111;
+        }
+    }
+#else
+    // map [0,1] onto texel centres so the end points hit the first/last entries exactly
+    float3 uvw = (p * (LutSize - 1.0f) + 0.5f) / LutSize;
+    float3 c = txLut.SampleLevel(GenericSampler, uvw, 0).rgb;
+#endif
+
+    return float4(saturate(c), InputColor.a);
+}
@@ -0,0 +1,110 @@
+//--------------------------------------------------------------------------------------
+// quality_metrics_cs.hlsl
+//
+// Squared error and SSIM of 8x8 blocks between a reference and a distorted frame. One
+// thread group handles one block of one plane (SV_GroupID.z). Block results are written as
+// (sample count, sum of squared differences, asuint(ssim)) and summed up on the cpu, see
+// metrics.rs for the matching cpu implementation.
+//
+// Format 0: RGB view, planes r, g, b
+// Format 1: AYUV through a R8G8B8A8 view (r = V, g = U, b = Y), planes y, u, v
+// Format 2: NV12, luma in t0/t1 and interleaved chroma in t2/t3, planes y, u, v
+//--------------------------------------------------------------------------------------
+Texture2D<float4> txReference : register(t0);
+Texture2D<float4> txDistorted : register(t1);
+Texture2D<float4> txReferenceChroma : register(t2);
+Texture2D<float4> txDistortedChroma : register(t3);
+
+RWStructuredBuffer<uint> Blocks : register(u0);
+
+cbuffer QualityConstants : register(b0)
+{
+    uint2 LumaSize;
+    uint2 BlockCount;
+    uint Format;
+    uint3 Padding;
+};
+
+static const float C1 = (0.01f * 255.0f) * (0.01f * 255.0f);
+static const float C2 = (0.03f * 255.0f) * (0.03f * 255.0f);
+
+groupshared uint gsRef[64];
+groupshared uint gsDist[64];
+groupshared uint gsValid[64];
+
+uint To8Bit(float v)
+{
+    return (uint)(saturate(v) * 255.0f + 0.5f);
+}
+
+uint LoadSample(Texture2D<float4> tex, Texture2D<float4> chroma, uint plane, uint2 pos)
+{
+    if (Format == 2)
+    {
+        if (plane == 0)
+            return To8Bit(tex.Load(int3(pos, 0)).r);
+        float2 uv = chroma.Load(int3(pos, 0)).rg;
+        return To8Bit(plane == 1 ? uv.x : uv.y);
+    }
+    float4 c = tex.Load(int3(pos, 0));
+    if (Format == 1)
+        return To8Bit(plane == 0 ? c.b : (plane == 1 ? c.g : c.r));
+    return To8Bit(plane == 0 ? c.r : (plane == 1 ? c.g : c.b));
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID, uint3 group : SV_GroupID, uint index : SV_GroupIndex)
+{
+    uint plane = group.z;
+    uint2 size = (Format == 2 && plane > 0) ? LumaSize / 2 : LumaSize;
+    uint2 pos = id.xy;
+
+    gsValid[index] = all(pos < size) ? 1 : 0;
+    gsRef[index] = 0;
+    gsDist[index] = 0;
+    if (gsValid[index])
+    {
+        gsRef[index] = LoadSample(txReference, txReferenceChroma, plane, pos);
+        gsDist[index] = LoadSample(txDistorted, txDistortedChroma, plane, pos);
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (index != 0)
+        return;
+
+    uint n = 0, sse = 0, sa = 0, sb = 0, saa = 0, sbb = 0, sab = 0;
+    for (uint i = 0; i < 64; i++)
+    {
+        if (!gsValid[i])
+            continue;
+        uint a = gsRef[i];
+        uint b = gsDist[i];
+        int d = (int)a - (int)b;
+        n += 1;
+        sse += (uint)(d * d);
+        sa += a;
+        sb += b;
+        saa += a * a;
+        sbb += b * b;
+        sab += a * b;
+    }
+
+    float ssim = 0.0f;
+    if (n > 0)
+    {
+        // integer numerators keep the variances exact, all terms fit in 32 bits for 64 samples
+        float nn = (float)(n * n);
+        float meanA = (float)sa / n;
+        float meanB = (float)sb / n;
+        float varA = (float)(n * saa - sa * sa) / nn;
+        float varB = (float)(n * sbb - sb * sb) / nn;
+        float cov = (float)((int)(n * sab) - (int)(sa * sb)) / nn;
+        ssim = ((2.0f * meanA * meanB + C1) * (2.0f * cov + C2)) /
+               ((meanA * meanA + meanB * meanB + C1) * (varA + varB + C2));
+    }
+
+    uint offset = ((plane * BlockCount.y + group.y) * BlockCount.x + group.x) * 3;
+    Blocks[offset] = n;
+    Blocks[offset + 1] = sse;
+    Blocks[offset + 2] = asuint(ssim);
+}
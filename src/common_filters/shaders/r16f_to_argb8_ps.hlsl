@@ -12,6 +12,8 @@ Texture2D txInput : register(t0);
 
 SamplerState GenericSampler : register(s0);
 
+#include "dither.hlsli"
+
 struct PS_INPUT
 {
     float4 Pos : SV_POSITION;
@@ -33,5 +35,5 @@ float4 main(PS_INPUT input) : SV_Target
     float3 hi = 1.055f * pow(c, 1.0f / 2.4f) - 0.055f;
     float3 encoded = (c <= 0.0031308f) ? lo : hi;
 
-    return float4(encoded, 1.0f);
+    return float4(Dither(encoded, input.Pos.xy), 1.0f);
 }
\ No newline at end of file
@@ -0,0 +1,45 @@
+//--------------------------------------------------------------------------------------
+// sharpen_ps.hlsl
+//
+// Unsharp mask evaluated at the output resolution, so the same shader serves both the
+// standalone sharpen filter and the fused scale + sharpen path. The four neighbours are
+// fetched one output texel apart; the difference to their average is added back scaled
+// by Strength. The result is clamped to the neighbourhood min/max which keeps edges
+// crisp without the bright/dark halos a plain unsharp mask produces on text.
+//--------------------------------------------------------------------------------------
+Texture2D txInput : register(t0);
+
+SamplerState GenericSampler : register(s0);
+
+cbuffer SharpenConstants : register(b0)
+{
+    float2 TexelSize;
+    float Strength;
+    float Padding;
+};
+
+struct PS_INPUT
+{
+    float4 Pos : SV_POSITION;
+    float2 Tex : TEXCOORD;
+};
+
+//--------------------------------------------------------------------------------------
+// Pixel Shader
+//--------------------------------------------------------------------------------------
+float4 main(PS_INPUT input) : SV_Target
+{
+    float4 c = txInput.Sample(GenericSampler, input.Tex);
+    float4 n = txInput.Sample(GenericSampler, input.Tex + float2(0.0f, -TexelSize.y));
+    float4 s = txInput.Sample(GenericSampler, input.Tex + float2(0.0f, TexelSize.y));
+    float4 w = txInput.Sample(GenericSampler, input.Tex + float2(-TexelSize.x, 0.0f));
+    float4 e = txInput.Sample(GenericSampler, input.Tex + float2(TexelSize.x, 0.0f));
+
+    float4 blur = (n + s + w + e) * 0.25f;
+    float4 lo = min(c, min(min(n, s), min(w, e)));
+    float4 hi = max(c, max(max(n, s), max(w, e)));
+
+    float4 sharp = clamp(c + Strength * (c - blur), lo, hi);
+
+    return float4(sharp.rgb, c.a);
+}
@@ -0,0 +1,52 @@
+//--------------------------------------------------------------------------------------
+// yuv.hlsli
+//
+// RGB to Y'CbCr. The matrix and range are chosen at compile time:
+//   YUV_MATRIX     601 (default), 709 or 2020
+//   YUV_FULL_RANGE 0 (default) for limited range, 1 for full range
+// generate_shader_variants! compiles including shaders once per combination.
+//--------------------------------------------------------------------------------------
+#ifndef YUV_MATRIX
+#define YUV_MATRIX 601
+#endif
+
+#ifndef YUV_FULL_RANGE
+#define YUV_FULL_RANGE 0
+#endif
+
+#if YUV_MATRIX == 709
+static const float YuvKr = 0.2126f;
+static const float YuvKb = 0.0722f;
+#elif YUV_MATRIX == 2020
+static const float YuvKr = 0.2627f;
+static const float YuvKb = 0.0593f;
+#else
+static const float YuvKr = 0.299f;
+static const float YuvKb = 0.114f;
+#endif
+
+#if YUV_FULL_RANGE
+static const float YuvYScale = 1.0f;
+static const float YuvYOffset = 0.0f;
+static const float YuvCScale = 1.0f;
+#else
+// 16-235 and 16-240 of 255. the offset is 16/256 as the conversion always used
+static const float YuvYScale = 219.0f / 255.0f;
+static const float YuvYOffset = 16.0f / 256.0f;
+static const float YuvCScale = 224.0f / 255.0f;
+#endif
+
+static const float3 YuvLumaWeights = float3(YuvKr, 1.0f - YuvKr - YuvKb, YuvKb);
+
+float RgbToY(float3 rgb)
+{
+	return YuvYOffset + YuvYScale * dot(rgb, YuvLumaWeights);
+}
+
+// (U, V)
+float2 RgbToUV(float3 rgb)
+{
+	float luma = dot(rgb, YuvLumaWeights);
+	float2 diff = float2((rgb.b - luma) / (2.0f * (1.0f - YuvKb)), (rgb.r - luma) / (2.0f * (1.0f - YuvKr)));
+	return 0.5f + YuvCScale * diff;
+}
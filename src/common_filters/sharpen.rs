@@ -0,0 +1,255 @@
+use win_desktop_duplication::texture::{ColorFormat, Texture};
+use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP;
+use windows::Win32::Graphics::Direct3D11::{D3D11_VIEWPORT, ID3D11Device4, ID3D11DeviceContext4, ID3D11RenderTargetView, ID3D11SamplerState, ID3D11ShaderResourceView};
+
+use crate::{DxFilter, Result};
+use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+use crate::error::DxFilterErr;
+use crate::shader::{PixelShader, ShaderStage, VertexShader};
+
+use super::{create_rtv, create_srv, create_tex_sampler, rgba_view_format, simple_vs};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::tex_reader::TextureReader;
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{ScaleARGBOrAYUV, SharpenARGB};
+    use crate::DxFilter;
+    use crate::utils::{create_input_texture, create_output_texture};
+
+    #[test]
+    fn test_sharpen_flat_and_edge() {
+        let (device, ctx) = build_device_and_ctx();
+
+        // left dark, right bright with a soft 64/96/160/192 ramp in the middle. a hard step would
+        // equal the neighbourhood clamp and come out unchanged even without sharpening.
+        let width = 8u32;
+        let height = 4u32;
+        let ramp = [64u8, 64, 64, 96, 160, 192, 192, 192];
+        let mut src = Vec::with_capacity((width * height * 4) as usize);
+        for _ in 0..height {
+            for &v in &ramp {
+                src.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+
+        let desc = TextureDesc { width, height, format: ColorFormat::ARGB8UNorm };
+        let input_tex = create_input_texture(&device, desc, Some(src)).unwrap();
+        let output_tex = create_output_texture(&device, desc, None).unwrap();
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+
+        let filter = SharpenARGB::new(&input_tex, &output_tex, 1.0, &device).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+
+        let mut out = Vec::new();
+        reader.get_data(&mut out, &output_tex).unwrap();
+
+        // flat regions are left untouched.
+        assert_eq!(out[0], 64);
+        assert_eq!(out[((width - 1) * 4) as usize], 192);
+        // the pixels of the ramp are pushed apart, but never beyond the local range.
+        let left = out[((width / 2 - 1) * 4) as usize];
+        let right = out[((width / 2) * 4) as usize];
+        assert!(left < 96 && right > 160, "edge not sharpened: {} {}", left, right);
+        assert!(left >= 64 && right <= 192, "sharpened beyond the neighbourhood: {} {}", left, right);
+
+        // fused path with zero strength behaves like plain scaling.
+        let scale = ScaleARGBOrAYUV::new(&input_tex, &output_tex, &device).unwrap();
+        scale.apply_filter(&ctx).unwrap();
+        let mut scaled = Vec::new();
+        reader.get_data(&mut scaled, &output_tex).unwrap();
+
+        let fused = ScaleARGBOrAYUV::new_with_sharpen(&input_tex, &output_tex, 0.0, &device).unwrap();
+        fused.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &output_tex).unwrap();
+        assert_eq!(out, scaled);
+    }
+}
+
+generate_shader!(sharpen_ps ps {
+    src_file: "src/common_filters/shaders/sharpen_ps.hlsl",
+    entry_point: "main",
+    target: "ps_5_0"
+});
+
+/// layout of `SharpenConstants` cbuffer in sharpen_ps.hlsl
+#[repr(C)]
+#[derive(Clone, Copy, Default, ShaderConstants)]
+struct SharpenConstants {
+    texel_size: [f32; 2],
+    strength: f32,
+    _padding: f32,
+}
+
+/// pixel shader and constants for the unsharp mask. Shared by [SharpenARGB] and the fused
+/// scale + sharpen mode of [ScaleARGBOrAYUV][super::ScaleARGBOrAYUV].
+pub(super) struct SharpenPass {
+    ps: PixelShader,
+    cb: ConstantBuffer<SharpenConstants>,
+    strength: f32,
+}
+
+impl SharpenPass {
+    pub(super) fn new(device: &ID3D11Device4, strength: f32) -> Result<Self> {
+        validate_strength(strength)?;
+        return Ok(Self {
+            ps: sharpen_ps(device.clone())?,
+            cb: ConstantBuffer::new(device, &SharpenConstants::default())?,
+            strength,
+        });
+    }
+
+    pub(super) fn strength(&self) -> f32 {
+        return self.strength;
+    }
+
+    pub(super) fn set_strength(&mut self, strength: f32) -> Result<()> {
+        validate_strength(strength)?;
+        self.strength = strength;
+        return Ok(());
+    }
+
+    /// uploads constants for a `width` x `height` render target and binds the pixel shader.
+    pub(super) fn bind(&self, ctx: &ID3D11DeviceContext4, width: u32, height: u32) -> Result<()> {
+        let constants = SharpenConstants {
+            texel_size: [1.0 / width as f32, 1.0 / height as f32],
+            strength: self.strength,
+            _padding: 0.0,
+        };
+        self.cb.update(ctx, &constants)?;
+        self.cb.bind(ctx, ShaderStage::Pixel, 0);
+        unsafe {
+            ctx.PSSetShader(self.ps.as_raw_ref(), Some(&[]));
+        }
+        return Ok(());
+    }
+}
+
+fn validate_strength(strength: f32) -> Result<()> {
+    if strength.is_finite() && strength >= 0.0 {
+        Ok(())
+    } else {
+        Err(DxFilterErr::BadParam(format!("sharpen strength must be a non-negative number, found {}", strength)))
+    }
+}
+
+/// Filter for sharpening [ARGBUNorm][ColorFormat::ARGB8UNorm], [ABGRUNorm][ColorFormat::ABGR8UNorm]
+/// or [AYUV][ColorFormat::AYUV] textures with a halo-clamped unsharp mask. filter also scales
+/// automatically based on input and output textures, sharpening at the output resolution.
+///
+/// `strength` of `0.0` disables sharpening, `0.5`-`1.0` is a good range for text that went soft
+/// after downscaling. To avoid an extra render when scaling anyway, use
+/// [ScaleARGBOrAYUV::new_with_sharpen][super::ScaleARGBOrAYUV::new_with_sharpen] instead.
+pub struct SharpenARGB {
+    device: ID3D11Device4,
+    vs: VertexShader,
+    sharpen: SharpenPass,
+
+    _in_tex: Texture,
+    _out_tex: Texture,
+
+    srv: ID3D11ShaderResourceView,
+    rtv: ID3D11RenderTargetView,
+    sampler: ID3D11SamplerState,
+}
+
+impl SharpenARGB {
+    /// create new instance of SharpenARGB filter. After creation, filter takes ARGB or ABGR or AYUV
+    /// input from `input_tex` and writes the sharpened image to same format `out_tex`.
+    pub fn new(input_tex: &Texture, out_tex: &Texture, strength: f32, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        Self::validate_output(out_tex)?;
+
+        let sharpen = SharpenPass::new(device, strength)?;
+        let vs = simple_vs(device.clone())?;
+
+        let srv = create_srv(device, input_tex, rgba_view_format(input_tex.desc().format))?;
+        let sampler = create_tex_sampler(device)?;
+        let rtv = create_rtv(device, out_tex, rgba_view_format(out_tex.desc().format))?;
+
+        return Ok(Self {
+            device: device.clone(),
+            vs,
+            sharpen,
+            _in_tex: input_tex.clone(),
+            _out_tex: out_tex.clone(),
+            srv,
+            rtv,
+            sampler,
+        });
+    }
+
+    /// current sharpening strength.
+    pub fn strength(&self) -> f32 {
+        return self.sharpen.strength();
+    }
+
+    /// change sharpening strength. takes effect on the next [apply_filter][DxFilter::apply_filter].
+    pub fn set_strength(&mut self, strength: f32) -> Result<()> {
+        return self.sharpen.set_strength(strength);
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::AYUV => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR or AYUV format found {:?}", desc.format)))
+            }
+        }
+    }
+    fn validate_output(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm | ColorFormat::AYUV => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR or AYUV format found {:?}", desc.format)))
+            }
+        }
+    }
+}
+
+impl DxFilter for SharpenARGB {
+    fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        let out_desc = self._out_tex.desc();
+        let vp = D3D11_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: out_desc.width as _,
+            Height: out_desc.height as _,
+            MinDepth: 0.0,
+            MaxDepth: 0.0,
+        };
+        unsafe {
+            ctx.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
+            ctx.VSSetShader(self.vs.as_raw_ref(), Some(&[]));
+            self.sharpen.bind(ctx, out_desc.width, out_desc.height)?;
+            ctx.PSSetSamplers(0, Some(&[Some(self.sampler.clone())]));
+            ctx.PSSetShaderResources(0, Some(&[Some(self.srv.clone())]));
+            ctx.RSSetViewports(Some(&[vp]));
+            ctx.OMSetRenderTargets(Some(&[Some(self.rtv.clone())]), None);
+            ctx.Draw(4, 0);
+        }
+        return Ok(());
+    }
+
+    fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        SharpenARGB::validate_input(tex)?;
+        self._in_tex = tex.clone();
+        self.srv = create_srv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+
+    fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
+        SharpenARGB::validate_output(tex)?;
+        self._out_tex = tex.clone();
+        self.rtv = create_rtv(&self.device, tex, rgba_view_format(tex.desc().format))?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,311 @@
+use std::cmp::Reverse;
+
+use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device4, ID3D11DeviceContext4};
+
+use crate::{DxFilter, Result};
+use crate::error::DxFilterErr;
+use crate::pool::{LeasedTexture, TexturePool};
+
+use super::{same_desc, ConvertARGBToNV12, ScaleARGBOrAYUV, ScaleAlgorithm};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::tex_reader::TextureReader;
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::test::build_device_and_ctx;
+    use crate::common_filters::{ScaleAlgorithm, SimulcastNV12, SimulcastRung};
+    use crate::DxFilter;
+    use crate::utils::{create_input_texture, create_output_texture};
+
+    #[test]
+    fn test_simulcast() {
+        let (device, ctx) = build_device_and_ctx();
+
+        let input_desc = TextureDesc { width: 64, height: 64, format: ColorFormat::ARGB8UNorm };
+        let input_tex = create_input_texture(&device, input_desc, Some(vec![10; 64 * 64 * 4])).unwrap();
+        let nv12 = |width, height| create_output_texture(&device, TextureDesc { width, height, format: ColorFormat::NV12 }, None).unwrap();
+        let outputs = [nv12(16, 16), nv12(64, 64), nv12(32, 32)];
+
+        let rungs = vec![
+            SimulcastRung::new(&outputs[0], ScaleAlgorithm::Point),
+            SimulcastRung::new(&outputs[1], ScaleAlgorithm::Bilinear),
+            SimulcastRung::new(&outputs[2], ScaleAlgorithm::Bicubic),
+        ];
+        let mut filter = SimulcastNV12::new(&input_tex, &rungs, &device).unwrap();
+        // full size rung needs no scaling, the others scale 64 -> 32 -> 16
+        assert_eq!(filter.intermediates(), 2);
+        filter.apply_filter(&ctx).unwrap();
+
+        let mut reader = TextureReader::new(device.clone(), ctx.clone());
+        let mut out = Vec::new();
+        for tex in &outputs {
+            let desc = tex.desc();
+            let luma = (desc.width * desc.height) as usize;
+            reader.get_data(&mut out, tex).unwrap();
+            assert_eq!(out.len(), luma * 3 / 2);
+            assert_eq!(out[0], 24);
+            assert_eq!(out[luma..luma + 2], [127, 127]);
+        }
+
+        // swapping an output of the same size keeps the ladder
+        let replacement = nv12(32, 32);
+        filter.set_output_tex(&replacement).unwrap();
+        filter.apply_filter(&ctx).unwrap();
+        reader.get_data(&mut out, &replacement).unwrap();
+        assert_eq!(out[0], 24);
+        assert!(filter.set_output_tex(&nv12(48, 48)).is_err());
+
+        assert!(SimulcastNV12::new(&input_tex, &[], &device).is_err());
+        // outputs are told apart by size
+        let twins = [SimulcastRung::new(&nv12(32, 32), ScaleAlgorithm::Point), SimulcastRung::new(&nv12(32, 32), ScaleAlgorithm::Bicubic)];
+        assert!(SimulcastNV12::new(&input_tex, &twins, &device).is_err());
+        assert!(filter.set_rungs(&twins).is_err());
+
+        // a rebuild with the same ladder hands back the pooled intermediates
+        let raw: Vec<_> = filter.intermediates.iter().map(|tex| tex.as_raw_ref().clone()).collect();
+        filter.set_rungs(&rungs).unwrap();
+        assert!(filter.intermediates.iter().map(|tex| tex.as_raw_ref().clone()).eq(raw));
+        let argb = create_output_texture(&device, input_desc, None).unwrap();
+        assert!(SimulcastNV12::new(&input_tex, &[SimulcastRung::new(&argb, ScaleAlgorithm::Bilinear)], &device).is_err());
+    }
+}
+
+/// An output of [SimulcastNV12]: an NV12 texture and the algorithm used to scale down to its size.
+#[derive(Clone)]
+pub struct SimulcastRung {
+    output: Texture,
+    algorithm: ScaleAlgorithm,
+}
+
+impl SimulcastRung {
+    /// create a rung writing to NV12 `output`, scaled with `algorithm`.
+    pub fn new(output: &Texture, algorithm: ScaleAlgorithm) -> Self {
+        Self { output: output.clone(), algorithm }
+    }
+
+    /// NV12 texture written by this rung.
+    pub fn output(&self) -> &Texture {
+        &self.output
+    }
+
+    /// algorithm used to scale down to this rung.
+    pub fn algorithm(&self) -> ScaleAlgorithm {
+        self.algorithm
+    }
+
+    fn area(&self) -> u64 {
+        let desc = self.output.desc();
+        desc.width as u64 * desc.height as u64
+    }
+}
+
+/// filters of a single rung. `scale` is absent when the rung has the size of its source.
+struct RungFilters {
+    scale: Option<ScaleARGBOrAYUV>,
+    convert: ConvertARGBToNV12,
+    /// true when the first filter of the rung reads the ladder input.
+    reads_input: bool,
+    size: (u32, u32),
+}
+
+/// Filter for producing several NV12 textures of different sizes from one [ARGBUNorm][ColorFormat::ARGB8UNorm]
+/// or [ABGRUNorm][ColorFormat::ABGR8UNorm] input, e.g. for simulcast or adaptive bitrate encoding.
+///
+/// Rungs are processed from largest to smallest. Every rung is scaled from the RGB image of the
+/// previous rung instead of the full size input, which saves bandwidth on tall ladders. Each rung
+/// picks its own [ScaleAlgorithm]; a rung with the size of its source is converted without scaling.
+/// The RGB images between rungs come from a [TexturePool].
+///
+/// ## Example Usage:
+/// ```
+/// let filter = SimulcastNV12::new(&capture_tex, &[
+///     SimulcastRung::new(&nv12_1080p, ScaleAlgorithm::Bilinear),
+///     SimulcastRung::new(&nv12_720p, ScaleAlgorithm::Bicubic),
+///     SimulcastRung::new(&nv12_360p, ScaleAlgorithm::Bilinear),
+/// ], &device)?;
+///
+/// filter.apply_filter(&ctx)?;
+/// ```
+pub struct SimulcastNV12 {
+    device: ID3D11Device4,
+    pool: TexturePool,
+    rungs: Vec<SimulcastRung>,
+    filters: Vec<RungFilters>,
+    intermediates: Vec<LeasedTexture>,
+
+    _in_tex: Texture,
+}
+
+impl SimulcastNV12 {
+    /// create new instance of SimulcastNV12 filter. After creation, filter takes RGB input from
+    /// `input_tex` and writes to the NV12 output of every rung. Every rung needs a different size.
+    pub fn new(input_tex: &Texture, rungs: &[SimulcastRung], device: &ID3D11Device4) -> Result<Self> {
+        return Self::with_pool(input_tex, rungs, &TexturePool::new(device), device);
+    }
+
+    /// same as [new][SimulcastNV12::new] but takes intermediate textures from `pool`.
+    pub fn with_pool(input_tex: &Texture, rungs: &[SimulcastRung], pool: &TexturePool, device: &ID3D11Device4) -> Result<Self> {
+        Self::validate_input(input_tex)?;
+        let rungs = Self::sort_rungs(rungs)?;
+        let (filters, intermediates) = Self::build(input_tex, &rungs, pool, device)?;
+        return Ok(Self {
+            device: device.clone(),
+            pool: pool.clone(),
+            rungs,
+            filters,
+            intermediates,
+            _in_tex: input_tex.clone(),
+        });
+    }
+
+    /// rungs in processing order, largest first.
+    pub fn rungs(&self) -> &[SimulcastRung] {
+        return &self.rungs;
+    }
+
+    /// number of intermediate RGB textures held by the ladder.
+    pub fn intermediates(&self) -> usize {
+        return self.intermediates.len();
+    }
+
+    /// replace all rungs of the ladder.
+    pub fn set_rungs(&mut self, rungs: &[SimulcastRung]) -> Result<()> {
+        let rungs = Self::sort_rungs(rungs)?;
+        self.rungs = rungs;
+        self.rebuild()?;
+        return Ok(());
+    }
+
+    /// build the filters of the ladder again for the current input and rungs.
+    fn rebuild(&mut self) -> Result<()> {
+        // return the old intermediates to the pool first so rungs of the same size reuse them
+        self.filters.clear();
+        self.intermediates.clear();
+        (self.filters, self.intermediates) = Self::build(&self._in_tex, &self.rungs, &self.pool, &self.device)?;
+        return Ok(());
+    }
+
+    fn sort_rungs(rungs: &[SimulcastRung]) -> Result<Vec<SimulcastRung>> {
+        if rungs.is_empty() {
+            return Err(DxFilterErr::BadParam("simulcast needs at least one rung".to_owned()));
+        }
+        for (i, rung) in rungs.iter().enumerate() {
+            Self::validate_output(&rung.output)?;
+            // outputs are replaced by size in set_output_tex
+            let desc = rung.output.desc();
+            if rungs[..i].iter().any(|other| (other.output.desc().width, other.output.desc().height) == (desc.width, desc.height)) {
+                return Err(DxFilterErr::BadParam(format!("more than one simulcast rung with size {}x{}", desc.width, desc.height)));
+            }
+        }
+        let mut rungs = rungs.to_vec();
+        rungs.sort_by_key(|rung| Reverse(rung.area()));
+        return Ok(rungs);
+    }
+
+    fn build(input_tex: &Texture, rungs: &[SimulcastRung], pool: &TexturePool, device: &ID3D11Device4) -> Result<(Vec<RungFilters>, Vec<LeasedTexture>)> {
+        let format = input_tex.desc().format;
+        let mut filters = Vec::with_capacity(rungs.len());
+        let mut intermediates: Vec<LeasedTexture> = Vec::with_capacity(rungs.len());
+        // index into `intermediates` of the RGB image of the previous rung, `None` for the input
+        let mut source_idx: Option<usize> = None;
+
+        for rung in rungs {
+            let out_desc = rung.output.desc();
+            let size = (out_desc.width, out_desc.height);
+            let reads_input = source_idx.is_none();
+            let source: Texture = match source_idx {
+                Some(i) => (*intermediates[i]).clone(),
+                None => input_tex.clone(),
+            };
+            let source_desc = source.desc();
+
+            let (scale, convert) = if (source_desc.width, source_desc.height) == size {
+                (None, ConvertARGBToNV12::new(&source, &rung.output, device)?)
+            } else {
+                let scaled = pool.acquire_intermediate(TextureDesc { width: size.0, height: size.1, format })?;
+                let scale = ScaleARGBOrAYUV::new_with_algorithm(&source, &scaled, rung.algorithm, device)?;
+                let convert = ConvertARGBToNV12::new(&scaled, &rung.output, device)?;
+                intermediates.push(scaled);
+                source_idx = Some(intermediates.len() - 1);
+                (Some(scale), convert)
+            };
+            filters.push(RungFilters { scale, convert, reads_input, size });
+        }
+        Ok((filters, intermediates))
+    }
+
+    fn validate_input(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::ARGB8UNorm | ColorFormat::ABGR8UNorm => {
+                Ok(())
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected ARGB or ABGR format found {:?}", desc.format).to_owned()))
+            }
+        }
+    }
+
+    fn validate_output(tex: &Texture) -> Result<()> {
+        let desc = tex.desc();
+        match desc.format {
+            ColorFormat::NV12 if desc.width % 2 == 0 && desc.height % 2 == 0 => {
+                Ok(())
+            }
+            ColorFormat::NV12 => {
+                Err(DxFilterErr::BadParam(format!("NV12 outputs need even dimensions, found {}x{}", desc.width, desc.height)))
+            }
+            _ => {
+                Err(DxFilterErr::BadParam(format!("expected NV12 format found {:?}", desc.format).to_owned()))
+            }
+        }
+    }
+}
+
+impl DxFilter for SimulcastNV12 {
+    fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        for rung in &self.filters {
+            if let Some(scale) = &rung.scale {
+                scale.apply_filter(ctx)?;
+            }
+            rung.convert.apply_filter(ctx)?;
+        }
+        return Ok(());
+    }
+
+    /// rebuilds the ladder when the size or format of the input changes.
+    fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        Self::validate_input(tex)?;
+        // no filters are left after a failed rebuild, build them again
+        if self.filters.is_empty() || !same_desc(&tex.desc(), &self._in_tex.desc()) {
+            self._in_tex = tex.clone();
+            return self.rebuild();
+        }
+        for rung in self.filters.iter_mut().filter(|rung| rung.reads_input) {
+            match &mut rung.scale {
+                Some(scale) => scale.set_input_tex(tex)?,
+                None => rung.convert.set_input_tex(tex)?,
+            }
+        }
+        self._in_tex = tex.clone();
+        return Ok(());
+    }
+
+    /// replaces the output of the rung with the same size as `tex`. rung sizes are unique, see
+    /// [new][SimulcastNV12::new].
+    fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
+        Self::validate_output(tex)?;
+        let desc = tex.desc();
+        let idx = match self.filters.iter().position(|rung| rung.size == (desc.width, desc.height)) {
+            Some(idx) => idx,
+            None => {
+                return Err(DxFilterErr::BadParam(format!("no simulcast rung with size {}x{}", desc.width, desc.height)));
+            }
+        };
+        self.filters[idx].convert.set_output_tex(tex)?;
+        self.rungs[idx].output = tex.clone();
+        return Ok(());
+    }
+}
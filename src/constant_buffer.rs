@@ -0,0 +1,181 @@
+//! Constant buffers for passing runtime parameters to shaders.
+//!
+//! Describe the `cbuffer` with a `#[repr(C)]` struct deriving [ShaderConstants], which checks the
+//! HLSL packing rules at compile time, and upload it with a [ConstantBuffer].
+//!
+//! ## Example Usage:
+//! ```ignore
+//! use dxfilter::constant_buffer::{ConstantBuffer, ShaderConstants};
+//! use dxfilter::shader::ShaderStage;
+//!
+//! #[repr(C)]
+//! #[derive(Clone, Copy, ShaderConstants)]
+//! struct TintConstants {
+//!     color: [f32; 3],
+//!     strength: f32,
+//! }
+//!
+//! // paste into the shader, or compare with the declaration already there
+//! println!("{}", TintConstants::hlsl_cbuffer(0));
+//!
+//! let cb = ConstantBuffer::new(&device, &TintConstants { color: [1.0, 0.5, 0.0], strength: 0.3 })?;
+//! cb.bind(&ctx, ShaderStage::Pixel, 0);
+//! // draw
+//!
+//! cb.update(&ctx, &TintConstants { color: [0.0, 0.5, 1.0], strength: 0.3 })?;
+//! ```
+//!
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ptr::copy_nonoverlapping;
+
+use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_CONSTANT_BUFFER, D3D11_BUFFER_DESC, D3D11_CPU_ACCESS_WRITE, D3D11_MAP_WRITE_DISCARD, D3D11_MAPPED_SUBRESOURCE, D3D11_SUBRESOURCE_DATA, D3D11_USAGE, D3D11_USAGE_DEFAULT, D3D11_USAGE_DYNAMIC, ID3D11Buffer, ID3D11Device4, ID3D11DeviceContext4};
+
+pub use shader_macro::ShaderConstants;
+
+use crate::error::DxFilterErr;
+use crate::Result;
+use crate::shader::ShaderStage;
+
+#[cfg(test)]
+mod test {
+    use crate::constant_buffer::{ConstantBuffer, ShaderConstants};
+    use crate::shader::ShaderStage;
+    use crate::utils::{AdapterFactory, create_device_context};
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default, ShaderConstants)]
+    struct TestConstants {
+        color: [f32; 3],
+        strength: f32,
+        rows: [[f32; 4]; 2],
+    }
+
+    #[test]
+    fn test_hlsl_cbuffer() {
+        assert_eq!(TestConstants::hlsl_cbuffer(2), "cbuffer TestConstants : register(b2)\n{\n    float3 color;\n    float strength;\n    float4 rows[2];\n};\n");
+    }
+
+    #[test]
+    fn test_constant_buffer() {
+        let adapter = AdapterFactory::new().get_adapter_by_idx(0).unwrap();
+        let (device, ctx) = create_device_context(&adapter).unwrap();
+
+        let constants = TestConstants { strength: 0.5, ..Default::default() };
+        for cb in [ConstantBuffer::new(&device, &constants).unwrap(), ConstantBuffer::new_dynamic(&device, &constants).unwrap()] {
+            cb.update(&ctx, &TestConstants { strength: 1.0, ..constants }).unwrap();
+            cb.bind(&ctx, ShaderStage::Pixel, 0);
+            cb.bind(&ctx, ShaderStage::Compute, 1);
+        }
+    }
+}
+
+/// Rust struct with the layout of an HLSL `cbuffer`. Implement it with `#[derive(ShaderConstants)]`
+/// on a `#[repr(C)]` struct; the derive rejects structs whose fields don't line up with HLSL
+/// packing and fills in the constants below.
+pub trait ShaderConstants: Copy {
+    /// name of the struct, used as the name of the `cbuffer`.
+    const NAME: &'static str;
+
+    /// HLSL declarations of the fields, one per line.
+    const HLSL_FIELDS: &'static str;
+
+    /// HLSL `cbuffer` declaration matching the struct, bound to register `b{register}`.
+    fn hlsl_cbuffer(register: u32) -> String {
+        format!("cbuffer {} : register(b{})\n{{\n{}}};\n", Self::NAME, register, Self::HLSL_FIELDS)
+    }
+}
+
+/// A constant buffer holding one `T`.
+///
+/// Buffers created with [new][ConstantBuffer::new] are updated with `UpdateSubresource`, which
+/// suits constants changing at most once per frame. [new_dynamic][ConstantBuffer::new_dynamic]
+/// buffers are written with `Map`, which is cheaper when the same buffer is updated several times
+/// per frame.
+pub struct ConstantBuffer<T: ShaderConstants> {
+    buffer: ID3D11Buffer,
+    dynamic: bool,
+    _constants: PhantomData<T>,
+}
+
+impl<T: ShaderConstants> ConstantBuffer<T> {
+    /// create a constant buffer holding `initial`, updated with `UpdateSubresource`.
+    pub fn new(device: &ID3D11Device4, initial: &T) -> Result<Self> {
+        return Self::create(device, initial, false);
+    }
+
+    /// create a constant buffer holding `initial`, updated with `Map`.
+    pub fn new_dynamic(device: &ID3D11Device4, initial: &T) -> Result<Self> {
+        return Self::create(device, initial, true);
+    }
+
+    fn create(device: &ID3D11Device4, initial: &T, dynamic: bool) -> Result<Self> {
+        let (usage, cpu_access): (D3D11_USAGE, u32) = if dynamic {
+            (D3D11_USAGE_DYNAMIC, D3D11_CPU_ACCESS_WRITE.0 as _)
+        } else {
+            (D3D11_USAGE_DEFAULT, 0)
+        };
+        let desc = D3D11_BUFFER_DESC {
+            ByteWidth: size_of::<T>() as _,
+            Usage: usage,
+            BindFlags: D3D11_BIND_CONSTANT_BUFFER.0 as _,
+            CPUAccessFlags: cpu_access,
+            MiscFlags: 0,
+            StructureByteStride: 0,
+        };
+        let data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: initial as *const T as _,
+            SysMemPitch: 0,
+            SysMemSlicePitch: 0,
+        };
+        let mut buffer = None;
+        if let Err(e) = unsafe { device.CreateBuffer(&desc, Some(&data), Some(&mut buffer)) } {
+            return Err(DxFilterErr::Unknown(format!("failed to create constant buffer. {:?}", e)));
+        }
+        return Ok(Self {
+            buffer: buffer.unwrap(),
+            dynamic,
+            _constants: PhantomData,
+        });
+    }
+
+    /// replace the contents of the buffer with `data`. shaders see the new values in draws and
+    /// dispatches issued afterwards.
+    pub fn update(&self, ctx: &ID3D11DeviceContext4, data: &T) -> Result<()> {
+        if !self.dynamic {
+            unsafe {
+                ctx.UpdateSubresource(&self.buffer, 0, None, data as *const T as _, 0, 0);
+            }
+            return Ok(());
+        }
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        if let Err(e) = unsafe { ctx.Map(&self.buffer, 0, D3D11_MAP_WRITE_DISCARD, 0, Some(&mut mapped)) } {
+            return Err(DxFilterErr::Unknown(format!("failed to map constant buffer. {:?}", e)));
+        }
+        unsafe {
+            copy_nonoverlapping(data as *const T, mapped.pData as *mut T, 1);
+            ctx.Unmap(&self.buffer, 0);
+        }
+        return Ok(());
+    }
+
+    /// bind the buffer to register `b{slot}` of given shader stage.
+    pub fn bind(&self, ctx: &ID3D11DeviceContext4, stage: ShaderStage, slot: u32) {
+        let buffers = [Some(self.buffer.clone())];
+        unsafe {
+            match stage {
+                ShaderStage::Vertex => ctx.VSSetConstantBuffers(slot, Some(&buffers)),
+                ShaderStage::Pixel => ctx.PSSetConstantBuffers(slot, Some(&buffers)),
+                ShaderStage::Compute => ctx.CSSetConstantBuffers(slot, Some(&buffers)),
+                ShaderStage::Geometry => ctx.GSSetConstantBuffers(slot, Some(&buffers)),
+                ShaderStage::Hull => ctx.HSSetConstantBuffers(slot, Some(&buffers)),
+                ShaderStage::Domain => ctx.DSSetConstantBuffers(slot, Some(&buffers)),
+            }
+        }
+    }
+
+    /// get raw reference to `ID3D11Buffer` instance.
+    pub fn as_raw_ref(&self) -> &ID3D11Buffer {
+        return &self.buffer;
+    }
+}
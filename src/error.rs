@@ -2,6 +2,8 @@
 //!
 use std::fmt::Debug;
 
+use crate::shader::ShaderCompileErr;
+
 pub type DxResult<T> = Result<T, DxFilterErr>;
 
 /// all methods in this crate throw this error
@@ -16,4 +18,7 @@ pub enum DxFilterErr {
 
     /// unexpected internal error. typically happens only with bugs in this crate.
     Unknown(String),
+
+    /// HLSL source failed to compile at runtime, see [compile_runtime][crate::shader::compile_runtime].
+    ShaderCompile(ShaderCompileErr),
 }
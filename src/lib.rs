@@ -1,10 +1,16 @@
 #![doc = include_str ! ("../README.md")]
 
+// lets `#[derive(ShaderConstants)]` refer to `::dxfilter` inside this crate too
+extern crate self as dxfilter;
+
 use win_desktop_duplication::texture::Texture;
 use windows::Win32::Graphics::Direct3D11::{ID3D11Device4, ID3D11DeviceContext4};
 
 #[cfg(not(doc))]
 pub use shader_macro::compile_shader;
+pub use shader_macro::generate_shader_variants;
+#[doc(hidden)]
+pub use shader_macro::compile_shader_variants;
 
 
 #[macro_use]
@@ -17,8 +23,18 @@ pub use error::DxResult as Result;
 
 pub mod shader;
 
+pub mod constant_buffer;
+
 pub mod color;
 
+pub mod lut;
+
+pub mod metrics;
+
+pub mod pipeline;
+
+pub mod pool;
+
 mod common_filters;
 
 pub mod utils;
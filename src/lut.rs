@@ -0,0 +1,231 @@
+//! parser for Adobe / Resolve `.cube` 3D LUT files. The parsed [CubeLut] can be applied to
+//! textures with [ApplyLUT3D][crate::ApplyLUT3D].
+//!
+use std::fs::read_to_string;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::DxFilterErr;
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use crate::lut::CubeLut;
+
+    const SIMPLE_LUT: &str = "# created by hand
+TITLE \"invert\"
+
+LUT_3D_SIZE 2
+DOMAIN_MIN 0 0 0
+DOMAIN_MAX 1 1 1
+
+1.0 1.0 1.0
+0.0 1.0 1.0
+1.0 0.0 1.0
+0.0 0.0 1.0
+1.0 1.0 0.0
+0.0 1.0 0.0
+1.0 0.0 0.0
+0.0 0.0 0.0
+";
+
+    #[test]
+    fn test_parse_cube() {
+        let lut: CubeLut = SIMPLE_LUT.parse().unwrap();
+        assert_eq!(lut.title.as_deref(), Some("invert"));
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.domain_min, [0.0; 3]);
+        assert_eq!(lut.domain_max, [1.0; 3]);
+        assert_eq!(lut.data.len(), 8);
+        // red varies fastest
+        assert_eq!(lut.get(1, 0, 0), [0.0, 1.0, 1.0]);
+        assert_eq!(lut.get(0, 1, 0), [1.0, 0.0, 1.0]);
+        assert_eq!(lut.get(0, 0, 1), [1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_parse_resolve_input_range() {
+        let lut = CubeLut::parse("LUT_3D_SIZE 2\nLUT_3D_INPUT_RANGE -0.5 1.5\n\
+            0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n").unwrap();
+        assert_eq!(lut.title, None);
+        assert_eq!(lut.domain_min, [-0.5; 3]);
+        assert_eq!(lut.domain_max, [1.5; 3]);
+    }
+
+    #[test]
+    fn test_identity() {
+        let lut = CubeLut::identity(17);
+        assert_eq!(lut.data.len(), 17 * 17 * 17);
+        assert_eq!(lut.get(16, 8, 0), [1.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // missing size
+        assert!(CubeLut::parse("0 0 0\n").is_err());
+        // wrong number of entries
+        assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        // bad number, the error points at the line
+        let err = CubeLut::parse("LUT_3D_SIZE 2\n0 0 x\n").unwrap_err();
+        assert!(format!("{:?}", err).contains("line 2"), "{:?}", err);
+        // 1D LUTs are not supported
+        assert!(CubeLut::parse("LUT_1D_SIZE 2\n0 0 0\n1 1 1\n").is_err());
+        // size out of range
+        assert!(CubeLut::parse("LUT_3D_SIZE 1\n0 0 0\n").is_err());
+        // inverted domain
+        assert!(CubeLut::parse(&SIMPLE_LUT.replace("DOMAIN_MAX 1 1 1", "DOMAIN_MAX 0 1 1")).is_err());
+    }
+}
+
+/// largest `LUT_3D_SIZE` accepted. matches the limit in the Resolve `.cube` spec.
+pub const MAX_CUBE_SIZE: u32 = 256;
+
+/// A 3D colour lookup table as described by a `.cube` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubeLut {
+    /// value of the `TITLE` keyword, if present
+    pub title: Option<String>,
+    /// number of entries along each axis (`LUT_3D_SIZE`)
+    pub size: u32,
+    /// input value mapped to the first entry of each axis (`DOMAIN_MIN`)
+    pub domain_min: [f32; 3],
+    /// input value mapped to the last entry of each axis (`DOMAIN_MAX`)
+    pub domain_max: [f32; 3],
+    /// `size^3` output colours with red changing fastest, then green, then blue.
+    pub data: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    /// create a LUT of given size which maps every colour to itself. panics if `size` is below 2.
+    pub fn identity(size: u32) -> Self {
+        assert!(size >= 2, "lut size must be at least 2");
+        let max = (size - 1) as f32;
+        let mut data = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([r as f32 / max, g as f32 / max, b as f32 / max]);
+                }
+            }
+        }
+        Self {
+            title: None,
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data,
+        }
+    }
+
+    /// read and parse a `.cube` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = read_to_string(path.as_ref()).map_err(|e| {
+            DxFilterErr::BadParam(format!("failed to read lut file {:?}. {}", path.as_ref(), e))
+        })?;
+        Self::parse(&text)
+    }
+
+    /// parse the contents of a `.cube` file.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut data = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let keyword = parts.next().unwrap();
+
+            if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.') {
+                let entry = parse_floats::<3>(line_no, line.split_whitespace())?;
+                data.push(entry);
+                continue;
+            }
+            if !data.is_empty() {
+                return Err(bad_line(line_no, format!("keyword `{}` found after table data", keyword)));
+            }
+
+            match keyword {
+                "TITLE" => {
+                    let rest = line["TITLE".len()..].trim();
+                    title = Some(rest.trim_matches('"').to_owned());
+                }
+                "LUT_3D_SIZE" => {
+                    let [n] = parse_floats::<1>(line_no, parts)?;
+                    if n.fract() != 0.0 || n < 2.0 || n > MAX_CUBE_SIZE as f32 {
+                        return Err(bad_line(line_no, format!("LUT_3D_SIZE must be an integer in 2..={}, found {}", MAX_CUBE_SIZE, n)));
+                    }
+                    size = Some(n as u32);
+                }
+                "LUT_1D_SIZE" => {
+                    return Err(bad_line(line_no, "1D LUTs are not supported".to_owned()));
+                }
+                "DOMAIN_MIN" => {
+                    domain_min = parse_floats::<3>(line_no, parts)?;
+                }
+                "DOMAIN_MAX" => {
+                    domain_max = parse_floats::<3>(line_no, parts)?;
+                }
+                "LUT_3D_INPUT_RANGE" => {
+                    let [min, max] = parse_floats::<2>(line_no, parts)?;
+                    domain_min = [min; 3];
+                    domain_max = [max; 3];
+                }
+                // other keywords (e.g. LUT_1D_INPUT_RANGE) don't affect 3D tables
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or_else(|| DxFilterErr::BadParam("lut is missing LUT_3D_SIZE".to_owned()))?;
+        let expected = (size * size * size) as usize;
+        if data.len() != expected {
+            return Err(DxFilterErr::BadParam(format!("expected {} lut entries for LUT_3D_SIZE {}, found {}", expected, size, data.len())));
+        }
+        if (0..3).any(|i| domain_min[i] >= domain_max[i]) {
+            return Err(DxFilterErr::BadParam(format!("DOMAIN_MIN {:?} must be below DOMAIN_MAX {:?}", domain_min, domain_max)));
+        }
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            data,
+        })
+    }
+
+    /// output colour stored at given red, green and blue indices.
+    pub fn get(&self, r: u32, g: u32, b: u32) -> [f32; 3] {
+        self.data[(r + g * self.size + b * self.size * self.size) as usize]
+    }
+}
+
+impl FromStr for CubeLut {
+    type Err = DxFilterErr;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn parse_floats<'a, const N: usize>(line_no: usize, mut parts: impl Iterator<Item=&'a str>) -> Result<[f32; N]> {
+    let mut out = [0.0; N];
+    for v in out.iter_mut() {
+        let part = parts.next().ok_or_else(|| bad_line(line_no, format!("expected {} values", N)))?;
+        *v = part.parse::<f32>().map_err(|_| bad_line(line_no, format!("`{}` is not a number", part)))?;
+    }
+    if parts.next().is_some() {
+        return Err(bad_line(line_no, format!("expected {} values", N)));
+    }
+    Ok(out)
+}
+
+fn bad_line(line_no: usize, msg: String) -> DxFilterErr {
+    DxFilterErr::BadParam(format!("invalid cube lut at line {}: {}", line_no, msg))
+}
@@ -0,0 +1,244 @@
+//! PSNR and SSIM between two frames of the same format, for tuning scaling and chroma options.
+//!
+//! [compare_frames] works on tightly packed byte buffers as returned by
+//! `win_desktop_duplication::tex_reader::TextureReader` and runs anywhere.
+//! [TextureComparator][crate::TextureComparator] computes the same numbers on the gpu directly
+//! from textures. Both evaluate SSIM over non-overlapping 8x8 blocks and average the block
+//! scores, so their results agree up to floating point rounding.
+//!
+use win_desktop_duplication::texture::ColorFormat;
+
+use crate::error::DxFilterErr;
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::texture::ColorFormat;
+
+    use crate::metrics::{compare_frames, SSIM_BLOCK_SIZE};
+
+    #[test]
+    fn test_identical_frames() {
+        let frame: Vec<u8> = (0..16 * 16 * 4).map(|i| (i * 7 % 256) as u8).collect();
+        let metrics = compare_frames(ColorFormat::ARGB8UNorm, 16, 16, &frame, &frame).unwrap();
+        assert!(metrics.psnr.iter().all(|p| p.is_infinite()));
+        assert!(metrics.ssim.iter().all(|&s| (s - 1.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_psnr_per_plane() {
+        // BGRA memory order: only blue differs, by 1 on every pixel.
+        let reference = vec![100u8; 8 * 8 * 4];
+        let distorted: Vec<u8> = reference.iter().enumerate()
+            .map(|(i, &v)| if i % 4 == 0 { v + 1 } else { v }).collect();
+        let metrics = compare_frames(ColorFormat::ARGB8UNorm, 8, 8, &reference, &distorted).unwrap();
+        // planes are r, g, b
+        assert!(metrics.psnr[0].is_infinite() && metrics.psnr[1].is_infinite());
+        assert!((metrics.psnr[2] - 20.0 * 255f64.log10()).abs() < 1e-9);
+        // a constant offset on a flat block barely changes structure
+        assert!(metrics.ssim[2] > 0.99 && metrics.ssim[2] < 1.0);
+    }
+
+    #[test]
+    fn test_nv12_planes() {
+        let (width, height) = (16, 8);
+        let reference = vec![128u8; width * height * 3 / 2];
+        let mut distorted = reference.clone();
+        // touch one v sample, the second byte of each interleaved uv pair
+        distorted[width * height + 1] = 138;
+        let metrics = compare_frames(ColorFormat::NV12, width as _, height as _, &reference, &distorted).unwrap();
+        // planes are y, u, v
+        assert!(metrics.psnr[0].is_infinite() && metrics.psnr[1].is_infinite());
+        let samples = (width / 2 * height / 2) as f64;
+        let expected = 10.0 * (255.0 * 255.0 * samples / 100.0f64).log10();
+        assert!((metrics.psnr[2] - expected).abs() < 1e-9);
+        assert!(metrics.ssim[2] < 1.0);
+    }
+
+    #[test]
+    fn test_ssim_structure() {
+        // an inverted checkerboard keeps mean and variance but destroys structure
+        let size = SSIM_BLOCK_SIZE as usize * 2;
+        let mut reference = Vec::new();
+        let mut distorted = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let v = if (x + y) % 2 == 0 { 40u8 } else { 200u8 };
+                reference.extend_from_slice(&[v, v, v, 255]);
+                distorted.extend_from_slice(&[240 - v, 240 - v, 240 - v, 255]);
+            }
+        }
+        // AYUV memory order is v, u, y, a
+        let metrics = compare_frames(ColorFormat::AYUV, size as _, size as _, &reference, &distorted).unwrap();
+        assert!(metrics.ssim[0] < 0.0, "{:?}", metrics);
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let frame = vec![0u8; 8 * 8 * 4];
+        assert!(compare_frames(ColorFormat::ARGB8UNorm, 8, 8, &frame, &frame[..10]).is_err());
+        assert!(compare_frames(ColorFormat::ARGB16Float, 8, 8, &frame, &frame).is_err());
+        assert!(compare_frames(ColorFormat::NV12, 7, 8, &frame, &frame).is_err());
+    }
+}
+
+/// edge length of the square blocks SSIM is evaluated on.
+pub const SSIM_BLOCK_SIZE: u32 = 8;
+
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Quality of a distorted frame compared to a reference frame. Planes are `[r, g, b]` for
+/// [ARGB8UNorm][ColorFormat::ARGB8UNorm] and [ABGR8UNorm][ColorFormat::ABGR8UNorm], and `[y, u, v]`
+/// for [AYUV][ColorFormat::AYUV] and [NV12][ColorFormat::NV12]. Alpha is ignored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QualityMetrics {
+    /// peak signal to noise ratio in dB per plane. infinite when the plane is identical.
+    pub psnr: [f64; 3],
+    /// structural similarity per plane, `1.0` when the plane is identical.
+    pub ssim: [f64; 3],
+}
+
+/// squared error and SSIM of a single block of one plane.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BlockStats {
+    /// number of samples in the block. `0` for blocks outside the plane.
+    pub count: u32,
+    /// sum of squared differences.
+    pub sse: u64,
+    pub ssim: f64,
+}
+
+impl BlockStats {
+    fn new(reference: &Plane, distorted: &Plane, bx: u32, by: u32) -> Self {
+        let x0 = bx * SSIM_BLOCK_SIZE;
+        let y0 = by * SSIM_BLOCK_SIZE;
+        let x1 = (x0 + SSIM_BLOCK_SIZE).min(reference.width);
+        let y1 = (y0 + SSIM_BLOCK_SIZE).min(reference.height);
+
+        let mut count = 0u32;
+        let mut sse = 0u64;
+        let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let a = reference.get(x, y) as f64;
+                let b = distorted.get(x, y) as f64;
+                let d = reference.get(x, y) as i64 - distorted.get(x, y) as i64;
+                count += 1;
+                sse += (d * d) as u64;
+                sum_a += a;
+                sum_b += b;
+                sum_aa += a * a;
+                sum_bb += b * b;
+                sum_ab += a * b;
+            }
+        }
+        Self {
+            count,
+            sse,
+            ssim: block_ssim(count as f64, sum_a, sum_b, sum_aa, sum_bb, sum_ab),
+        }
+    }
+}
+
+/// SSIM of a block from the sums of its samples, squares and cross products.
+fn block_ssim(n: f64, sum_a: f64, sum_b: f64, sum_aa: f64, sum_bb: f64, sum_ab: f64) -> f64 {
+    let mean_a = sum_a / n;
+    let mean_b = sum_b / n;
+    let var_a = sum_aa / n - mean_a * mean_a;
+    let var_b = sum_bb / n - mean_b * mean_b;
+    let cov = sum_ab / n - mean_a * mean_b;
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2)) /
+        ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+}
+
+/// combine block statistics of the three planes into [QualityMetrics].
+pub(crate) fn aggregate_blocks(planes: [&[BlockStats]; 3]) -> QualityMetrics {
+    let mut metrics = QualityMetrics { psnr: [0.0; 3], ssim: [0.0; 3] };
+    for (i, blocks) in planes.iter().enumerate() {
+        let blocks = blocks.iter().filter(|b| b.count > 0);
+        let (mut samples, mut sse, mut ssim, mut n_blocks) = (0u64, 0u64, 0.0, 0u32);
+        for b in blocks {
+            samples += b.count as u64;
+            sse += b.sse;
+            ssim += b.ssim;
+            n_blocks += 1;
+        }
+        metrics.psnr[i] = if sse == 0 {
+            f64::INFINITY
+        } else {
+            10.0 * (255.0 * 255.0 * samples as f64 / sse as f64).log10()
+        };
+        metrics.ssim[i] = if n_blocks == 0 { 1.0 } else { ssim / n_blocks as f64 };
+    }
+    metrics
+}
+
+/// a single 8 bit plane inside a frame buffer.
+struct Plane<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    offset: usize,
+    /// distance between horizontally adjacent samples in bytes
+    step: usize,
+    pitch: usize,
+}
+
+impl<'a> Plane<'a> {
+    fn get(&self, x: u32, y: u32) -> u8 {
+        self.data[self.offset + y as usize * self.pitch + x as usize * self.step]
+    }
+
+    fn blocks(&self) -> (u32, u32) {
+        ((self.width + SSIM_BLOCK_SIZE - 1) / SSIM_BLOCK_SIZE, (self.height + SSIM_BLOCK_SIZE - 1) / SSIM_BLOCK_SIZE)
+    }
+}
+
+/// planes of a tightly packed frame in the order documented on [QualityMetrics].
+fn split_planes(format: ColorFormat, width: u32, height: u32, data: &[u8]) -> Result<[Plane<'_>; 3]> {
+    let w = width as usize;
+    let h = height as usize;
+    let packed = |offset| Plane { data, width, height, offset, step: 4, pitch: w * 4 };
+    let (expected, planes) = match format {
+        // memory order b, g, r, a
+        ColorFormat::ARGB8UNorm => (w * h * 4, [packed(2), packed(1), packed(0)]),
+        // memory order r, g, b, a
+        ColorFormat::ABGR8UNorm => (w * h * 4, [packed(0), packed(1), packed(2)]),
+        // memory order v, u, y, a
+        ColorFormat::AYUV => (w * h * 4, [packed(2), packed(1), packed(0)]),
+        ColorFormat::NV12 => {
+            if width % 2 != 0 || height % 2 != 0 {
+                return Err(DxFilterErr::BadParam(format!("NV12 frames need even dimensions, found {}x{}", width, height)));
+            }
+            let chroma = |offset| Plane { data, width: width / 2, height: height / 2, offset: w * h + offset, step: 2, pitch: w };
+            (w * h * 3 / 2, [Plane { data, width, height, offset: 0, step: 1, pitch: w }, chroma(0), chroma(1)])
+        }
+        _ => {
+            return Err(DxFilterErr::BadParam(format!("expected ARGB, ABGR, AYUV or NV12 format found {:?}", format)));
+        }
+    };
+    if data.len() != expected {
+        return Err(DxFilterErr::BadParam(format!("expected {} bytes for {:?} {}x{} frame, found {}", expected, format, width, height, data.len())));
+    }
+    Ok(planes)
+}
+
+/// compute PSNR and SSIM of `distorted` against `reference` on the cpu. both buffers hold a
+/// `width` x `height` frame of `format` without row padding; NV12 is the luma plane followed by
+/// the interleaved chroma plane.
+pub fn compare_frames(format: ColorFormat, width: u32, height: u32, reference: &[u8], distorted: &[u8]) -> Result<QualityMetrics> {
+    let ref_planes = split_planes(format, width, height, reference)?;
+    let dist_planes = split_planes(format, width, height, distorted)?;
+
+    let mut stats: [Vec<BlockStats>; 3] = Default::default();
+    for (i, blocks) in stats.iter_mut().enumerate() {
+        let (bw, bh) = ref_planes[i].blocks();
+        for by in 0..bh {
+            for bx in 0..bw {
+                blocks.push(BlockStats::new(&ref_planes[i], &dist_planes[i], bx, by));
+            }
+        }
+    }
+    Ok(aggregate_blocks([&stats[0], &stats[1], &stats[2]]))
+}
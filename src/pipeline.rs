@@ -0,0 +1,414 @@
+//! Chain several filters into a single [FilterPipeline]. The pipeline checks that the output format
+//! of every stage is accepted by the next one, allocates the intermediate textures between stages
+//! and runs all filters with one [apply_filter][DxFilter::apply_filter] call.
+//!
+//! ## Example Usage:
+//! ```
+//! use dxfilter::pipeline::{FilterPipeline, PipelineStage, StageKind};
+//!
+//! // scale, apply a user filter (e.g. a cursor overlay) and convert to NV12 for the encoder.
+//! let pipeline = FilterPipeline::new(&capture_tex, &nv12_tex, vec![
+//!     PipelineStage::new(StageKind::Scale).with_size(1280, 720),
+//!     PipelineStage::new(StageKind::Custom {
+//!         name: "cursor".to_owned(),
+//!         inputs: vec![ColorFormat::ARGB8UNorm],
+//!         output: ColorFormat::ARGB8UNorm,
+//!         build: Box::new(|input, output, device| Ok(Box::new(CursorOverlay::new(input, output, device)?))),
+//!     }),
+//!     PipelineStage::new(StageKind::ToNV12(DitherMode::None)),
+//! ], &device)?;
+//!
+//! pipeline.apply_filter(&ctx)?;
+//! ```
+//!
+use std::mem::discriminant;
+
+use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device4, ID3D11DeviceContext4};
+
+use crate::{ApplyLUT3D, ChromaKeyARGB, ChromaKeyParams, ColorAdjustARGB, ColorAdjustParams, ConvertARGBToAYUV, ConvertARGBToNV12, ConvertARGBToYUV444, ConvertHighBitToARGB8, DitherMode, DxFilter, LutInterpolation, Result, ScaleARGBOrAYUV, SharpenARGB};
+use crate::common_filters::same_desc;
+use crate::error::DxFilterErr;
+use crate::lut::CubeLut;
+use crate::pool::{LeasedTexture, TexturePool};
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+
+    use crate::common_filters::DitherMode;
+    use crate::DxFilter;
+    use crate::pipeline::{plan, FilterPipeline, PipelineStage, StageKind};
+    use crate::utils::{AdapterFactory, create_device_context, create_input_texture, create_output_texture};
+
+    fn desc(width: u32, height: u32, format: ColorFormat) -> TextureDesc {
+        TextureDesc { width, height, format }
+    }
+
+    #[test]
+    fn test_plan_intermediates() {
+        let stages = vec![
+            PipelineStage::new(StageKind::Scale).with_size(1280, 720),
+            PipelineStage::new(StageKind::Sharpen(0.5)),
+            PipelineStage::new(StageKind::ToNV12(DitherMode::None)),
+        ];
+        let descs = plan(desc(1920, 1080, ColorFormat::ARGB8UNorm), desc(1280, 720, ColorFormat::NV12), &stages).unwrap();
+        assert_eq!(descs.len(), 3);
+        assert_eq!((descs[0].width, descs[0].height), (1280, 720));
+        assert!(matches!(descs[0].format, ColorFormat::ARGB8UNorm));
+        assert_eq!((descs[1].width, descs[1].height), (1280, 720));
+        assert!(matches!(descs[2].format, ColorFormat::NV12));
+    }
+
+    #[test]
+    fn test_plan_hdr_input() {
+        let stages = vec![
+            PipelineStage::new(StageKind::HighBitToARGB8(DitherMode::Ordered)),
+            PipelineStage::new(StageKind::ToAYUV(DitherMode::None)),
+        ];
+        let descs = plan(desc(64, 64, ColorFormat::ARGB16Float), desc(64, 64, ColorFormat::AYUV), &stages).unwrap();
+        assert!(matches!(descs[0].format, ColorFormat::ARGB8UNorm));
+    }
+
+    #[test]
+    fn test_plan_errors() {
+        let input = desc(64, 64, ColorFormat::ARGB8UNorm);
+
+        // no stages
+        assert!(plan(input, input, &[]).is_err());
+
+        // NV12 can't be scaled
+        let stages = [PipelineStage::new(StageKind::ToNV12(DitherMode::None)), PipelineStage::new(StageKind::Scale)];
+        let err = plan(input, desc(64, 64, ColorFormat::NV12), &stages).unwrap_err();
+        assert!(format!("{:?}", err).contains("stage 1"), "{:?}", err);
+
+        // last stage can't produce the output format
+        let stages = [PipelineStage::new(StageKind::Scale)];
+        assert!(plan(input, desc(64, 64, ColorFormat::NV12), &stages).is_err());
+
+        // ARGB and ABGR are interchangeable at the output
+        assert!(plan(input, desc(32, 32, ColorFormat::ABGR8UNorm), &stages).is_ok());
+
+        // explicit size on the last stage must match the output
+        let stages = [PipelineStage::new(StageKind::Scale).with_size(16, 16)];
+        assert!(plan(input, desc(32, 32, ColorFormat::ARGB8UNorm), &stages).is_err());
+    }
+
+    #[test]
+    fn test_rebuild_reuses_intermediates() {
+        let adapter = AdapterFactory::new().get_adapter_by_idx(0).unwrap();
+        let (device, _ctx) = create_device_context(&adapter).unwrap();
+
+        let input = create_input_texture(&device, desc(64, 64, ColorFormat::ARGB8UNorm), None).unwrap();
+        let output = create_output_texture(&device, desc(32, 32, ColorFormat::ARGB8UNorm), None).unwrap();
+        let stages = vec![
+            PipelineStage::new(StageKind::Scale).with_size(32, 32),
+            PipelineStage::new(StageKind::Sharpen(0.5)),
+        ];
+        let mut pipeline = FilterPipeline::new(&input, &output, stages, &device).unwrap();
+        let raw = pipeline.intermediates()[0].as_raw_ref().clone();
+
+        // a new input size rebuilds, the 32x32 intermediate is handed back by the pool
+        let other_input = create_input_texture(&device, desc(48, 48, ColorFormat::ARGB8UNorm), None).unwrap();
+        pipeline.set_input_tex(&other_input).unwrap();
+        assert!(pipeline.intermediates()[0].as_raw_ref() == &raw);
+    }
+}
+
+/// builds a user supplied filter for a [StageKind::Custom] stage from its input and output
+/// textures.
+pub type FilterBuilder = Box<dyn Fn(&Texture, &Texture, &ID3D11Device4) -> Result<Box<dyn DxFilter>>>;
+
+/// filter run by a [PipelineStage].
+pub enum StageKind {
+    /// [ScaleARGBOrAYUV]
+    Scale,
+    /// [SharpenARGB] with given strength
+    Sharpen(f32),
+    /// [ColorAdjustARGB]
+    ColorAdjust(ColorAdjustParams),
+    /// [ChromaKeyARGB]
+    ChromaKey(ChromaKeyParams),
+    /// [ApplyLUT3D]
+    Lut3D(CubeLut, LutInterpolation),
+    /// [ConvertHighBitToARGB8] with given dithering
+    HighBitToARGB8(DitherMode),
+    /// [ConvertARGBToAYUV] with given dithering
+    ToAYUV(DitherMode),
+    /// [ConvertARGBToNV12] with given dithering
+    ToNV12(DitherMode),
+    /// [ConvertARGBToYUV444]
+    ToYUV444,
+    /// any other filter, e.g. a cursor overlay implemented outside this crate.
+    Custom {
+        /// used in error messages
+        name: String,
+        /// formats the filter accepts as input
+        inputs: Vec<ColorFormat>,
+        /// format the filter writes
+        output: ColorFormat,
+        build: FilterBuilder,
+    },
+}
+
+const RGB8: [ColorFormat; 2] = [ColorFormat::ARGB8UNorm, ColorFormat::ABGR8UNorm];
+
+impl StageKind {
+    fn name(&self) -> &str {
+        match self {
+            StageKind::Scale => "Scale",
+            StageKind::Sharpen(_) => "Sharpen",
+            StageKind::ColorAdjust(_) => "ColorAdjust",
+            StageKind::ChromaKey(_) => "ChromaKey",
+            StageKind::Lut3D(..) => "Lut3D",
+            StageKind::HighBitToARGB8(_) => "HighBitToARGB8",
+            StageKind::ToAYUV(_) => "ToAYUV",
+            StageKind::ToNV12(_) => "ToNV12",
+            StageKind::ToYUV444 => "ToYUV444",
+            StageKind::Custom { name, .. } => name,
+        }
+    }
+
+    /// formats accepted as input.
+    fn inputs(&self) -> Vec<ColorFormat> {
+        match self {
+            StageKind::Scale | StageKind::Sharpen(_) => {
+                vec![ColorFormat::ARGB8UNorm, ColorFormat::ABGR8UNorm, ColorFormat::AYUV]
+            }
+            StageKind::ColorAdjust(_) | StageKind::ChromaKey(_) | StageKind::Lut3D(..) | StageKind::ToYUV444 => {
+                RGB8.to_vec()
+            }
+            StageKind::HighBitToARGB8(_) => vec![ColorFormat::ARGB10UNorm, ColorFormat::ARGB16Float],
+            StageKind::ToAYUV(_) | StageKind::ToNV12(_) => {
+                vec![ColorFormat::ARGB8UNorm, ColorFormat::ABGR8UNorm, ColorFormat::ARGB10UNorm]
+            }
+            StageKind::Custom { inputs, .. } => inputs.clone(),
+        }
+    }
+
+    /// formats the stage can write for given input, preferred one first.
+    fn outputs(&self, input: ColorFormat) -> Vec<ColorFormat> {
+        match self {
+            StageKind::Scale | StageKind::Sharpen(_) if matches!(input, ColorFormat::AYUV) => vec![ColorFormat::AYUV],
+            StageKind::Scale | StageKind::Sharpen(_) | StageKind::ColorAdjust(_) |
+            StageKind::ChromaKey(_) | StageKind::Lut3D(..) => {
+                if matches!(input, ColorFormat::ABGR8UNorm) {
+                    vec![ColorFormat::ABGR8UNorm, ColorFormat::ARGB8UNorm]
+                } else {
+                    RGB8.to_vec()
+                }
+            }
+            StageKind::HighBitToARGB8(_) => RGB8.to_vec(),
+            StageKind::ToAYUV(_) => vec![ColorFormat::AYUV],
+            StageKind::ToNV12(_) => vec![ColorFormat::NV12],
+            StageKind::ToYUV444 => vec![ColorFormat::YUV444],
+            StageKind::Custom { output, .. } => vec![*output],
+        }
+    }
+
+    fn build(&self, input: &Texture, output: &Texture, device: &ID3D11Device4) -> Result<Box<dyn DxFilter>> {
+        Ok(match self {
+            StageKind::Scale => Box::new(ScaleARGBOrAYUV::new(input, output, device)?),
+            StageKind::Sharpen(strength) => Box::new(SharpenARGB::new(input, output, *strength, device)?),
+            StageKind::ColorAdjust(params) => {
+                let mut filter = ColorAdjustARGB::new(input, output, device)?;
+                filter.set_params(*params)?;
+                Box::new(filter)
+            }
+            StageKind::ChromaKey(params) => Box::new(ChromaKeyARGB::new(input, output, *params, device)?),
+            StageKind::Lut3D(lut, interpolation) => Box::new(ApplyLUT3D::new(input, output, lut, *interpolation, device)?),
+            StageKind::HighBitToARGB8(dither) => {
+                let mut filter = ConvertHighBitToARGB8::new(input, output, device)?;
+                filter.set_dither(*dither);
+                Box::new(filter)
+            }
+            StageKind::ToAYUV(dither) => {
+                let mut filter = ConvertARGBToAYUV::new(input, output, device)?;
+                filter.set_dither(*dither);
+                Box::new(filter)
+            }
+            StageKind::ToNV12(dither) => {
+                let mut filter = ConvertARGBToNV12::new(input, output, device)?;
+                filter.set_dither(*dither);
+                Box::new(filter)
+            }
+            StageKind::ToYUV444 => Box::new(ConvertARGBToYUV444::new(input, output, device)?),
+            StageKind::Custom { build, .. } => build(input, output, device)?,
+        })
+    }
+}
+
+/// a single step of a [FilterPipeline].
+pub struct PipelineStage {
+    kind: StageKind,
+    size: Option<(u32, u32)>,
+}
+
+impl PipelineStage {
+    /// create a stage which writes at the size of the previous stage. the last stage always writes
+    /// at the size of the pipeline output.
+    pub fn new(kind: StageKind) -> Self {
+        Self { kind, size: None }
+    }
+
+    /// make the stage write a `width` x `height` texture. filters scale automatically, so this is
+    /// where the resolution changes within a pipeline.
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// filter run by this stage.
+    pub fn kind(&self) -> &StageKind {
+        &self.kind
+    }
+}
+
+fn contains(formats: &[ColorFormat], format: ColorFormat) -> bool {
+    formats.iter().any(|f| discriminant(f) == discriminant(&format))
+}
+
+/// check that `stages` can turn `input` into `output` and return the texture written by every
+/// stage. the last entry is always `output`.
+pub fn plan(input: TextureDesc, output: TextureDesc, stages: &[PipelineStage]) -> Result<Vec<TextureDesc>> {
+    if stages.is_empty() {
+        return Err(DxFilterErr::BadParam("pipeline needs at least one stage".to_owned()));
+    }
+    let mut descs = Vec::with_capacity(stages.len());
+    let mut current = input;
+    for (i, stage) in stages.iter().enumerate() {
+        let kind = &stage.kind;
+        if !contains(&kind.inputs(), current.format) {
+            return Err(DxFilterErr::BadParam(format!("stage {} ({}) does not accept {:?} input, expected one of {:?}",
+                                                     i, kind.name(), current.format, kind.inputs())));
+        }
+        let outputs = kind.outputs(current.format);
+        let next = if i + 1 == stages.len() {
+            if !contains(&outputs, output.format) {
+                return Err(DxFilterErr::BadParam(format!("last stage ({}) can't write {:?} output, expected one of {:?}",
+                                                         kind.name(), output.format, outputs)));
+            }
+            if let Some((width, height)) = stage.size {
+                if (width, height) != (output.width, output.height) {
+                    return Err(DxFilterErr::BadParam(format!("last stage ({}) has size {}x{} but output is {}x{}",
+                                                             kind.name(), width, height, output.width, output.height)));
+                }
+            }
+            output
+        } else {
+            let (width, height) = stage.size.unwrap_or((current.width, current.height));
+            TextureDesc { width, height, format: outputs[0] }
+        };
+        descs.push(next);
+        current = next;
+    }
+    Ok(descs)
+}
+
+/// A chain of filters run as one. Built from the input and output textures and a list of
+/// [PipelineStage]s. Formats are validated and intermediate textures are allocated when the
+/// pipeline is created, see [plan] for the rules.
+///
+/// [set_input_tex][DxFilter::set_input_tex] and [set_output_tex][DxFilter::set_output_tex] only
+/// rebuild the pipeline when the size or format of the texture changes. Intermediate textures come
+/// from a [TexturePool], so switching back and forth between resolutions doesn't allocate once the
+/// pool is warm. Pipelines can share a pool with [with_pool][FilterPipeline::with_pool].
+pub struct FilterPipeline {
+    device: ID3D11Device4,
+    pool: TexturePool,
+    stages: Vec<PipelineStage>,
+    filters: Vec<Box<dyn DxFilter>>,
+    intermediates: Vec<LeasedTexture>,
+
+    _in_tex: Texture,
+    _out_tex: Texture,
+}
+
+impl FilterPipeline {
+    /// create new pipeline which runs `stages` in order, reading from `input_tex` and writing to
+    /// `out_tex`.
+    pub fn new(input_tex: &Texture, out_tex: &Texture, stages: Vec<PipelineStage>, device: &ID3D11Device4) -> Result<Self> {
+        return Self::with_pool(input_tex, out_tex, stages, &TexturePool::new(device), device);
+    }
+
+    /// same as [new][FilterPipeline::new] but takes intermediate textures from `pool`.
+    pub fn with_pool(input_tex: &Texture, out_tex: &Texture, stages: Vec<PipelineStage>, pool: &TexturePool, device: &ID3D11Device4) -> Result<Self> {
+        let (filters, intermediates) = Self::build(input_tex, out_tex, &stages, pool, device)?;
+        return Ok(Self {
+            device: device.clone(),
+            pool: pool.clone(),
+            stages,
+            filters,
+            intermediates,
+            _in_tex: input_tex.clone(),
+            _out_tex: out_tex.clone(),
+        });
+    }
+
+    /// stages of this pipeline.
+    pub fn stages(&self) -> &[PipelineStage] {
+        return &self.stages;
+    }
+
+    /// textures between stages. entry `i` is written by stage `i` and read by stage `i + 1`.
+    pub fn intermediates(&self) -> &[LeasedTexture] {
+        return &self.intermediates;
+    }
+
+    fn build(input_tex: &Texture, out_tex: &Texture, stages: &[PipelineStage], pool: &TexturePool, device: &ID3D11Device4) -> Result<(Vec<Box<dyn DxFilter>>, Vec<LeasedTexture>)> {
+        let descs = plan(input_tex.desc(), out_tex.desc(), stages)?;
+
+        let mut intermediates = Vec::with_capacity(descs.len() - 1);
+        for desc in &descs[..descs.len() - 1] {
+            intermediates.push(pool.acquire_intermediate(*desc)?);
+        }
+
+        let mut filters = Vec::with_capacity(stages.len());
+        for (i, stage) in stages.iter().enumerate() {
+            let input: &Texture = if i == 0 { input_tex } else { &intermediates[i - 1] };
+            let output: &Texture = if i + 1 == stages.len() { out_tex } else { &intermediates[i] };
+            filters.push(stage.kind.build(input, output, device)?);
+        }
+        Ok((filters, intermediates))
+    }
+
+    fn rebuild(&mut self, input_tex: &Texture, out_tex: &Texture) -> Result<()> {
+        // return the old intermediates to the pool first so a rebuild of the same size reuses them
+        self.filters.clear();
+        self.intermediates.clear();
+        (self.filters, self.intermediates) = Self::build(input_tex, out_tex, &self.stages, &self.pool, &self.device)?;
+        self._in_tex = input_tex.clone();
+        self._out_tex = out_tex.clone();
+        return Ok(());
+    }
+}
+
+impl DxFilter for FilterPipeline {
+    fn apply_filter(&self, ctx: &ID3D11DeviceContext4) -> Result<()> {
+        for filter in &self.filters {
+            filter.apply_filter(ctx)?;
+        }
+        return Ok(());
+    }
+
+    fn set_input_tex(&mut self, tex: &Texture) -> Result<()> {
+        // no filters are left after a failed rebuild, build them again
+        if !self.filters.is_empty() && same_desc(&tex.desc(), &self._in_tex.desc()) {
+            self.filters[0].set_input_tex(tex)?;
+            self._in_tex = tex.clone();
+            return Ok(());
+        }
+        let out_tex = self._out_tex.clone();
+        return self.rebuild(tex, &out_tex);
+    }
+
+    fn set_output_tex(&mut self, tex: &Texture) -> Result<()> {
+        if !self.filters.is_empty() && same_desc(&tex.desc(), &self._out_tex.desc()) {
+            self.filters.last_mut().unwrap().set_output_tex(tex)?;
+            self._out_tex = tex.clone();
+            return Ok(());
+        }
+        let in_tex = self._in_tex.clone();
+        return self.rebuild(&in_tex, tex);
+    }
+}
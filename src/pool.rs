@@ -0,0 +1,240 @@
+//! Pool of textures which are handed out as [LeasedTexture]s and recycled when the lease is
+//! dropped. Useful when intermediate textures are re-created often, e.g. by a
+//! [FilterPipeline][crate::pipeline::FilterPipeline] during resolution switches.
+//!
+//! ## Example Usage:
+//! ```
+//! let pool = TexturePool::new(&device);
+//!
+//! let tex = pool.acquire_intermediate(TextureDesc { width: 1280, height: 720, format: ColorFormat::ARGB8UNorm })?;
+//! // use `tex` like a `Texture`
+//! drop(tex);
+//!
+//! // no allocation, the texture from above is handed out again
+//! let tex = pool.acquire_intermediate(TextureDesc { width: 1280, height: 720, format: ColorFormat::ARGB8UNorm })?;
+//! ```
+//!
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem::{discriminant, Discriminant};
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+
+use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
+use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_FLAG, D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_USAGE_DEFAULT, ID3D11Device4};
+
+use crate::Result;
+use crate::utils::create_texture;
+
+#[cfg(test)]
+mod test {
+    use win_desktop_duplication::texture::{ColorFormat, TextureDesc};
+    use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE};
+
+    use crate::pool::{PoolKey, Shelves, TexturePool, DEFAULT_MAX_IDLE};
+    use crate::utils::{AdapterFactory, create_device_context};
+
+    fn desc(width: u32, height: u32, format: ColorFormat) -> TextureDesc {
+        TextureDesc { width, height, format }
+    }
+
+    #[test]
+    fn test_keys() {
+        let rt = D3D11_BIND_RENDER_TARGET;
+        let key = PoolKey::new(desc(64, 64, ColorFormat::ARGB8UNorm), rt);
+        assert!(key == PoolKey::new(desc(64, 64, ColorFormat::ARGB8UNorm), rt));
+        assert!(key != PoolKey::new(desc(64, 64, ColorFormat::ABGR8UNorm), rt));
+        assert!(key != PoolKey::new(desc(64, 32, ColorFormat::ARGB8UNorm), rt));
+        assert!(key != PoolKey::new(desc(64, 64, ColorFormat::ARGB8UNorm), D3D11_BIND_SHADER_RESOURCE));
+    }
+
+    #[test]
+    fn test_shelves() {
+        let mut shelves = Shelves::new(2);
+        assert_eq!(shelves.take(&1), None);
+
+        shelves.put(1, "a");
+        shelves.put(1, "b");
+        // over the limit, dropped
+        shelves.put(1, "c");
+        shelves.put(2, "d");
+        assert_eq!(shelves.idle(), 3);
+
+        assert_eq!(shelves.take(&1), Some("b"));
+        assert_eq!(shelves.take(&1), Some("a"));
+        assert_eq!(shelves.take(&1), None);
+
+        shelves.clear();
+        assert_eq!(shelves.idle(), 0);
+        assert_eq!(DEFAULT_MAX_IDLE, 4);
+    }
+
+    #[test]
+    fn test_texture_pool() {
+        let adapter = AdapterFactory::new().get_adapter_by_idx(0).unwrap();
+        let (device, _ctx) = create_device_context(&adapter).unwrap();
+
+        let pool = TexturePool::new(&device);
+        let hd = desc(1280, 720, ColorFormat::ARGB8UNorm);
+
+        let first = pool.acquire_intermediate(hd).unwrap();
+        let raw = first.as_raw_ref().clone();
+        assert_eq!(pool.idle(), 0);
+        drop(first);
+        assert_eq!(pool.idle(), 1);
+
+        // same description gets the same texture back
+        let second = pool.acquire_intermediate(hd).unwrap();
+        assert!(second.as_raw_ref() == &raw);
+        // different description allocates
+        let other = pool.acquire_intermediate(desc(640, 360, ColorFormat::ARGB8UNorm)).unwrap();
+        assert!(other.as_raw_ref() != &raw);
+
+        // leases outliving the pool just release their texture
+        drop(pool);
+        drop(second);
+    }
+}
+
+/// number of idle textures kept per description unless changed with [TexturePool::set_max_idle].
+pub const DEFAULT_MAX_IDLE: usize = 4;
+
+/// textures are interchangeable when size, format and bind flags match.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+struct PoolKey {
+    width: u32,
+    height: u32,
+    format: Discriminant<ColorFormat>,
+    bind_flags: i32,
+}
+
+impl PoolKey {
+    fn new(desc: TextureDesc, bind_flags: D3D11_BIND_FLAG) -> Self {
+        Self {
+            width: desc.width,
+            height: desc.height,
+            format: discriminant(&desc.format),
+            bind_flags: bind_flags.0,
+        }
+    }
+}
+
+/// idle items grouped by key, at most `max_idle` per key.
+struct Shelves<K, T> {
+    free: HashMap<K, Vec<T>>,
+    max_idle: usize,
+}
+
+impl<K: Hash + Eq, T> Shelves<K, T> {
+    fn new(max_idle: usize) -> Self {
+        Self { free: HashMap::new(), max_idle }
+    }
+
+    fn take(&mut self, key: &K) -> Option<T> {
+        self.free.get_mut(key).and_then(|items| items.pop())
+    }
+
+    fn put(&mut self, key: K, item: T) {
+        let items = self.free.entry(key).or_default();
+        if items.len() < self.max_idle {
+            items.push(item);
+        }
+    }
+
+    fn idle(&self) -> usize {
+        self.free.values().map(|items| items.len()).sum()
+    }
+
+    fn set_max_idle(&mut self, max_idle: usize) {
+        self.max_idle = max_idle;
+        for items in self.free.values_mut() {
+            items.truncate(max_idle);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.free.clear();
+    }
+}
+
+/// Hands out textures keyed by [TextureDesc] and bind flags and takes them back when the
+/// [LeasedTexture] is dropped. Cloning the pool is cheap and shares the idle textures.
+///
+/// The pool is single threaded like the immediate device context the filters run on.
+#[derive(Clone)]
+pub struct TexturePool {
+    device: ID3D11Device4,
+    shelves: Rc<RefCell<Shelves<PoolKey, Texture>>>,
+}
+
+impl TexturePool {
+    /// create an empty pool allocating textures on `device`.
+    pub fn new(device: &ID3D11Device4) -> Self {
+        Self {
+            device: device.clone(),
+            shelves: Rc::new(RefCell::new(Shelves::new(DEFAULT_MAX_IDLE))),
+        }
+    }
+
+    /// get an idle texture matching `desc` and `bind_flags` or allocate a new one. contents of a
+    /// recycled texture are undefined.
+    pub fn acquire(&self, desc: TextureDesc, bind_flags: D3D11_BIND_FLAG) -> Result<LeasedTexture> {
+        let key = PoolKey::new(desc, bind_flags);
+        let recycled = self.shelves.borrow_mut().take(&key);
+        let tex = match recycled {
+            Some(tex) => tex,
+            None => create_texture(&self.device, desc, D3D11_USAGE_DEFAULT, bind_flags, Default::default(), None)?,
+        };
+        Ok(LeasedTexture {
+            tex: Some(tex),
+            key,
+            shelves: Rc::downgrade(&self.shelves),
+        })
+    }
+
+    /// [acquire][TexturePool::acquire] a texture which can be both the output of one filter and the
+    /// input of the next, like [create_intermediate_texture][crate::utils::create_intermediate_texture].
+    pub fn acquire_intermediate(&self, desc: TextureDesc) -> Result<LeasedTexture> {
+        self.acquire(desc, D3D11_BIND_FLAG(D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_SHADER_RESOURCE.0))
+    }
+
+    /// number of textures waiting to be reused.
+    pub fn idle(&self) -> usize {
+        self.shelves.borrow().idle()
+    }
+
+    /// change how many idle textures are kept per description. extra idle textures are released.
+    pub fn set_max_idle(&self, max_idle: usize) {
+        self.shelves.borrow_mut().set_max_idle(max_idle);
+    }
+
+    /// release all idle textures. leased textures are not affected.
+    pub fn clear(&self) {
+        self.shelves.borrow_mut().clear();
+    }
+}
+
+/// A texture borrowed from a [TexturePool]. Dereferences to [Texture] and goes back to the pool
+/// when dropped. If the pool is gone by then, the texture is released.
+pub struct LeasedTexture {
+    tex: Option<Texture>,
+    key: PoolKey,
+    shelves: Weak<RefCell<Shelves<PoolKey, Texture>>>,
+}
+
+impl Deref for LeasedTexture {
+    type Target = Texture;
+
+    fn deref(&self) -> &Texture {
+        self.tex.as_ref().unwrap()
+    }
+}
+
+impl Drop for LeasedTexture {
+    fn drop(&mut self) {
+        if let (Some(tex), Some(shelves)) = (self.tex.take(), self.shelves.upgrade()) {
+            shelves.borrow_mut().put(self.key, tex);
+        }
+    }
+}
@@ -1,10 +1,280 @@
-//! Contains utils for creating Vertex and Pixel shaders from compiled shader byte code.
-//! you can easily use [`generate_shader!`] macro instead of these structs directly.
+//! Contains utils for creating Vertex, Pixel, Compute, Geometry, Hull and Domain shaders from
+//! compiled shader byte code. you can easily use [`generate_shader!`] macro instead of these
+//! structs directly.
 //!
-use windows::Win32::Graphics::Direct3D11::{ID3D11Device4, ID3D11PixelShader, ID3D11VertexShader};
+//! Shaders that are only known at runtime, e.g. user written effects, can be compiled with
+//! [compile_runtime] and passed to the `new` functions of these structs.
+//!
+//! [ShaderReflection] lists the registers and constant buffers a shader uses, so filters can check
+//! them against what they bind with [ShaderReflectionExt] before the first draw.
+//!
+use std::ffi::CString;
+use std::fs::read_to_string;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+
+use windows::Win32::Graphics::Direct3D11::{ID3D11ComputeShader, ID3D11Device4, ID3D11DomainShader, ID3D11GeometryShader, ID3D11HullShader, ID3D11PixelShader, ID3D11VertexShader};
+use crate::constant_buffer::ShaderConstants;
 use crate::Result;
 use crate::error::DxFilterErr;
 
+// shared with `compile_shader!` so runtime and build time compilation behave the same
+use shader_common::{fxc, hlsl_log, reflection};
+
+pub use shader_common::hlsl_log::HlslMessage;
+pub use shader_common::reflection::{BoundResource, ConstantBufferLayout, ConstantVariable, ResourceKind, ShaderReflection, SignatureElement};
+
+#[cfg(test)]
+mod test {
+    use crate::error::DxFilterErr;
+    use crate::constant_buffer::ShaderConstants;
+    use crate::shader::{compile_runtime, compile_runtime_file, CompileOptions, PixelShader, ResourceKind, ShaderReflection, ShaderReflectionExt};
+    use crate::utils::{AdapterFactory, create_device_context};
+
+    const SRC: &str = "float4 main() : SV_Target
+{
+    return float4(TINT, 1.0);
+}
+";
+
+    #[test]
+    fn test_compile_runtime() {
+        let adapter = AdapterFactory::new().get_adapter_by_idx(0).unwrap();
+        let (device, _ctx) = create_device_context(&adapter).unwrap();
+
+        let options = CompileOptions { defines: &[("TINT", "1.0, 0.5, 0.0")], ..Default::default() };
+        let blob = compile_runtime(SRC, "main", "ps_5_0", &options).unwrap();
+        PixelShader::new(&blob, device).unwrap();
+
+        // TINT undefined
+        match compile_runtime(SRC, "main", "ps_5_0", &CompileOptions::default()) {
+            Err(DxFilterErr::ShaderCompile(e)) => {
+                assert_eq!(e.errors.len(), 1, "{}", e.log);
+                assert_eq!(e.errors[0].line, 3);
+                assert!(e.errors[0].message.contains("TINT"), "{}", e.log);
+            }
+            _ => panic!("expected compile error"),
+        }
+        assert!(matches!(compile_runtime(SRC, "ma\0in", "ps_5_0", &CompileOptions::default()), Err(DxFilterErr::BadParam(_))));
+    }
+
+    #[test]
+    fn test_compile_runtime_includes() {
+        let dir = std::env::temp_dir().join(format!("dxfilter_includes_{}", std::process::id()));
+        let shared = dir.join("shared");
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(shared.join("strength.hlsli"), "#define STRENGTH 0.5\n").unwrap();
+        std::fs::write(dir.join("main.hlsl"), "#include \"tint.hlsli\"\n#include \"strength.hlsli\"\n\
+float4 main() : SV_Target { return float4(TINT * STRENGTH, 1.0); }\n").unwrap();
+
+        // in-memory files first, then the include directories
+        let options = CompileOptions {
+            include_dirs: &[shared.as_path()],
+            includes: &[("tint.hlsli", "#define TINT float3(1.0, 0.5, 0.0)")],
+            ..Default::default()
+        };
+        compile_runtime_file(dir.join("main.hlsl"), "main", "ps_5_0", &options).unwrap();
+        let src = std::fs::read_to_string(dir.join("main.hlsl")).unwrap();
+        compile_runtime(&src, "main", "ps_5_0", &options).unwrap();
+
+        // without the include directory strength.hlsli can't be found
+        let options = CompileOptions { include_dirs: &[], ..options };
+        assert!(matches!(compile_runtime(&src, "main", "ps_5_0", &options), Err(DxFilterErr::ShaderCompile(_))));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, ShaderConstants)]
+    struct Tint {
+        color: [f32; 3],
+        strength: f32,
+    }
+
+    #[test]
+    fn test_reflection() {
+        let src = "Texture2D tx : register(t1);
+SamplerState smp : register(s0);
+cbuffer Tint : register(b2) { float3 color; float strength; };
+float4 main(float4 pos : SV_Position, float2 uv : TEXCOORD0) : SV_Target
+{
+    return tx.Sample(smp, uv) * float4(color, strength);
+}
+";
+        let reflection = ShaderReflection::from_bytecode(&compile_runtime(src, "main", "ps_5_0", &CompileOptions::default()).unwrap()).unwrap();
+        let tx = reflection.resource("tx").unwrap();
+        assert_eq!((tx.kind, tx.slot, tx.count), (ResourceKind::Texture, 1, 1));
+        let tint = reflection.constant_buffer("Tint").unwrap();
+        assert_eq!(tint.size, 16);
+        assert_eq!(tint.variables.iter().map(|v| (v.name.as_str(), v.offset)).collect::<Vec<_>>(), vec![("color", 0), ("strength", 12)]);
+        assert_eq!(reflection.inputs.iter().map(|e| e.semantic.as_str()).collect::<Vec<_>>(), vec!["SV_Position", "TEXCOORD"]);
+        assert_eq!(reflection.outputs.len(), 1);
+
+        let bindings = [(ResourceKind::Texture, 1), (ResourceKind::Sampler, 0), (ResourceKind::ConstantBuffer, 2)];
+        reflection.check_bindings(&bindings).unwrap();
+        reflection.check_constants::<Tint>(2).unwrap();
+        // texture bound to t0 instead of t1
+        match reflection.check_bindings(&[(ResourceKind::Texture, 0), (ResourceKind::Sampler, 0), (ResourceKind::ConstantBuffer, 2)]) {
+            Err(DxFilterErr::BadParam(e)) => assert!(e.contains("`tx` at register t1"), "{}", e),
+            _ => panic!("expected a missing binding"),
+        }
+        assert!(reflection.check_constants::<Tint>(0).is_err());
+
+        assert!(ShaderReflection::from_bytecode(&[0; 16]).is_err());
+    }
+}
+
+/// error of [compile_runtime].
+#[derive(Clone, Debug)]
+pub struct ShaderCompileErr {
+    /// errors with their location in the source. empty when the log couldn't be parsed.
+    pub errors: Vec<HlslMessage>,
+    /// full output of the compiler.
+    pub log: String,
+}
+
+/// defines and `#include` sources of [compile_runtime], the runtime counterparts of the
+/// `defines`, `include_dirs` and `includes` keys of [`compile_shader!`][crate::compile_shader].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompileOptions<'a> {
+    /// `(name, value)` pairs defined before compiling.
+    pub defines: &'a [(&'a str, &'a str)],
+    /// searched for `#include` after the directory of the including file. relative paths are
+    /// relative to the current directory.
+    pub include_dirs: &'a [&'a Path],
+    /// in-memory files as `(include name, content)`, searched before the file system.
+    pub includes: &'a [(&'a str, &'a str)],
+}
+
+fn c_string(value: &str, what: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| DxFilterErr::BadParam(format!("{} can't contain nul bytes", what)))
+}
+
+fn compile(src: &str, src_name: Option<&str>, entry_point: &str, target: &str, options: &CompileOptions) -> Result<Vec<u8>> {
+    let src_name_c = src_name.map(|name| c_string(name, "source name")).transpose()?;
+    let entry_point = c_string(entry_point, "entry point")?;
+    let target = c_string(target, "target")?;
+    let mut owned_defines = Vec::with_capacity(options.defines.len());
+    for (name, def) in options.defines {
+        owned_defines.push((c_string(name, "define")?, c_string(def, "define")?));
+    }
+    let defines: Vec<_> = owned_defines.iter().map(|(name, def)| (name.as_c_str(), def.as_c_str())).collect();
+
+    let root = src_name.and_then(|name| Path::new(name).parent()).map(Path::to_path_buf).unwrap_or_default();
+    let files = options.includes.iter().map(|(name, content)| (name.to_string(), content.as_bytes().to_vec())).collect();
+    let include_dirs = options.include_dirs.iter().map(|dir| dir.to_path_buf()).collect::<Vec<PathBuf>>();
+    let include = fxc::IncludeHandler::new(files, include_dirs, root);
+    let input = fxc::FxcInput {
+        src: src.as_bytes(),
+        src_name: src_name_c.as_deref(),
+        defines: &defines,
+        entry_point: &entry_point,
+        target: &target,
+        flags1: 0,
+        flags2: 0,
+        secondary_data_flags: 0,
+        secondary_data: &[],
+        include: &include,
+    };
+    return fxc::compile(&input).map_err(|log| DxFilterErr::ShaderCompile(ShaderCompileErr {
+        errors: hlsl_log::parse_log(&log),
+        log,
+    }));
+}
+
+/// compile HLSL `src` at runtime, the same way [`compile_shader!`][crate::compile_shader] does at
+/// build time. `options` holds the defines, include directories and in-memory includes, see
+/// [CompileOptions]. `#include` is resolved against the current directory before the include
+/// directories; use [compile_runtime_file] for sources with includes next to them.
+///
+/// returns the bytecode, to be passed to e.g. [PixelShader::new], or
+/// [DxFilterErr::ShaderCompile] with the line and column of every error.
+///
+/// ## Example Usage:
+/// ```ignore
+/// let options = CompileOptions { defines: &[("STRENGTH", "0.5")], ..Default::default() };
+/// let blob = compile_runtime(user_src, "main", "ps_5_0", &options)?;
+/// let ps = PixelShader::new(&blob, device.clone())?;
+/// ```
+pub fn compile_runtime(src: &str, entry_point: &str, target: &str, options: &CompileOptions) -> Result<Vec<u8>> {
+    return compile(src, None, entry_point, target, options);
+}
+
+/// same as [compile_runtime] but reads the source from `path`. `#include` is resolved against the
+/// directory of `path` and errors refer to it by name, like `src_file` of [`compile_shader!`][crate::compile_shader].
+pub fn compile_runtime_file(path: impl AsRef<Path>, entry_point: &str, target: &str, options: &CompileOptions) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let src = match read_to_string(path) {
+        Ok(src) => src,
+        Err(e) => {
+            return Err(DxFilterErr::BadParam(format!("failed to read shader source {}. {}", path.display(), e)));
+        }
+    };
+    return compile(&src, Some(&path.to_string_lossy()), entry_point, target, options);
+}
+
+/// loading and checks of [ShaderReflection] that report [DxFilterErr]s.
+pub trait ShaderReflectionExt: Sized {
+    /// read the reflection data of compiled `bytecode`, e.g. from [compile_runtime]. Shaders
+    /// compiled by [`compile_shader!`][crate::compile_shader] get it with `reflect: true` instead.
+    fn from_bytecode(bytecode: &[u8]) -> Result<Self>;
+
+    /// check that every register the shader uses is in `bindings`, given as the kind and slot the
+    /// filter binds, e.g. `(ResourceKind::Texture, 0)` for `PSSetShaderResources(0, ..)`. Bindings
+    /// the shader doesn't use are fine, the compiler drops resources that don't affect the output.
+    fn check_bindings(&self, bindings: &[(ResourceKind, u32)]) -> Result<()>;
+
+    /// check that the `cbuffer` at register `b{slot}` has the size of `T`, i.e. that a
+    /// [ConstantBuffer][crate::constant_buffer::ConstantBuffer] of `T` bound there matches it.
+    fn check_constants<T: ShaderConstants>(&self, slot: u32) -> Result<()>;
+}
+
+impl ShaderReflectionExt for ShaderReflection {
+    fn from_bytecode(bytecode: &[u8]) -> Result<Self> {
+        return reflection::parse(bytecode).map_err(DxFilterErr::BadParam);
+    }
+
+    fn check_bindings(&self, bindings: &[(ResourceKind, u32)]) -> Result<()> {
+        for resource in &self.resources {
+            for slot in resource.slot..resource.slot + resource.count {
+                if !bindings.contains(&(resource.kind, slot)) {
+                    return Err(DxFilterErr::BadParam(format!("shader uses `{}` at register {}{} which is not bound",
+                                                             resource.name, resource.kind.register(), slot)));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn check_constants<T: ShaderConstants>(&self, slot: u32) -> Result<()> {
+        let layout = self.resources.iter()
+            .find(|r| r.kind == ResourceKind::ConstantBuffer && r.slot == slot)
+            .and_then(|r| self.constant_buffer(&r.name));
+        let layout = match layout {
+            Some(layout) => layout,
+            None => {
+                return Err(DxFilterErr::BadParam(format!("shader has no cbuffer at register b{} for {}", slot, T::NAME)));
+            }
+        };
+        if layout.size as usize != size_of::<T>() {
+            return Err(DxFilterErr::BadParam(format!("cbuffer `{}` at register b{} is {} bytes but {} is {} bytes",
+                                                     layout.name, slot, layout.size, T::NAME, size_of::<T>())));
+        }
+        return Ok(());
+    }
+}
+
+/// pipeline stage a resource is bound to, e.g. by [ConstantBuffer::bind][crate::constant_buffer::ConstantBuffer::bind].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    Pixel,
+    Compute,
+    Geometry,
+    Hull,
+    Domain,
+}
+
 /// structure to hold `ID3D11VertexShader`. Create new shader using [`generate_shader!`]
 pub struct VertexShader(ID3D11VertexShader);
 
@@ -42,3 +312,79 @@ impl PixelShader {
         return &self.0;
     }
 }
+
+/// structure to hold `ID3D11ComputeShader`. Create new instance using [`generate_shader!`]
+pub struct ComputeShader(ID3D11ComputeShader);
+
+impl ComputeShader {
+    /// create a new ComputeShader instance from compiled shader blob and directx device.
+    pub fn new(blob: &[u8], device: ID3D11Device4) -> Result<Self> {
+        let mut shader = None;
+        if let Err(e) = unsafe { device.CreateComputeShader(blob, None, Some(&mut shader)) } {
+            return Err(DxFilterErr::Unknown(format!("{:?}", e)));
+        }
+        return Ok(Self(shader.unwrap()));
+    }
+
+    /// get raw reference to `ID3D11ComputeShader` instance.
+    pub fn as_raw_ref(&self) -> &ID3D11ComputeShader {
+        return &self.0;
+    }
+}
+
+/// structure to hold `ID3D11GeometryShader`. Create new instance using [`generate_shader!`]
+pub struct GeometryShader(ID3D11GeometryShader);
+
+impl GeometryShader {
+    /// create a new GeometryShader instance from compiled shader blob and directx device.
+    pub fn new(blob: &[u8], device: ID3D11Device4) -> Result<Self> {
+        let mut shader = None;
+        if let Err(e) = unsafe { device.CreateGeometryShader(blob, None, Some(&mut shader)) } {
+            return Err(DxFilterErr::Unknown(format!("{:?}", e)));
+        }
+        return Ok(Self(shader.unwrap()));
+    }
+
+    /// get raw reference to `ID3D11GeometryShader` instance.
+    pub fn as_raw_ref(&self) -> &ID3D11GeometryShader {
+        return &self.0;
+    }
+}
+
+/// structure to hold `ID3D11HullShader`. Create new instance using [`generate_shader!`]
+pub struct HullShader(ID3D11HullShader);
+
+impl HullShader {
+    /// create a new HullShader instance from compiled shader blob and directx device.
+    pub fn new(blob: &[u8], device: ID3D11Device4) -> Result<Self> {
+        let mut shader = None;
+        if let Err(e) = unsafe { device.CreateHullShader(blob, None, Some(&mut shader)) } {
+            return Err(DxFilterErr::Unknown(format!("{:?}", e)));
+        }
+        return Ok(Self(shader.unwrap()));
+    }
+
+    /// get raw reference to `ID3D11HullShader` instance.
+    pub fn as_raw_ref(&self) -> &ID3D11HullShader {
+        return &self.0;
+    }
+}
+
+/// structure to hold `ID3D11DomainShader`. Create new instance using [`generate_shader!`]
+pub struct DomainShader(ID3D11DomainShader);
+
+impl DomainShader {
+    /// create a new DomainShader instance from compiled shader blob and directx device.
+    pub fn new(blob: &[u8], device: ID3D11Device4) -> Result<Self> {
+        let mut shader = None;
+        if let Err(e) = unsafe { device.CreateDomainShader(blob, None, Some(&mut shader)) } {
+            return Err(DxFilterErr::Unknown(format!("{:?}", e)));
+        }
+        return Ok(Self(shader.unwrap()));
+    }
+
+    /// get raw reference to `ID3D11DomainShader` instance.
+    pub fn as_raw_ref(&self) -> &ID3D11DomainShader {
+        return &self.0;
+    }
+}
@@ -12,12 +12,21 @@
 ///     src_name: "path/to/shader/source/file"  // [optional] required for #include if any.
 ///                                             // This is auto generated when src_file is used.
 ///     defines: {                      // [optional] used to define shader macros before compiling
-///         ("DEFINE_1","32"),
+///         ["DEFINE_1","32"],
+///     },
+///     include_dirs: ["path/to/shared/hlsl"], // [optional] searched for #include after the
+///                                            // directory of the including file
+///     includes: {                     // [optional] in-memory files for #include, searched first
+///         "colors.hlsli": "float3 to_linear(float3 c) { return pow(c, 2.2); }",
 ///     },
 ///     flags1: 0,                      // [optional] flags1
 ///     flags2: 0,                      // [optional] flags2
 ///     secondary_data_flags: 0         // [optional] secondary_data_flags
 ///     secondary_data: ""              // [optional] secondary_data
+///     precompiled: "path/to/blob.cso" // [optional] blob to embed on hosts without the compiler
+///     reflect: false                  // [optional] evaluate to (bytecode, ShaderReflection)
+///     compiler: "fxc"                 // [optional] "dxc" compiles shader model 6 to DXIL
+///     args: ["-enable-16bit-types"]   // [optional] extra DXC arguments, only with "dxc"
 /// }
 ///
 /// ```
@@ -57,6 +66,20 @@ macro_rules! compile_shader {
 ///             generate_shader!(fn_name ps {...})
 /// ```
 ///
+/// supported shader kinds and the struct returned by the generated function:
+///
+/// | kind | shader stage | returns                                         |
+/// |------|--------------|-------------------------------------------------|
+/// | `vs` | vertex       | [VertexShader][crate::shader::VertexShader]     |
+/// | `ps` | pixel        | [PixelShader][crate::shader::PixelShader]       |
+/// | `cs` | compute      | [ComputeShader][crate::shader::ComputeShader]   |
+/// | `gs` | geometry     | [GeometryShader][crate::shader::GeometryShader] |
+/// | `hs` | hull         | [HullShader][crate::shader::HullShader]         |
+/// | `ds` | domain       | [DomainShader][crate::shader::DomainShader]     |
+///
+/// To compile a shader once per combination of defines and choose one at runtime, see
+/// [generate_shader_variants!][crate::generate_shader_variants].
+///
 /// ## Example Usage:
 ///
 /// ```rust
@@ -106,5 +129,45 @@ macro_rules! generate_shader {
             unimplemented!()
         }
     };
+    ($name:ident cs $content: tt) => {
+        fn $name (device: $crate::ID3D11Device4) -> $crate::Result<$crate::shader::ComputeShader> {
+            #[cfg(not(doc))]
+            {
+                $crate::shader::ComputeShader::new(&$crate::compile_shader! $content, device)
+            }
+            #[cfg(doc)]
+            unimplemented!()
+        }
+    };
+    ($name:ident gs $content: tt) => {
+        fn $name (device: $crate::ID3D11Device4) -> $crate::Result<$crate::shader::GeometryShader> {
+            #[cfg(not(doc))]
+            {
+                $crate::shader::GeometryShader::new(&$crate::compile_shader! $content, device)
+            }
+            #[cfg(doc)]
+            unimplemented!()
+        }
+    };
+    ($name:ident hs $content: tt) => {
+        fn $name (device: $crate::ID3D11Device4) -> $crate::Result<$crate::shader::HullShader> {
+            #[cfg(not(doc))]
+            {
+                $crate::shader::HullShader::new(&$crate::compile_shader! $content, device)
+            }
+            #[cfg(doc)]
+            unimplemented!()
+        }
+    };
+    ($name:ident ds $content: tt) => {
+        fn $name (device: $crate::ID3D11Device4) -> $crate::Result<$crate::shader::DomainShader> {
+            #[cfg(not(doc))]
+            {
+                $crate::shader::DomainShader::new(&$crate::compile_shader! $content, device)
+            }
+            #[cfg(doc)]
+            unimplemented!()
+        }
+    };
 }
 
@@ -2,7 +2,7 @@
 //! should not need these methods. However, for simple applications, these will be helpful
 
 use win_desktop_duplication::texture::{ColorFormat, Texture, TextureDesc};
-use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_FLAG, D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_RESOURCE_MISC_FLAG, D3D11_SDK_VERSION, D3D11_SUBRESOURCE_DATA, D3D11_TEXTURE2D_DESC, D3D11_USAGE, D3D11_USAGE_DEFAULT, D3D11CreateDevice, ID3D11Device4, ID3D11DeviceContext4};
+use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_FLAG, D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_BIND_UNORDERED_ACCESS, D3D11_RESOURCE_MISC_FLAG, D3D11_SDK_VERSION, D3D11_SUBRESOURCE_DATA, D3D11_TEXTURE2D_DESC, D3D11_USAGE, D3D11_USAGE_DEFAULT, D3D11CreateDevice, ID3D11Device4, ID3D11DeviceContext4};
 use windows::Win32::Graphics::Direct3D::{D3D_DRIVER_TYPE_UNKNOWN, D3D_FEATURE_LEVEL_11_1};
 use windows::Win32::Graphics::Dxgi::Common::DXGI_SAMPLE_DESC;
 use windows::core::Interface;
@@ -26,6 +26,21 @@ pub fn create_output_texture(device: &ID3D11Device4, tex_desc: TextureDesc, init
                           Default::default(), initial_data);
 }
 
+/// create new texture which can be the output of one filter and the input of the next
+pub fn create_intermediate_texture(device: &ID3D11Device4, tex_desc: TextureDesc, initial_data: Option<Vec<u8>>) -> Result<Texture> {
+    return create_texture(device, tex_desc,
+                          D3D11_USAGE_DEFAULT, D3D11_BIND_FLAG(D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_SHADER_RESOURCE.0),
+                          Default::default(), initial_data);
+}
+
+/// create new output texture which filters can also write from compute shaders, e.g.
+/// [NV12Backend::Compute][crate::NV12Backend::Compute]
+pub fn create_uav_output_texture(device: &ID3D11Device4, tex_desc: TextureDesc, initial_data: Option<Vec<u8>>) -> Result<Texture> {
+    return create_texture(device, tex_desc,
+                          D3D11_USAGE_DEFAULT, D3D11_BIND_FLAG(D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_UNORDERED_ACCESS.0),
+                          Default::default(), initial_data);
+}
+
 /// create directx device and context from given adapter
 pub fn create_device_context(adapter: &Adapter) -> Result<(ID3D11Device4, ID3D11DeviceContext4)> {
     let feature_levels = [D3D_FEATURE_LEVEL_11_1];
@@ -49,8 +64,8 @@ pub fn create_device_context(adapter: &Adapter) -> Result<(ID3D11Device4, ID3D11
     return Ok((device, ctx));
 }
 
-fn create_texture(device: &ID3D11Device4, tex_desc: TextureDesc, usage: D3D11_USAGE, bind_flags: D3D11_BIND_FLAG,
-                  misc_flag: D3D11_RESOURCE_MISC_FLAG, initial_data: Option<Vec<u8>>) -> Result<Texture> {
+pub(crate) fn create_texture(device: &ID3D11Device4, tex_desc: TextureDesc, usage: D3D11_USAGE, bind_flags: D3D11_BIND_FLAG,
+                             misc_flag: D3D11_RESOURCE_MISC_FLAG, initial_data: Option<Vec<u8>>) -> Result<Texture> {
     let desc = D3D11_TEXTURE2D_DESC {
         Width: tex_desc.width,
         Height: tex_desc.height,
@@ -86,8 +101,8 @@ fn create_texture(device: &ID3D11Device4, tex_desc: TextureDesc, usage: D3D11_US
         }
     }?;
     let mut srd =D3D11_SUBRESOURCE_DATA::default();
-    let init_img = if initial_data.is_some() {
-        srd.pSysMem = initial_data.unwrap().as_ptr() as _;
+    let init_img = if let Some(data) = &initial_data {
+        srd.pSysMem = data.as_ptr() as _;
         srd.SysMemPitch = pitch;
         srd.SysMemSlicePitch = 0;
         Some(&srd as _)